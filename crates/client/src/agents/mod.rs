@@ -1,21 +1,58 @@
-use bevy::prelude::*;
-use common::agents::{Agent, InitializeAgent};
+use bevy::{color::palettes::css, prelude::*};
+use common::agents::{Agent, AgentSuspicionMeter, SuspicionLevel};
 
-use crate::{
-    networking::params::ClientMessages, physics_replication::SnapshotInterpolation,
-    server_entity_map::LocalServerEntity,
-};
+use crate::{camera::MainCamera, physics_replication::SnapshotInterpolation};
+
+/// How high above an agent's origin their suspicion meter is drawn.
+const SUSPICION_METER_HEIGHT: f32 = 2.2;
+/// The width of a full suspicion meter.
+const SUSPICION_METER_WIDTH: f32 = 0.8;
 
 pub fn build(app: &mut App) {
-    app.add_systems(Update, initialize_agents);
+    app.add_systems(Update, (init_agents, draw_suspicion_meters));
 }
 
-fn initialize_agents(mut commands: Commands, mut messages: ClientMessages<InitializeAgent>) {
-    for InitializeAgent { entity } in messages.drain() {
-        commands.spawn((
-            LocalServerEntity(entity),
-            Agent,
-            SnapshotInterpolation::default(),
-        ));
+fn init_agents(mut commands: Commands, agent_q: Query<Entity, Added<Agent>>) {
+    for agent_entity in agent_q.iter() {
+        commands
+            .entity(agent_entity)
+            .insert(SnapshotInterpolation::default());
+    }
+}
+
+/// Draws a bar above each suspicious agent that fills up and changes colour as they become more suspicious.
+fn draw_suspicion_meters(
+    mut gizmos: Gizmos,
+    agent_q: Query<(&Transform, &AgentSuspicionMeter), With<Agent>>,
+    camera_q: Query<&GlobalTransform, With<MainCamera>>,
+) -> Result {
+    let camera_transform = camera_q.single()?;
+
+    for (agent_transform, &meter) in agent_q.iter() {
+        let suspicion = meter.suspicion();
+
+        if suspicion <= 0. {
+            continue;
+        }
+
+        let color = match meter.level() {
+            SuspicionLevel::Calm => css::WHITE,
+            SuspicionLevel::Curious => css::YELLOW,
+            SuspicionLevel::Investigating => css::ORANGE,
+            SuspicionLevel::Alarmed => css::RED,
+        };
+
+        let center = agent_transform.translation + Vec3::Y * SUSPICION_METER_HEIGHT;
+        // face the bar towards the camera
+        let right = camera_transform.right() * SUSPICION_METER_WIDTH / 2.;
+
+        gizmos.line(center - right, center + right, css::DIM_GRAY);
+        gizmos.line(
+            center - right,
+            center - right + right * 2. * suspicion,
+            color,
+        );
     }
+
+    Ok(())
 }
@@ -0,0 +1,71 @@
+//! The alarm state as last sent by the server, for the HUD and audio cues.
+//!
+//! Doors locked by a lockdown are drawn so the crew can see which ways are shut.
+
+use std::time::Duration;
+
+use bevy::{color::palettes::css, prelude::*};
+use common::{
+    alarm::{AlarmLevel, SetAlarmState},
+    elements::door::{DOOR_SIZE, LockedDoor},
+};
+
+use crate::networking::params::ClientMessages;
+
+pub fn build(app: &mut App) {
+    app.init_resource::<Alarm>();
+
+    app.add_systems(Update, (receive_alarm_state, draw_locked_doors));
+}
+
+#[derive(Resource, Default)]
+pub struct Alarm {
+    pub level: AlarmLevel,
+    /// When the police arrive, measured in [Time] elapsed.
+    pub police_arrive_at: Option<Duration>,
+}
+
+impl Alarm {
+    /// How long until the police arrive, if they are on their way.
+    pub fn police_arrive_in(&self, time: &Time) -> Option<Duration> {
+        self.police_arrive_at
+            .map(|arrive_at| arrive_at.saturating_sub(time.elapsed()))
+    }
+}
+
+fn receive_alarm_state(
+    mut messages: ClientMessages<SetAlarmState>,
+    mut alarm: ResMut<Alarm>,
+    time: Res<Time>,
+) {
+    for SetAlarmState {
+        level,
+        police_arrive_in,
+    } in messages.drain()
+    {
+        if level != alarm.level {
+            info!("The alarm is now {:?}", level);
+        }
+
+        if let Some(police_arrive_in) = police_arrive_in {
+            debug!(
+                "The police arrive in {:.0} seconds",
+                police_arrive_in.as_secs_f32()
+            );
+        }
+
+        alarm.level = level;
+        alarm.police_arrive_at = police_arrive_in.map(|arrive_in| time.elapsed() + arrive_in);
+    }
+}
+
+fn draw_locked_doors(mut gizmos: Gizmos, door_q: Query<&LockedDoor>) {
+    for door in door_q.iter() {
+        gizmos.cuboid(
+            Transform::from_translation(door.position + door.rotation * Vec3::Y * DOOR_SIZE.y / 2.)
+                .with_rotation(door.rotation)
+                .with_scale(DOOR_SIZE),
+            css::RED,
+        );
+    }
+}
@@ -1,15 +1,12 @@
-use avian3d::prelude::*;
 use bevy::{input::mouse::MouseMotion, prelude::*};
-use common::{GameLayer, character::controller::CharacterInput};
+use common::character::controller::CharacterInput;
 
 use crate::{character::LocalPlayer, input::ControlScheme};
 
 const MAX_VERTICAL_CAMERA_ANGLE: f32 = std::f32::consts::FRAC_PI_2 * 0.9;
-const ON_GROUND_TOLERANCE: f32 = 0.02;
-pub const PLAYER_JUMP_SPEED: f32 = 3.;
 
 pub fn build(app: &mut App) {
-    app.add_systems(Update, (get_movement_input, get_camera_input, jump_players));
+    app.add_systems(Update, (get_movement_input, get_camera_input));
 }
 
 fn get_movement_input(
@@ -25,6 +22,9 @@ fn get_movement_input(
     character_input.move_backward = input.pressed(controls.move_backward);
     character_input.move_left = input.pressed(controls.move_left);
     character_input.move_right = input.pressed(controls.move_right);
+    character_input.crouch = input.pressed(controls.crouch);
+    // latched until the next fixed update simulates it, so holding jump doesn't keep jumping
+    character_input.jump |= input.just_pressed(controls.jump);
 }
 
 fn get_camera_input(
@@ -48,28 +48,3 @@ fn get_camera_input(
     )
     .unwrap();
 }
-
-fn jump_players(
-    controls: Res<ControlScheme>,
-    input: Res<ButtonInput<KeyCode>>,
-    mut player_q: Query<(&Position, &mut LinearVelocity), With<LocalPlayer>>,
-    spatial_query: SpatialQuery,
-) {
-    let Ok((&Position(position), mut velocity)) = player_q.single_mut() else {
-        return;
-    };
-
-    let on_ground = spatial_query
-        .cast_ray(
-            position,
-            Dir3::NEG_Y,
-            ON_GROUND_TOLERANCE,
-            true,
-            &SpatialQueryFilter::from_mask([GameLayer::World]),
-        )
-        .is_some();
-
-    if on_ground && input.just_pressed(controls.jump) {
-        velocity.0 += Vec3::Y * PLAYER_JUMP_SPEED;
-    }
-}
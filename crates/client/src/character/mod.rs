@@ -1,32 +1,41 @@
-use std::time::Duration;
+use std::collections::VecDeque;
 
 use avian3d::prelude::*;
 use bevy::prelude::*;
 use common::character::{
-    Character, CharacterStateUpdate, SetLocalPlayer, SpawnCharacter,
-    controller::{CharacterController, CharacterInput},
+    Character, CharacterStateAck, CharacterStateUpdate, SetLocalPlayer,
+    controller::{CharacterController, CharacterInput, CharacterSimulation, CharacterState},
 };
 use nevy::*;
 
 use crate::{
     networking::params::{ClientMessages, LocalClientMessageSender},
     physics_replication::SnapshotInterpolation,
-    server_entity_map::{LocalServerEntity, ServerEntityMap},
+    replication::ReceiveComponentUpdates,
+    server_entity_map::ServerEntityMap,
 };
 
 pub mod controls;
 
-const PLAYER_UPDATE_INTERVAL: Duration = Duration::from_millis(100);
+/// How many unacknowledged steps to remember for reconciliation.
+const PREDICTION_HISTORY_LENGTH: usize = 128;
+/// How far the predicted position can be from the server's before it is reconciled.
+const RECONCILE_TOLERANCE: f32 = 0.01;
 
 pub fn build(app: &mut App) {
     controls::build(app);
 
     app.add_systems(
         Update,
-        (
-            send_character_updates,
-            (initialize_players, set_local_player).chain(),
-        ),
+        (init_characters, set_local_player)
+            .chain()
+            .after(ReceiveComponentUpdates),
+    );
+
+    app.add_systems(FixedPreUpdate, reconcile_local_player);
+    app.add_systems(
+        FixedPostUpdate,
+        record_predicted_steps.after(PhysicsSet::StepSimulation),
     );
 }
 
@@ -34,15 +43,27 @@ pub fn build(app: &mut App) {
 #[derive(Component)]
 pub struct LocalPlayer;
 
-fn initialize_players(mut commands: Commands, mut messages: ClientMessages<SpawnCharacter>) {
-    for SpawnCharacter { server_entity } in messages.drain() {
-        debug!("Spawed character {}", server_entity);
+/// A ring buffer of the local player's predicted steps that the server hasn't acknowledged yet.
+#[derive(Component, Default)]
+pub struct PredictionHistory {
+    next_sequence: u32,
+    steps: VecDeque<PredictedStep>,
+}
+
+struct PredictedStep {
+    sequence: u32,
+    input: CharacterInput,
+    /// The predicted state after simulating this step.
+    state: CharacterState,
+}
+
+fn init_characters(mut commands: Commands, character_q: Query<Entity, Added<Character>>) {
+    for character_entity in character_q.iter() {
+        debug!("Spawed character {}", character_entity);
 
-        commands.spawn((
-            LocalServerEntity(server_entity),
-            Character,
-            SnapshotInterpolation::default(),
-        ));
+        commands
+            .entity(character_entity)
+            .insert(SnapshotInterpolation::default());
     }
 }
 
@@ -62,37 +83,137 @@ fn set_local_player(
         commands
             .entity(character_entity)
             .remove::<SnapshotInterpolation>()
-            .insert((LocalPlayer, CharacterController));
+            .insert((
+                LocalPlayer,
+                CharacterController,
+                PredictionHistory::default(),
+            ));
     }
 }
 
-fn send_character_updates(
-    character_q: Query<(&Position, &LinearVelocity, &Rotation, &CharacterInput), With<LocalPlayer>>,
+/// Records the step the local player just simulated and sends it's input to the server.
+///
+/// Clears the jump input once it has been simulated.
+fn record_predicted_steps(
+    mut character_q: Query<
+        (
+            &mut PredictionHistory,
+            &mut CharacterInput,
+            &Position,
+            &LinearVelocity,
+        ),
+        With<LocalPlayer>,
+    >,
     mut messages: LocalClientMessageSender,
     message_id: Res<MessageId<CharacterStateUpdate>>,
+) -> Result {
+    let Ok((mut history, mut character_input, position, velocity)) = character_q.single_mut()
+    else {
+        return Ok(());
+    };
+
+    let input = *character_input;
+    character_input.jump = false;
+
+    let sequence = history.next_sequence;
+    history.next_sequence = sequence.wrapping_add(1);
+
+    history.steps.push_back(PredictedStep {
+        sequence,
+        input,
+        state: CharacterState {
+            position: position.0,
+            velocity: velocity.0,
+        },
+    });
+
+    if history.steps.len() > PREDICTION_HISTORY_LENGTH {
+        history.steps.pop_front();
+    }
+
+    messages.write(*message_id, true, &CharacterStateUpdate { sequence, input })?;
+
+    Ok(())
+}
+
+/// Compares the server's state of the local player with what was predicted.
+///
+/// If they disagree the local player is rewound to the server's state
+/// and every step the server hasn't simulated yet is replayed.
+fn reconcile_local_player(
+    mut messages: ClientMessages<CharacterStateAck>,
+    mut history_q: Query<
+        (Entity, &mut PredictionHistory, &Collider, &CollisionLayers),
+        With<LocalPlayer>,
+    >,
+    mut character_params: ParamSet<(
+        CharacterSimulation,
+        Query<(&mut Position, &mut LinearVelocity), With<LocalPlayer>>,
+    )>,
     time: Res<Time>,
-    mut last_update: Local<Duration>,
 ) -> Result {
-    if time.elapsed() - *last_update > PLAYER_UPDATE_INTERVAL {
-        *last_update = time.elapsed();
+    let Some(ack) = messages.drain().max_by_key(|ack| ack.sequence) else {
+        return Ok(());
+    };
+
+    let Ok((character_entity, mut history, collider, collision_layers)) = history_q.single_mut()
+    else {
+        return Ok(());
+    };
+
+    // forget steps the server has already simulated past
+    while history
+        .steps
+        .front()
+        .is_some_and(|step| step.sequence < ack.sequence)
+    {
+        history.steps.pop_front();
+    }
 
-        let Ok((&Position(position), &LinearVelocity(velocity), &Rotation(rotation), &input)) =
-            character_q.single()
-        else {
-            return Ok(());
-        };
+    let Some(acked_step) = history.steps.pop_front() else {
+        return Ok(());
+    };
+
+    if acked_step.sequence != ack.sequence {
+        // the ack is older than anything remembered
+        history.steps.push_front(acked_step);
+        return Ok(());
+    }
+
+    if acked_step.state.position.distance(ack.position) <= RECONCILE_TOLERANCE {
+        return Ok(());
+    }
+
+    debug!(
+        "Reconciling local player, prediction was off by {}",
+        acked_step.state.position.distance(ack.position)
+    );
+
+    let mut state = CharacterState {
+        position: ack.position,
+        velocity: ack.velocity,
+    };
 
-        messages.write(
-            *message_id,
-            false,
-            &CharacterStateUpdate {
-                position,
-                velocity,
-                rotation,
-                input,
-            },
-        )?;
+    let simulation = character_params.p0();
+
+    for step in history.steps.iter_mut() {
+        state = simulation.step(
+            character_entity,
+            collider,
+            collision_layers,
+            &step.input,
+            state,
+            time.delta_secs(),
+        );
+
+        step.state = state;
     }
 
+    let mut character_q = character_params.p1();
+    let (mut position, mut velocity) = character_q.single_mut()?;
+
+    position.0 = state.position;
+    velocity.0 = state.velocity;
+
     Ok(())
 }
@@ -0,0 +1,180 @@
+//! Estimates the server's clock.
+//!
+//! The client regularly pings the server and uses the round trip time of each ping
+//! to work out how far ahead the server's clock is, the same way as NTP.
+
+use std::{collections::VecDeque, time::Duration};
+
+use bevy::prelude::*;
+use common::clock::{TimePing, TimePong};
+use nevy::*;
+
+use crate::networking::{
+    ClientConnection,
+    params::{ClientMessages, LocalClientMessageSender},
+};
+
+const PING_INTERVAL: Duration = Duration::from_millis(100);
+/// How many of the latest samples to estimate the clock from.
+const CLOCK_SAMPLES: usize = 16;
+/// Samples with a round trip time this many jitters above the median are ignored.
+///
+/// Their offset is less accurate because the time spent in each direction is less likely to be equal.
+const OUTLIER_JITTERS: f64 = 2.;
+/// How many seconds per second the smoothed clock can be corrected by.
+const CLOCK_SLEW_RATE: f64 = 0.05;
+/// If the smoothed clock is off by more than this many seconds it is snapped instead of corrected gradually.
+const CLOCK_SNAP_THRESHOLD: f64 = 0.25;
+
+pub fn build(app: &mut App) {
+    app.init_resource::<ServerClock>();
+
+    app.add_systems(
+        Update,
+        (send_pings, receive_pongs, update_server_clock)
+            .chain()
+            .in_set(UpdateServerClock),
+    );
+}
+
+/// System set in [Update] that updates the [ServerClock].
+#[derive(SystemSet, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct UpdateServerClock;
+
+/// The client's estimate of the server's clock.
+#[derive(Resource, Default)]
+pub struct ServerClock {
+    samples: VecDeque<ClockSample>,
+    /// The measured server time minus local time in seconds.
+    target_offset: Option<f64>,
+    /// The smoothed server time minus local time in seconds.
+    offset: f64,
+    rtt: Duration,
+    jitter: Duration,
+    server_time: Duration,
+}
+
+#[derive(Clone, Copy)]
+struct ClockSample {
+    rtt: f64,
+    offset: f64,
+}
+
+impl ServerClock {
+    /// Whether any pings have been answered yet.
+    pub fn is_synchronized(&self) -> bool {
+        self.target_offset.is_some()
+    }
+
+    /// The median round trip time to the server.
+    pub fn rtt(&self) -> Duration {
+        self.rtt
+    }
+
+    /// The average deviation of the round trip time from the median.
+    pub fn jitter(&self) -> Duration {
+        self.jitter
+    }
+
+    /// The smoothed estimate of the server's time this frame.
+    pub fn server_time(&self) -> Duration {
+        self.server_time
+    }
+
+    fn add_sample(&mut self, sample: ClockSample) {
+        self.samples.push_back(sample);
+
+        if self.samples.len() > CLOCK_SAMPLES {
+            self.samples.pop_front();
+        }
+
+        let mut rtts: Vec<f64> = self.samples.iter().map(|sample| sample.rtt).collect();
+        rtts.sort_by(f64::total_cmp);
+
+        let median_rtt = rtts[rtts.len() / 2];
+        let jitter =
+            rtts.iter().map(|rtt| (rtt - median_rtt).abs()).sum::<f64>() / rtts.len() as f64;
+
+        let accepted: Vec<f64> = self
+            .samples
+            .iter()
+            .filter(|sample| sample.rtt <= median_rtt + jitter * OUTLIER_JITTERS)
+            .map(|sample| sample.offset)
+            .collect();
+
+        self.rtt = Duration::from_secs_f64(median_rtt);
+        self.jitter = Duration::from_secs_f64(jitter);
+        // the sample with the median rtt is always accepted
+        self.target_offset = Some(accepted.iter().sum::<f64>() / accepted.len() as f64);
+    }
+}
+
+fn send_pings(
+    connection_q: Query<&ConnectionStatus, With<ClientConnection>>,
+    mut messages: LocalClientMessageSender,
+    message_id: Res<MessageId<TimePing>>,
+    time: Res<Time<Real>>,
+    mut last_ping: Local<Duration>,
+) -> Result {
+    let Ok(ConnectionStatus::Established) = connection_q.single() else {
+        return Ok(());
+    };
+
+    if time.elapsed() < *last_ping + PING_INTERVAL {
+        return Ok(());
+    }
+
+    *last_ping = time.elapsed();
+
+    // if out of bandwidth don't send
+    messages.write(
+        *message_id,
+        false,
+        &TimePing {
+            client_time: time.elapsed(),
+        },
+    )?;
+
+    Ok(())
+}
+
+fn receive_pongs(
+    mut messages: ClientMessages<TimePong>,
+    mut clock: ResMut<ServerClock>,
+    time: Res<Time<Real>>,
+) {
+    for TimePong {
+        client_time,
+        server_time,
+    } in messages.drain()
+    {
+        let Some(rtt) = time.elapsed().checked_sub(client_time) else {
+            warn!("Received a pong from the future");
+            continue;
+        };
+
+        let rtt = rtt.as_secs_f64();
+
+        // assume the pong took half the round trip to arrive
+        let offset = server_time.as_secs_f64() + rtt / 2. - time.elapsed().as_secs_f64();
+
+        clock.add_sample(ClockSample { rtt, offset });
+    }
+}
+
+fn update_server_clock(mut clock: ResMut<ServerClock>, time: Res<Time<Real>>) {
+    let Some(target_offset) = clock.target_offset else {
+        return;
+    };
+
+    let difference = target_offset - clock.offset;
+
+    if difference.abs() > CLOCK_SNAP_THRESHOLD {
+        clock.offset = target_offset;
+    } else {
+        let max_correction = CLOCK_SLEW_RATE * time.delta_secs_f64();
+        clock.offset += difference.clamp(-max_correction, max_correction);
+    }
+
+    clock.server_time = Duration::from_secs_f64((time.elapsed_secs_f64() + clock.offset).max(0.));
+}
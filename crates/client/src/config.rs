@@ -0,0 +1,80 @@
+use std::{net::SocketAddr, path::PathBuf};
+
+use bevy::prelude::*;
+use common::{networking::fingerprint::CertificateFingerprint, state::is_valid_username};
+
+#[derive(Resource)]
+pub struct ClientConfig {
+    pub server_address: SocketAddr,
+    /// The name the server's certificate is expected to be for.
+    pub server_name: String,
+    pub verification: ServerVerification,
+    pub username: String,
+    /// The password of a private server.
+    pub password: Option<String>,
+}
+
+/// How the client verifies the server's certificate.
+pub enum ServerVerification {
+    /// Only trust a certificate with this fingerprint, used for self signed certificates.
+    Fingerprint(CertificateFingerprint),
+    /// Trust certificates issued by the certificate authorities in this PEM file.
+    CertificateAuthorities(PathBuf),
+}
+
+impl ClientConfig {
+    /// Loads the config from command line arguments.
+    ///
+    /// `client <server address> (--fingerprint <hex> | --ca <path>) [--server-name <name>] [--username <name>] [--password <password>]`
+    pub fn load() -> Result<Self> {
+        let mut args = std::env::args().skip(1);
+
+        let server_address = args
+            .next()
+            .ok_or("Expected server address as first argument")?;
+
+        let server_address = server_address
+            .parse()
+            .map_err(|_| "Invalid server address")?;
+
+        let mut server_name = "localhost".to_string();
+        let mut verification = None;
+        let mut username = "Player".to_string();
+        let mut password = None;
+
+        while let Some(arg) = args.next() {
+            let value = args
+                .next()
+                .ok_or_else(|| format!("Expected a value after \"{}\"", arg))?;
+
+            match arg.as_str() {
+                "--fingerprint" => {
+                    verification = Some(ServerVerification::Fingerprint(value.parse()?));
+                }
+                "--ca" => {
+                    verification = Some(ServerVerification::CertificateAuthorities(value.into()))
+                }
+                "--server-name" => server_name = value,
+                "--username" => username = value,
+                "--password" => password = Some(value),
+                _ => return Err(format!("Unknown argument \"{}\"", arg).into()),
+            }
+        }
+
+        let verification = verification.ok_or(
+            "Expected either \"--fingerprint\" of the server's certificate or a \"--ca\" file to verify it with",
+        )?;
+
+        if !is_valid_username(&username) {
+            return Err(format!("Invalid username \"{}\"", username).into());
+        }
+
+        Ok(ClientConfig {
+            server_address,
+            server_name,
+            verification,
+            username,
+            password,
+        })
+    }
+}
@@ -11,6 +11,10 @@ pub struct ControlScheme {
     pub move_left: KeyCode,
     pub move_right: KeyCode,
     pub jump: KeyCode,
+    pub crouch: KeyCode,
+
+    pub toggle_ready: KeyCode,
+    pub start_match: KeyCode,
 
     pub mouse_sensitivity: Vec2,
 }
@@ -23,6 +27,10 @@ impl Default for ControlScheme {
             move_left: KeyCode::KeyD,
             move_right: KeyCode::KeyA,
             jump: KeyCode::Space,
+            crouch: KeyCode::ControlLeft,
+
+            toggle_ready: KeyCode::KeyR,
+            start_match: KeyCode::Enter,
 
             mouse_sensitivity: Vec2::splat(0.002),
         }
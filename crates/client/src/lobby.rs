@@ -0,0 +1,85 @@
+use bevy::prelude::*;
+use common::match_state::{LobbyPlayer, LobbyPlayers, SetReady, StartMatch};
+use nevy::*;
+
+use crate::{
+    input::ControlScheme,
+    networking::params::{ClientMessages, LocalClientMessageSender},
+    state::ClientState,
+};
+
+pub fn build(app: &mut App) {
+    app.init_resource::<Lobby>();
+
+    app.add_systems(
+        Update,
+        (
+            receive_lobby_players,
+            (toggle_ready, start_match).run_if(in_state(ClientState::Lobby)),
+        )
+            .chain(),
+    );
+
+    // the server unreadies everyone when returning to the lobby
+    app.add_systems(OnEnter(ClientState::Lobby), reset_ready);
+}
+
+/// The players in the lobby as last sent by the server.
+#[derive(Resource, Default)]
+pub struct Lobby {
+    pub players: Vec<LobbyPlayer>,
+    /// Whether the local player is ready.
+    pub ready: bool,
+}
+
+fn receive_lobby_players(mut messages: ClientMessages<LobbyPlayers>, mut lobby: ResMut<Lobby>) {
+    for LobbyPlayers { players } in messages.drain() {
+        for player in players.iter() {
+            debug!(
+                "Lobby player \"{}\"{}{}",
+                player.username,
+                if player.ready { " ready" } else { "" },
+                if player.host { " (host)" } else { "" },
+            );
+        }
+
+        lobby.players = players;
+    }
+}
+
+fn toggle_ready(
+    controls: Res<ControlScheme>,
+    input: Res<ButtonInput<KeyCode>>,
+    mut lobby: ResMut<Lobby>,
+    mut messages: LocalClientMessageSender,
+    message_id: Res<MessageId<SetReady>>,
+) -> Result {
+    if !input.just_pressed(controls.toggle_ready) {
+        return Ok(());
+    }
+
+    lobby.ready = !lobby.ready;
+
+    messages.write(*message_id, true, &SetReady { ready: lobby.ready })?;
+
+    Ok(())
+}
+
+fn start_match(
+    controls: Res<ControlScheme>,
+    input: Res<ButtonInput<KeyCode>>,
+    mut messages: LocalClientMessageSender,
+    message_id: Res<MessageId<StartMatch>>,
+) -> Result {
+    if !input.just_pressed(controls.start_match) {
+        return Ok(());
+    }
+
+    messages.write(*message_id, true, &StartMatch)?;
+
+    Ok(())
+}
+
+fn reset_ready(mut lobby: ResMut<Lobby>) {
+    lobby.ready = false;
+}
@@ -2,21 +2,35 @@ use avian3d::prelude::*;
 use bevy::prelude::*;
 use common::CommonPlugin;
 
-use crate::networking::ClientConnection;
+use crate::config::ClientConfig;
 
 pub mod agents;
+pub mod alarm;
 pub mod camera;
 pub mod character;
-pub mod elements;
+pub mod clock;
+pub mod config;
 pub mod input;
+pub mod lobby;
 pub mod networking;
 pub mod physics_replication;
+pub mod replication;
 pub mod server_entity_map;
 pub mod state;
 
 fn main() {
     let mut app = App::new();
 
+    match ClientConfig::load() {
+        Ok(config) => {
+            app.insert_resource(config);
+        }
+        Err(err) => {
+            println!("Failed to load client config: {}", err);
+            return;
+        }
+    }
+
     app.add_plugins(DefaultPlugins.set(bevy::log::LogPlugin {
         level: bevy::log::Level::DEBUG,
         filter: bevy::log::DEFAULT_FILTER.to_string()
@@ -33,13 +47,16 @@ fn main() {
 
     networking::build(&mut app);
     state::build(&mut app);
+    clock::build(&mut app);
     server_entity_map::build(&mut app);
+    replication::build(&mut app);
     physics_replication::build(&mut app);
     input::build(&mut app);
+    lobby::build(&mut app);
     character::build(&mut app);
     camera::build(&mut app);
-    elements::build(&mut app);
     agents::build(&mut app);
+    alarm::build(&mut app);
 
     app.add_systems(PostStartup, debug_connect_to_server);
 
@@ -49,24 +66,9 @@ fn main() {
 fn debug_connect_to_server(
     mut commands: Commands,
     endpoint_q: Query<Entity, With<networking::ClientEndpoint>>,
+    config: Res<ClientConfig>,
 ) -> Result {
     let endpoint_entity = endpoint_q.single()?;
 
-    let address = std::env::args()
-        .nth(1)
-        .expect("Expected server address as first argument")
-        .parse()
-        .expect("Invalid server address");
-
-    commands.spawn((
-        ClientConnection,
-        nevy::ConnectionOf(endpoint_entity),
-        nevy::QuicConnectionConfig {
-            client_config: networking::create_connection_config(),
-            address,
-            server_name: "example.server".to_string(),
-        },
-    ));
-
-    Ok(())
+    networking::connect_to_server(&mut commands, endpoint_entity, &config)
 }
@@ -1,5 +1,9 @@
 use bevy::prelude::*;
+use common::networking::fingerprint::CertificateFingerprint;
 use nevy::*;
+use rustls::pki_types::{CertificateDer, pem::PemObject};
+
+use crate::config::{ClientConfig, ServerVerification};
 
 pub mod params;
 
@@ -29,74 +33,58 @@ fn spawn_endpoint(mut commands: Commands) -> Result {
     Ok(())
 }
 
-pub fn create_connection_config() -> nevy::quinn_proto::ClientConfig {
-    // some day I need to figure out how to do tls properly
-    // someone help me
-
-    #[derive(Debug)]
-    struct AlwaysVerify;
-
-    impl rustls::client::danger::ServerCertVerifier for AlwaysVerify {
-        fn verify_server_cert(
-            &self,
-            _end_entity: &rustls::pki_types::CertificateDer<'_>,
-            _intermediates: &[rustls::pki_types::CertificateDer<'_>],
-            _server_name: &rustls::pki_types::ServerName<'_>,
-            _ocsp_response: &[u8],
-            _now: rustls::pki_types::UnixTime,
-        ) -> Result<rustls::client::danger::ServerCertVerified, rustls::Error> {
-            Ok(rustls::client::danger::ServerCertVerified::assertion())
-        }
-
-        fn verify_tls12_signature(
-            &self,
-            _message: &[u8],
-            _cert: &rustls::pki_types::CertificateDer<'_>,
-            _dss: &rustls::DigitallySignedStruct,
-        ) -> Result<rustls::client::danger::HandshakeSignatureValid, rustls::Error> {
-            Ok(rustls::client::danger::HandshakeSignatureValid::assertion())
-        }
+/// Spawns a new connection to the server in the [ClientConfig].
+pub fn connect_to_server(
+    commands: &mut Commands,
+    endpoint_entity: Entity,
+    config: &ClientConfig,
+) -> Result {
+    commands.spawn((
+        ClientConnection,
+        ConnectionOf(endpoint_entity),
+        QuicConnectionConfig {
+            client_config: create_connection_config(&config.verification)?,
+            address: config.server_address,
+            server_name: config.server_name.clone(),
+        },
+    ));
 
-        fn verify_tls13_signature(
-            &self,
-            _message: &[u8],
-            _cert: &rustls::pki_types::CertificateDer<'_>,
-            _dss: &rustls::DigitallySignedStruct,
-        ) -> Result<rustls::client::danger::HandshakeSignatureValid, rustls::Error> {
-            Ok(rustls::client::danger::HandshakeSignatureValid::assertion())
-        }
+    Ok(())
+}
 
-        fn supported_verify_schemes(&self) -> Vec<rustls::SignatureScheme> {
-            vec![
-                rustls::SignatureScheme::RSA_PKCS1_SHA256,
-                rustls::SignatureScheme::RSA_PKCS1_SHA512,
-                rustls::SignatureScheme::RSA_PKCS1_SHA384,
-                rustls::SignatureScheme::RSA_PKCS1_SHA1,
-                rustls::SignatureScheme::RSA_PSS_SHA256,
-                rustls::SignatureScheme::RSA_PSS_SHA384,
-                rustls::SignatureScheme::RSA_PSS_SHA512,
-                rustls::SignatureScheme::ECDSA_NISTP256_SHA256,
-                rustls::SignatureScheme::ECDSA_NISTP521_SHA512,
-                rustls::SignatureScheme::ECDSA_NISTP384_SHA384,
-                rustls::SignatureScheme::ED25519,
-                rustls::SignatureScheme::ED448,
-                rustls::SignatureScheme::ECDSA_SHA1_Legacy,
-            ]
+pub fn create_connection_config(
+    verification: &ServerVerification,
+) -> Result<nevy::quinn_proto::ClientConfig> {
+    let provider = std::sync::Arc::new(rustls::crypto::ring::default_provider());
+
+    let builder = rustls::ClientConfig::builder_with_provider(provider.clone())
+        .with_protocol_versions(&[&rustls::version::TLS13])?;
+
+    let mut tls_config = match verification {
+        ServerVerification::Fingerprint(fingerprint) => builder
+            .dangerous()
+            .with_custom_certificate_verifier(std::sync::Arc::new(PinnedFingerprintVerifier {
+                fingerprint: *fingerprint,
+                algorithms: provider.signature_verification_algorithms,
+            }))
+            .with_no_client_auth(),
+        ServerVerification::CertificateAuthorities(path) => {
+            let mut root_store = rustls::RootCertStore::empty();
+
+            for certificate in CertificateDer::pem_file_iter(path)? {
+                root_store.add(certificate?)?;
+            }
+
+            builder
+                .with_root_certificates(root_store)
+                .with_no_client_auth()
         }
-    }
+    };
 
-    let mut tls_config = rustls::ClientConfig::builder_with_provider(std::sync::Arc::new(
-        rustls::crypto::ring::default_provider(),
-    ))
-    .with_protocol_versions(&[&rustls::version::TLS13])
-    .unwrap()
-    .dangerous()
-    .with_custom_certificate_verifier(std::sync::Arc::new(AlwaysVerify))
-    .with_no_client_auth();
     tls_config.alpn_protocols = vec![b"h3".to_vec()];
 
     let quic_tls_config =
-        nevy::quinn_proto::crypto::rustls::QuicClientConfig::try_from(tls_config).unwrap();
+        nevy::quinn_proto::crypto::rustls::QuicClientConfig::try_from(tls_config)?;
     let mut quinn_client_config =
         nevy::quinn_proto::ClientConfig::new(std::sync::Arc::new(quic_tls_config));
 
@@ -105,5 +93,55 @@ pub fn create_connection_config() -> nevy::quinn_proto::ClientConfig {
     transport_config.keep_alive_interval(Some(std::time::Duration::from_millis(200)));
     quinn_client_config.transport_config(std::sync::Arc::new(transport_config));
 
-    quinn_client_config
+    Ok(quinn_client_config)
+}
+
+/// Trusts a single certificate by it's fingerprint instead of by who issued it.
+///
+/// Signatures are still verified so the server has to have the certificate's private key.
+#[derive(Debug)]
+struct PinnedFingerprintVerifier {
+    fingerprint: CertificateFingerprint,
+    algorithms: rustls::crypto::WebPkiSupportedAlgorithms,
+}
+
+impl rustls::client::danger::ServerCertVerifier for PinnedFingerprintVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &rustls::pki_types::ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: rustls::pki_types::UnixTime,
+    ) -> Result<rustls::client::danger::ServerCertVerified, rustls::Error> {
+        if CertificateFingerprint::of(end_entity) != self.fingerprint {
+            return Err(rustls::Error::InvalidCertificate(
+                rustls::CertificateError::ApplicationVerificationFailure,
+            ));
+        }
+
+        Ok(rustls::client::danger::ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &rustls::DigitallySignedStruct,
+    ) -> Result<rustls::client::danger::HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls12_signature(message, cert, dss, &self.algorithms)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &rustls::DigitallySignedStruct,
+    ) -> Result<rustls::client::danger::HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls13_signature(message, cert, dss, &self.algorithms)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<rustls::SignatureScheme> {
+        self.algorithms.supported_schemes()
+    }
 }
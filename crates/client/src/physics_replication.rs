@@ -1,74 +1,65 @@
 use std::{collections::VecDeque, time::Duration};
 
 use avian3d::prelude::*;
-use bevy::prelude::*;
-use common::physics::*;
+use bevy::{ecs::entity::EntityHashMap, prelude::*};
+use common::{ServerEntity, physics::*};
+use nevy::MessageId;
 
-use crate::{networking::params::ClientMessages, server_entity_map::ServerEntityMap};
+use crate::{
+    clock::ServerClock,
+    networking::params::{ClientMessages, LocalClientMessageSender},
+    server_entity_map::ServerEntityMap,
+    state::ClientState,
+};
 
 pub fn build(app: &mut App) {
-    app.init_resource::<PhysicsTimeEstimate>();
     app.init_resource::<PhysicsSnapshots>();
+    app.init_resource::<SnapshotBaselines>();
     app.init_resource::<InterpolateValues>();
+    app.init_resource::<SnapshotJitter>();
+    app.init_resource::<SnapshotInterpolationConfig>();
     app.insert_resource(SnapshotPlayoutDelay {
         delay: Duration::from_millis(200),
     });
 
     app.add_systems(
         Update,
-        (
-            receive_time_samples,
-            calculate_time_estimate,
-            receive_physics_snapshots,
-        ),
+        (receive_physics_snapshots, adapt_playout_delay).chain(),
     );
 
+    app.add_systems(OnEnter(ClientState::Reconnecting), reset_snapshots);
+
     app.add_systems(
         PostUpdate,
         (clear_interpolation, queue_interpolation, interpolate_bodies).chain(),
     );
 }
 
-/// how many samples to keep to make the time estimate
-const TIME_ESTIMATE_SAMPLES: usize = 30;
-
-#[derive(Resource, Default)]
-pub struct PhysicsTimeEstimate {
-    /// list of samples and when they were received
-    samples: VecDeque<(TimeSample, Duration)>,
-    pub time_estimate: Duration,
-}
-
-fn receive_time_samples(
-    mut messages: ClientMessages<TimeSample>,
-    mut physics_time: ResMut<PhysicsTimeEstimate>,
-    time: Res<Time>,
-) {
-    for sample in messages.drain() {
-        physics_time.samples.push_back((sample, time.elapsed()));
-
-        if physics_time.samples.len() > TIME_ESTIMATE_SAMPLES {
-            physics_time.samples.pop_front();
-        }
-    }
-}
-
-fn calculate_time_estimate(mut physics_time: ResMut<PhysicsTimeEstimate>, time: Res<Time>) {
-    if let Some(time_estimate) = physics_time
-        .samples
-        .iter()
-        .map(|(sample, received_time)| sample.time + (time.elapsed() - *received_time))
-        .sum::<Duration>()
-        .checked_div(physics_time.samples.len() as u32)
-    {
-        physics_time.time_estimate = time_estimate;
-    }
+/// How much each received snapshot contributes to the smoothed jitter.
+const JITTER_SMOOTHING: f32 = 1. / 16.;
+/// How many times the measured jitter to add to the playout delay.
+const JITTER_DELAY_MULTIPLIER: f32 = 3.;
+const MIN_PLAYOUT_DELAY: Duration = Duration::from_millis(50);
+const MAX_PLAYOUT_DELAY: Duration = Duration::from_millis(500);
+/// How quickly the playout delay moves towards it's target, per second.
+const PLAYOUT_DELAY_ADAPT_RATE: f32 = 0.5;
+
+/// A decoded physics snapshot.
+pub struct ReceivedSnapshot {
+    pub time: Duration,
+    pub bodies: Vec<(ServerEntity, PhysicsBodySnapshot)>,
 }
 
 #[derive(Resource, Default)]
 pub struct PhysicsSnapshots {
     /// ordered list of physics snapshots sent from the server
-    pub snapshots: VecDeque<PhysicsSnapshot>,
+    pub snapshots: VecDeque<ReceivedSnapshot>,
+}
+
+/// The quantized bodies of received snapshots that the server may use as a baseline.
+#[derive(Resource, Default)]
+struct SnapshotBaselines {
+    received: VecDeque<(u32, EntityHashMap<QuantizedBody>)>,
 }
 
 impl PhysicsSnapshots {
@@ -87,12 +78,112 @@ impl PhysicsSnapshots {
 fn receive_physics_snapshots(
     mut messages: ClientMessages<PhysicsSnapshot>,
     mut snapshots: ResMut<PhysicsSnapshots>,
-) {
+    mut baselines: ResMut<SnapshotBaselines>,
+    mut jitter: ResMut<SnapshotJitter>,
+    mut sender: LocalClientMessageSender,
+    message_id: Res<MessageId<PhysicsSnapshotAck>>,
+    time: Res<Time>,
+) -> Result {
     for snapshot in messages.drain() {
+        // the server will only use this baseline or newer ones from now on
+        if let Some(baseline) = snapshot.baseline {
+            while baselines
+                .received
+                .front()
+                .is_some_and(|&(sequence, _)| sequence < baseline)
+            {
+                baselines.received.pop_front();
+            }
+        }
+
+        let Some(bodies) = decode_snapshot(&snapshot, &baselines) else {
+            warn!(
+                "Couldn't decode physics snapshot {}, baseline {:?} is missing",
+                snapshot.sequence, snapshot.baseline
+            );
+            continue;
+        };
+
+        jitter.sample(snapshot.time, time.elapsed());
+
         let index = snapshots.search(snapshot.time);
 
-        snapshots.snapshots.insert(index, snapshot);
+        snapshots.snapshots.insert(
+            index,
+            ReceivedSnapshot {
+                time: snapshot.time,
+                bodies: bodies
+                    .iter()
+                    .map(|(&server_entity, body)| (server_entity.into(), body.dequantize()))
+                    .collect(),
+            },
+        );
+
+        baselines.received.push_back((snapshot.sequence, bodies));
+
+        // the server never uses a baseline older than the snapshots it remembers,
+        // which it keeps sending while acks are being lost
+        if baselines.received.len() > SNAPSHOT_HISTORY_LENGTH {
+            baselines.received.pop_front();
+        }
+
+        // if out of bandwidth don't send, the server will keep using an older baseline
+        sender.write(
+            *message_id,
+            false,
+            &PhysicsSnapshotAck {
+                sequence: snapshot.sequence,
+            },
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Forgets everything received on the previous connection,
+/// the server starts sending snapshots from the beginning again.
+fn reset_snapshots(
+    mut snapshots: ResMut<PhysicsSnapshots>,
+    mut baselines: ResMut<SnapshotBaselines>,
+    mut jitter: ResMut<SnapshotJitter>,
+) {
+    *snapshots = PhysicsSnapshots::default();
+    *baselines = SnapshotBaselines::default();
+    *jitter = SnapshotJitter::default();
+}
+
+/// Applies a snapshot to it's baseline to get the state of every body.
+///
+/// Returns `None` if the baseline isn't known.
+fn decode_snapshot(
+    snapshot: &PhysicsSnapshot,
+    baselines: &SnapshotBaselines,
+) -> Option<EntityHashMap<QuantizedBody>> {
+    let baseline = match snapshot.baseline {
+        None => None,
+        Some(baseline) => Some(
+            baselines
+                .received
+                .iter()
+                .find(|&&(sequence, _)| sequence == baseline)
+                .map(|(_, bodies)| bodies)?,
+        ),
+    };
+
+    let mut bodies = baseline.cloned().unwrap_or_default();
+
+    for &server_entity in snapshot.removed.iter() {
+        bodies.remove(&Entity::from(server_entity));
+    }
+
+    for &(server_entity, update) in snapshot.bodies.iter() {
+        let server_entity = Entity::from(server_entity);
+        let body = update.decode(baseline.and_then(|baseline| baseline.get(&server_entity)))?;
+
+        bodies.insert(server_entity, body);
     }
+
+    Some(bodies)
 }
 
 /// Insert onto a mapped server entity to
@@ -102,19 +193,107 @@ fn receive_physics_snapshots(
 /// has a [Position] and [Rotation].
 #[derive(Component, Default)]
 pub struct SnapshotInterpolation {
-    start: Option<PhysicsBodySnapshot>,
-    end: Option<PhysicsBodySnapshot>,
+    /// The latest state of the body at or before the playout time.
+    start: Option<TimedBody>,
+    /// The earliest state of the body after the playout time.
+    end: Option<TimedBody>,
+    /// The last start state of the body,
+    /// used once the snapshots it was in have been forgotten.
+    last_known: Option<TimedBody>,
+}
+
+#[derive(Clone, Copy)]
+struct TimedBody {
+    time: Duration,
+    body: PhysicsBodySnapshot,
 }
 
+/// How far behind the estimated server time snapshots are played out.
+///
+/// Adapts to the measured jitter of received snapshots.
 #[derive(Resource)]
 pub struct SnapshotPlayoutDelay {
     pub delay: Duration,
 }
 
+#[derive(Resource)]
+pub struct SnapshotInterpolationConfig {
+    /// How long a body will keep moving with it's last known velocity
+    /// when there are no newer snapshots of it.
+    pub extrapolation_window: Duration,
+    /// Bodies that move further than this between two snapshots
+    /// are teleported instead of interpolated.
+    pub snap_distance: f32,
+}
+
+impl Default for SnapshotInterpolationConfig {
+    fn default() -> Self {
+        SnapshotInterpolationConfig {
+            extrapolation_window: Duration::from_millis(250),
+            snap_distance: 5.,
+        }
+    }
+}
+
+/// Measures how much the arrival of snapshots varies.
+#[derive(Resource, Default)]
+struct SnapshotJitter {
+    /// The server time of the latest snapshot and the local time it was received.
+    last_received: Option<(Duration, Duration)>,
+    /// Smoothed server time between snapshots in seconds.
+    interval: f32,
+    /// Smoothed difference between the server and local time between snapshots in seconds.
+    jitter: f32,
+}
+
+impl SnapshotJitter {
+    fn sample(&mut self, server_time: Duration, received_time: Duration) {
+        if let Some((last_server_time, last_received_time)) = self.last_received {
+            // ignore snapshots that arrived out of order
+            if server_time <= last_server_time {
+                return;
+            }
+
+            let interval = (server_time - last_server_time).as_secs_f32();
+            let received_interval = (received_time - last_received_time).as_secs_f32();
+
+            if self.interval == 0. {
+                self.interval = interval;
+            }
+
+            self.interval += (interval - self.interval) * JITTER_SMOOTHING;
+            self.jitter += ((received_interval - interval).abs() - self.jitter) * JITTER_SMOOTHING;
+        }
+
+        self.last_received = Some((server_time, received_time));
+    }
+}
+
 #[derive(Resource, Default)]
 struct InterpolateValues {
-    interpolate_percent: f32,
-    velocity_scale: f32,
+    playout_time: Duration,
+}
+
+/// Moves the playout delay towards one snapshot interval plus a margin for jitter.
+fn adapt_playout_delay(
+    jitter: Res<SnapshotJitter>,
+    mut playout_delay: ResMut<SnapshotPlayoutDelay>,
+    time: Res<Time>,
+) {
+    if jitter.last_received.is_none() {
+        return;
+    }
+
+    let target = (jitter.interval + jitter.jitter * JITTER_DELAY_MULTIPLIER).clamp(
+        MIN_PLAYOUT_DELAY.as_secs_f32(),
+        MAX_PLAYOUT_DELAY.as_secs_f32(),
+    );
+
+    // adapt gradually so that bodies don't visibly jump
+    let delay = playout_delay.delay.as_secs_f32();
+    let step = (time.delta_secs() * PLAYOUT_DELAY_ADAPT_RATE).min(1.);
+
+    playout_delay.delay = Duration::from_secs_f32(delay + (target - delay) * step);
 }
 
 fn clear_interpolation(mut body_q: Query<&mut SnapshotInterpolation>) {
@@ -126,88 +305,60 @@ fn clear_interpolation(mut body_q: Query<&mut SnapshotInterpolation>) {
 
 fn queue_interpolation(
     mut snapshots: ResMut<PhysicsSnapshots>,
-    physics_time: Res<PhysicsTimeEstimate>,
+    clock: Res<ServerClock>,
     playout_delay: Res<SnapshotPlayoutDelay>,
     map: Res<ServerEntityMap>,
     mut interpolate_values: ResMut<InterpolateValues>,
     mut body_q: Query<&mut SnapshotInterpolation>,
 ) {
-    // calculate playout time based on time estimate and delay
-    let playout_time = physics_time
-        .time_estimate
-        .saturating_sub(playout_delay.delay);
-
-    // get the indicies of the snapshots before and after the playout time
-    let end_index = snapshots.search(playout_time);
-
-    let Some(start_index) = end_index.checked_sub(1) else {
-        // warn!("Have no snapshot to start interpolation at");
+    if !clock.is_synchronized() {
         return;
-    };
-
-    // get the snapshots
-    let Some(end_snapshot) = snapshots.snapshots.get(end_index) else {
-        // warn!("Have no snapshot to end interpolation at");
-        return;
-    };
-
-    let start_snapshot = snapshots
-        .snapshots
-        .get(start_index)
-        .expect("index should be valid if control reaches here");
-
-    // calculate the interpolation percent
-    let snapshot_difference = end_snapshot.time - start_snapshot.time;
-    let interpolate_distance = playout_time - start_snapshot.time;
-    interpolate_values.interpolate_percent =
-        interpolate_distance.as_secs_f32() / snapshot_difference.as_secs_f32();
-
-    // scale velocities based on time difference between snapshots
-    interpolate_values.velocity_scale = snapshot_difference.as_secs_f32();
+    }
 
-    // update the bodies that appear in the two snapshots
-    for &(server_entity, snapshot) in start_snapshot.bodies.iter() {
-        let Some(body_entity) = map.get_client_entity(server_entity) else {
-            continue;
-        };
+    // calculate playout time based on the server's time and delay
+    let playout_time = clock.server_time().saturating_sub(playout_delay.delay);
 
-        let Ok(mut interpolation) = body_q.get_mut(body_entity) else {
-            continue;
-        };
+    interpolate_values.playout_time = playout_time;
 
-        interpolation.start = Some(snapshot);
+    // snapshots before this index are at or before the playout time
+    let split_index = snapshots
+        .snapshots
+        .partition_point(|snapshot| snapshot.time <= playout_time);
+
+    // find each body's own start and end, bodies can be missing from some snapshots
+    for (index, snapshot) in snapshots.snapshots.iter().enumerate() {
+        for &(server_entity, body) in snapshot.bodies.iter() {
+            let Some(body_entity) = map.get_client_entity(server_entity) else {
+                continue;
+            };
+
+            let Ok(mut interpolation) = body_q.get_mut(body_entity) else {
+                continue;
+            };
+
+            let state = TimedBody {
+                time: snapshot.time,
+                body,
+            };
+
+            if index < split_index {
+                interpolation.start = Some(state);
+            } else if interpolation.end.is_none() {
+                interpolation.end = Some(state);
+            }
+        }
     }
 
-    for &(server_entity, snapshot) in end_snapshot.bodies.iter() {
-        let Some(body_entity) = map.get_client_entity(server_entity) else {
-            continue;
-        };
-
-        let Ok(mut interpolation) = body_q.get_mut(body_entity) else {
-            continue;
-        };
-
-        interpolation.end = Some(snapshot);
+    for mut interpolation in body_q.iter_mut() {
+        match interpolation.start {
+            Some(start) => interpolation.last_known = Some(start),
+            None => interpolation.start = interpolation.last_known,
+        }
     }
 
-    // // snapshot and playout time debug info
-    // let earliest = snapshots
-    //     .snapshots
-    //     .front()
-    //     .map(|s| s.time)
-    //     .unwrap_or_default();
-    // let latest = snapshots
-    //     .snapshots
-    //     .back()
-    //     .map(|s| s.time)
-    //     .unwrap_or_default();
-    // debug!(
-    //     "playout: {:#?} start: {:#?} end: {:#?} earliest: {:#?} latest: {:#?}",
-    //     playout_time, start_snapshot.time, end_snapshot.time, earliest, latest
-    // );
-
-    // forget snapshots older than the two used
-    for _ in 0..start_index {
+    // forget snapshots older than the latest one before the playout time,
+    // bodies that aren't in it still have their last known state
+    for _ in 0..split_index.saturating_sub(1) {
         snapshots.snapshots.pop_front();
     }
 }
@@ -215,47 +366,83 @@ fn queue_interpolation(
 fn interpolate_bodies(
     mut gizmos: Gizmos<PhysicsGizmos>,
     interpolate_values: Res<InterpolateValues>,
+    config: Res<SnapshotInterpolationConfig>,
     mut body_q: Query<(&SnapshotInterpolation, &mut Position, &mut Rotation)>,
 ) {
+    let playout_time = interpolate_values.playout_time;
+
     for (interpolation, mut position, mut rotation) in body_q.iter_mut() {
-        // only interpolate if the body is in the start and end snapshot
-        let (Some(start), Some(end)) = (interpolation.start, interpolation.end) else {
-            continue;
+        let (new_position, new_rotation) = match (interpolation.start, interpolation.end) {
+            (Some(start), Some(end)) => {
+                // the body teleported, stay at the start until the end is reached
+                if start.body.position.distance(end.body.position) > config.snap_distance {
+                    (start.body.position, start.body.rotation)
+                } else {
+                    let snapshot_difference = (end.time - start.time).as_secs_f32();
+                    let interpolate_percent = (playout_time.saturating_sub(start.time))
+                        .as_secs_f32()
+                        / snapshot_difference;
+
+                    // scale velocities based on time difference between snapshots
+                    let start_velocity = start.body.linear_velocity * snapshot_difference;
+                    let end_velocity = end.body.linear_velocity * snapshot_difference;
+
+                    for time in 0..10 {
+                        let a = hermite(
+                            time as f32 / 10.0,
+                            start.body.position,
+                            start_velocity,
+                            end.body.position,
+                            end_velocity,
+                        );
+
+                        let b = hermite(
+                            (time + 1) as f32 / 10.0,
+                            start.body.position,
+                            start_velocity,
+                            end.body.position,
+                            end_velocity,
+                        );
+
+                        gizmos.line(a, b, bevy::color::palettes::css::BLUE);
+                    }
+
+                    (
+                        // hermite interpolation for position
+                        hermite(
+                            interpolate_percent,
+                            start.body.position,
+                            start_velocity,
+                            end.body.position,
+                            end_velocity,
+                        ),
+                        // spherical linear interpolation for rotation
+                        start
+                            .body
+                            .rotation
+                            .slerp(end.body.rotation, interpolate_percent),
+                    )
+                }
+            }
+            // no newer snapshots have arrived, keep moving for a short while
+            (Some(start), None) => {
+                let extrapolate_time = playout_time
+                    .saturating_sub(start.time)
+                    .min(config.extrapolation_window);
+
+                (
+                    start.body.position
+                        + start.body.linear_velocity * extrapolate_time.as_secs_f32(),
+                    start.body.rotation,
+                )
+            }
+            // the body was spawned after the playout time
+            (None, Some(end)) => (end.body.position, end.body.rotation),
+            (None, None) => continue,
         };
 
-        // hermite interpolation for position
-        position.0 = hermite(
-            interpolate_values.interpolate_percent,
-            start.position,
-            start.linear_velocity * interpolate_values.velocity_scale,
-            end.position,
-            end.linear_velocity * interpolate_values.velocity_scale,
-        );
-
-        // spherical linear interpolation for rotation
-        rotation.0 = start
-            .rotation
-            .slerp(end.rotation, interpolate_values.interpolate_percent);
-
-        for time in 0..10 {
-            let a = hermite(
-                time as f32 / 10.0,
-                start.position,
-                start.linear_velocity * interpolate_values.velocity_scale,
-                end.position,
-                end.linear_velocity * interpolate_values.velocity_scale,
-            );
-
-            let b = hermite(
-                (time + 1) as f32 / 10.0,
-                start.position,
-                start.linear_velocity * interpolate_values.velocity_scale,
-                end.position,
-                end.linear_velocity * interpolate_values.velocity_scale,
-            );
-
-            gizmos.line(a, b, bevy::color::palettes::css::BLUE);
-        }
+        position.0 = new_position;
+        rotation.0 = new_rotation;
     }
 }
 
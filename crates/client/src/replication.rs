@@ -0,0 +1,98 @@
+//! Client side of [component replication](common::networking::replicate_component).
+//!
+//! Uses exclusive systems so that entities spawned for one component
+//! are in the [ServerEntityMap] before the next component is received.
+
+use bevy::prelude::*;
+use common::networking::replicate_component::{
+    RemoveComponent, ReplicateComponents, ReplicatedComponent, UpdateComponent,
+    replicated_components,
+};
+use nevy::*;
+
+use crate::{
+    networking::ClientConnection,
+    server_entity_map::{LocalServerEntity, ServerEntityMap},
+};
+
+pub fn build(app: &mut App) {
+    replicated_components(app, &mut ClientReplication);
+}
+
+/// System set in [Update] that applies replicated component updates from the server.
+///
+/// Systems that look up replicated entities in the [ServerEntityMap] should run after this.
+#[derive(SystemSet, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct ReceiveComponentUpdates;
+
+struct ClientReplication;
+
+impl ReplicateComponents for ClientReplication {
+    fn replicate<T: ReplicatedComponent>(&mut self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (
+                receive_component_updates::<T>,
+                receive_component_removals::<T>,
+            )
+                .chain()
+                .in_set(ReceiveComponentUpdates),
+        );
+    }
+}
+
+fn receive_component_updates<T: ReplicatedComponent>(
+    world: &mut World,
+    connection_q: &mut QueryState<
+        &mut ReceivedMessages<UpdateComponent<T>>,
+        With<ClientConnection>,
+    >,
+) {
+    let messages: Vec<_> = match connection_q.single_mut(world) {
+        Ok(mut messages) => messages.drain().collect(),
+        Err(_) => return,
+    };
+
+    for UpdateComponent { entity, value } in messages {
+        match world
+            .resource::<ServerEntityMap>()
+            .get_client_entity(entity)
+        {
+            Some(client_entity) => {
+                world.entity_mut(client_entity).insert(value);
+            }
+            None => {
+                world.spawn((LocalServerEntity(entity), value));
+            }
+        }
+    }
+}
+
+fn receive_component_removals<T: ReplicatedComponent>(
+    world: &mut World,
+    connection_q: &mut QueryState<
+        &mut ReceivedMessages<RemoveComponent<T>>,
+        With<ClientConnection>,
+    >,
+) {
+    let messages: Vec<_> = match connection_q.single_mut(world) {
+        Ok(mut messages) => messages.drain().collect(),
+        Err(_) => return,
+    };
+
+    for RemoveComponent { entity, .. } in messages {
+        let Some(client_entity) = world
+            .resource::<ServerEntityMap>()
+            .get_client_entity(entity)
+        else {
+            warn!(
+                "Server removed a {} from {} which doesn't exist",
+                std::any::type_name::<T>(),
+                entity
+            );
+            continue;
+        };
+
+        world.entity_mut(client_entity).remove::<T>();
+    }
+}
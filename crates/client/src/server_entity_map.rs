@@ -6,10 +6,19 @@ use bevy::{
     },
     prelude::*,
 };
-use common::ServerEntity;
+use common::{ServerEntity, networking::replicate_despawn::ServerEntityRemoved};
+
+use crate::{networking::params::ClientMessages, state::ClientState};
 
 pub fn build(app: &mut App) {
     app.init_resource::<ServerEntityMap>();
+
+    // despawn before anything is initialized so that entities that are removed and re-added
+    // don't despawn the newly initialized entity.
+    app.add_systems(PreUpdate, despawn_removed_entities);
+
+    // the server will replicate everything again after reconnecting
+    app.add_systems(OnEnter(ClientState::Reconnecting), despawn_server_entities);
 }
 
 /// A resource containing a map of [ServerEntity]s to [LocalServerEntity]s.
@@ -88,6 +97,31 @@ impl ServerEntityMap {
     }
 }
 
+/// Despawns entities when the server says they were despawned or stopped being relevant.
+fn despawn_removed_entities(
+    mut commands: Commands,
+    mut messages: ClientMessages<ServerEntityRemoved>,
+    map: Res<ServerEntityMap>,
+) {
+    for ServerEntityRemoved { entity } in messages.drain() {
+        let Some(client_entity) = map.get_client_entity(entity) else {
+            warn!("Server removed {} which doesn't exist", entity);
+            continue;
+        };
+
+        commands.entity(client_entity).despawn();
+    }
+}
+
+fn despawn_server_entities(
+    mut commands: Commands,
+    entity_q: Query<Entity, With<LocalServerEntity>>,
+) {
+    for entity in entity_q.iter() {
+        commands.entity(entity).despawn();
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::server_entity_map::{LocalServerEntity, ServerEntityMap};
@@ -1,24 +1,94 @@
+use std::time::Duration;
+
 use bevy::prelude::*;
-use common::{networking::StreamHeader, state::JoinGameRequest};
+use common::{
+    match_state::{MatchState, SetMatchState},
+    networking::StreamHeader,
+    state::{JoinGameRequest, JoinGameResponse, PROTOCOL_VERSION, SessionToken},
+};
 use nevy::*;
 
-use crate::networking::ClientConnection;
+use crate::{
+    config::ClientConfig,
+    networking::{ClientConnection, ClientEndpoint, connect_to_server, params::ClientMessages},
+};
+
+/// How long to wait between attempts to reconnect.
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(2);
 
 pub fn build(app: &mut App) {
     app.init_state::<ClientState>();
+    app.add_computed_state::<Joined>();
+    app.init_resource::<ClientSession>();
 
     app.add_systems(
         Update,
-        send_join_request.run_if(in_state(ClientState::Disconnected)),
+        (
+            (send_join_request, receive_join_response)
+                .chain()
+                .run_if(not(in_state(Joined))),
+            receive_match_state.after(receive_join_response),
+            detect_lost_connection.run_if(in_state(Joined)),
+            retry_connection.run_if(in_state(ClientState::Reconnecting)),
+        ),
     );
 }
 
 /// The state of the client.
+///
+/// Once joined this follows the server's [MatchState].
 #[derive(States, Default, Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum ClientState {
     #[default]
     Disconnected,
-    Joined,
+    /// The connection was lost and the client is trying to join again with it's [ClientSession].
+    ///
+    /// Everything replicated from the server is despawned when entering this state.
+    Reconnecting,
+    Lobby,
+    Loading,
+    Briefing,
+    InProgress,
+    Extracted,
+    Failed,
+    Results,
+}
+
+impl From<MatchState> for ClientState {
+    fn from(state: MatchState) -> Self {
+        match state {
+            MatchState::Lobby => ClientState::Lobby,
+            MatchState::Loading => ClientState::Loading,
+            MatchState::Briefing => ClientState::Briefing,
+            MatchState::InProgress => ClientState::InProgress,
+            MatchState::Extracted => ClientState::Extracted,
+            MatchState::Failed => ClientState::Failed,
+            MatchState::Results => ClientState::Results,
+        }
+    }
+}
+
+/// Computed state that exists while the client has joined the game, whatever phase the match is in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Joined;
+
+impl ComputedStates for Joined {
+    type SourceStates = ClientState;
+
+    fn compute(state: ClientState) -> Option<Self> {
+        match state {
+            ClientState::Disconnected | ClientState::Reconnecting => None,
+            _ => Some(Joined),
+        }
+    }
+}
+
+/// The token the server gave this client when it joined.
+///
+/// Sent when joining again to be the same player after disconnecting.
+#[derive(Resource, Default)]
+pub struct ClientSession {
+    pub session_token: Option<SessionToken>,
 }
 
 fn send_join_request(
@@ -28,6 +98,8 @@ fn send_join_request(
     >,
     mut sender: LocalMessageSender,
     message_id: Res<MessageId<JoinGameRequest>>,
+    config: Res<ClientConfig>,
+    session: Res<ClientSession>,
 ) -> Result {
     sender.flush()?;
     sender.finish_all_if_uncongested()?;
@@ -39,7 +111,10 @@ fn send_join_request(
             *message_id,
             true,
             &JoinGameRequest {
-                username: "Player".into(),
+                protocol_version: PROTOCOL_VERSION,
+                username: config.username.clone(),
+                password: config.password.clone(),
+                session_token: session.session_token,
             },
         )?;
 
@@ -48,3 +123,79 @@ fn send_join_request(
 
     Ok(())
 }
+
+fn receive_join_response(
+    mut messages: ClientMessages<JoinGameResponse>,
+    mut session: ResMut<ClientSession>,
+    mut next_state: ResMut<NextState<ClientState>>,
+) {
+    for response in messages.drain() {
+        match response {
+            JoinGameResponse::Accepted { session_token } => {
+                info!("Joined game");
+
+                session.session_token = Some(session_token);
+
+                // the server sends the match state right after accepting
+                next_state.set(ClientState::Lobby);
+            }
+            JoinGameResponse::Rejected { reason } => {
+                error!("Server rejected join request: {}", reason);
+            }
+        }
+    }
+}
+
+fn receive_match_state(
+    mut messages: ClientMessages<SetMatchState>,
+    mut next_state: ResMut<NextState<ClientState>>,
+) {
+    for SetMatchState { state } in messages.drain() {
+        info!("Match is now in the {:?} phase", state);
+
+        next_state.set(state.into());
+    }
+}
+
+fn detect_lost_connection(
+    connection_q: Query<&ConnectionStatus, (Changed<ConnectionStatus>, With<ClientConnection>)>,
+    mut next_state: ResMut<NextState<ClientState>>,
+) {
+    for status in connection_q.iter() {
+        if let ConnectionStatus::Closed { .. } | ConnectionStatus::Failed { .. } = status {
+            warn!("Lost connection to the server, reconnecting");
+
+            next_state.set(ClientState::Reconnecting);
+        }
+    }
+}
+
+/// Replaces the connection with a new one until one succeeds.
+fn retry_connection(
+    mut commands: Commands,
+    connection_q: Query<(Entity, &ConnectionStatus), With<ClientConnection>>,
+    endpoint_q: Query<Entity, With<ClientEndpoint>>,
+    config: Res<ClientConfig>,
+    time: Res<Time<Real>>,
+    mut last_attempt: Local<Option<Duration>>,
+) -> Result {
+    for (connection_entity, status) in connection_q.iter() {
+        match status {
+            ConnectionStatus::Closed { .. } | ConnectionStatus::Failed { .. } => {
+                commands.entity(connection_entity).despawn();
+            }
+            // wait for the current attempt to finish
+            _ => return Ok(()),
+        }
+    }
+
+    if last_attempt.is_some_and(|last_attempt| time.elapsed() < last_attempt + RECONNECT_INTERVAL) {
+        return Ok(());
+    }
+
+    *last_attempt = Some(time.elapsed());
+
+    info!("Attempting to reconnect to {}", config.server_address);
+
+    connect_to_server(&mut commands, endpoint_q.single()?, &config)
+}
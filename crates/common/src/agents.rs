@@ -1,15 +1,10 @@
 use avian3d::prelude::*;
 use bevy::prelude::*;
-use nevy::*;
 use serde::{Deserialize, Serialize};
 
-use crate::{GameLayer, ServerEntity};
+use crate::GameLayer;
 
-pub fn build(app: &mut App) {
-    app.add_message::<InitializeAgent>();
-}
-
-#[derive(Component)]
+#[derive(Component, Serialize, Deserialize, Clone)]
 #[require(
     Collider::capsule_endpoints(0.25, Vec3::Y * 0.25, Vec3::Y * 1.75),
     CollisionLayers::new([GameLayer::Agents, GameLayer::Opaque], 0),
@@ -20,11 +15,91 @@ pub fn build(app: &mut App) {
 )]
 pub struct Agent;
 
-#[derive(Component, Serialize, Deserialize)]
-#[require(Position)]
-pub struct PatrolPoint;
+/// How suspicious an agent has to be of a target to become curious about it.
+pub const CURIOUS_SUSPICION: f32 = 0.3;
+/// How suspicious an agent has to be of a target to investigate it.
+pub const INVESTIGATE_SUSPICION: f32 = 0.6;
+/// How suspicious an agent has to be of a target to be alarmed by it.
+pub const ALARMED_SUSPICION: f32 = 1.0;
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum SuspicionLevel {
+    Calm,
+    Curious,
+    Investigating,
+    Alarmed,
+}
+
+impl SuspicionLevel {
+    /// The level of a suspicion from 0 to 1.
+    pub fn of(suspicion: f32) -> Self {
+        if suspicion >= ALARMED_SUSPICION {
+            SuspicionLevel::Alarmed
+        } else if suspicion >= INVESTIGATE_SUSPICION {
+            SuspicionLevel::Investigating
+        } else if suspicion >= CURIOUS_SUSPICION {
+            SuspicionLevel::Curious
+        } else {
+            SuspicionLevel::Calm
+        }
+    }
+}
+
+/// How suspicious an agent is of the target they are most suspicious of.
+///
+/// Replicated so that clients can show how close a guard is to noticing someone.
+/// Quantized so that it only changes when the suspicion changes noticeably.
+#[derive(Component, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+pub struct AgentSuspicionMeter(u8);
+
+impl AgentSuspicionMeter {
+    pub fn new(suspicion: f32) -> Self {
+        AgentSuspicionMeter((suspicion.clamp(0., 1.) * u8::MAX as f32).round() as u8)
+    }
+
+    /// The suspicion from 0 to 1.
+    pub fn suspicion(self) -> f32 {
+        self.0 as f32 / u8::MAX as f32
+    }
+
+    pub fn level(self) -> SuspicionLevel {
+        SuspicionLevel::of(self.suspicion())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::agents::{AgentSuspicionMeter, SuspicionLevel};
+
+    #[test]
+    fn suspicion_levels() {
+        assert_eq!(SuspicionLevel::of(0.), SuspicionLevel::Calm);
+        assert_eq!(SuspicionLevel::of(0.29), SuspicionLevel::Calm);
+        assert_eq!(SuspicionLevel::of(0.3), SuspicionLevel::Curious);
+        assert_eq!(SuspicionLevel::of(0.59), SuspicionLevel::Curious);
+        assert_eq!(SuspicionLevel::of(0.6), SuspicionLevel::Investigating);
+        assert_eq!(SuspicionLevel::of(0.99), SuspicionLevel::Investigating);
+        assert_eq!(SuspicionLevel::of(1.), SuspicionLevel::Alarmed);
+    }
+
+    #[test]
+    fn suspicion_meter_round_trip() {
+        for i in 0..=100 {
+            let suspicion = i as f32 / 100.;
+            let meter = AgentSuspicionMeter::new(suspicion);
+
+            assert!((meter.suspicion() - suspicion).abs() <= 0.5 / u8::MAX as f32 + f32::EPSILON);
+        }
 
-#[derive(Serialize, Deserialize)]
-pub struct InitializeAgent {
-    pub entity: ServerEntity,
+        assert_eq!(AgentSuspicionMeter::new(-1.).suspicion(), 0.);
+        assert_eq!(AgentSuspicionMeter::new(2.).suspicion(), 1.);
+        assert_eq!(
+            AgentSuspicionMeter::new(0.45).level(),
+            SuspicionLevel::Curious
+        );
+        assert_eq!(
+            AgentSuspicionMeter::new(1.).level(),
+            SuspicionLevel::Alarmed
+        );
+    }
 }
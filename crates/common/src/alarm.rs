@@ -0,0 +1,30 @@
+use std::time::Duration;
+
+use bevy::prelude::*;
+use nevy::AddMessage;
+use serde::{Deserialize, Serialize};
+
+pub fn build(app: &mut App) {
+    app.add_message::<SetAlarmState>();
+}
+
+/// How alert the level is to the crew, raised by the server as they are noticed.
+#[derive(Serialize, Deserialize, Default, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum AlarmLevel {
+    #[default]
+    Calm,
+    /// A guard has called in something suspicious and the others are searching.
+    Suspicious,
+    /// The alarm has been raised and the police are on their way.
+    Alarm,
+    /// The police have arrived and the doors are locked.
+    Lockdown,
+}
+
+/// Server -> Client message sent when the alarm level changes and when a client joins.
+#[derive(Serialize, Deserialize)]
+pub struct SetAlarmState {
+    pub level: AlarmLevel,
+    /// How long until the police arrive, if they are on their way.
+    pub police_arrive_in: Option<Duration>,
+}
@@ -1,11 +1,18 @@
 use avian3d::prelude::*;
-use bevy::prelude::*;
+use bevy::{ecs::system::SystemParam, prelude::*};
 use serde::{Deserialize, Serialize};
 
-use crate::character::Character;
+use crate::{
+    GameLayer,
+    character::Character,
+    noise::{NoiseEvent, NoiseKind},
+};
 
 const PLAYER_ACCELERATION: f32 = 75.;
-const PLAYER_MOVE_SPEED: f32 = 3.;
+pub const PLAYER_MOVE_SPEED: f32 = 3.;
+pub const PLAYER_CROUCH_SPEED: f32 = 1.5;
+pub const PLAYER_JUMP_SPEED: f32 = 3.;
+const ON_GROUND_TOLERANCE: f32 = 0.02;
 const MAX_INTEGRATE_ITERATIONS: usize = 50;
 const PLAYER_COLLISION_MARGIN: f32 = 0.002;
 
@@ -14,6 +21,7 @@ pub fn build(app: &mut App) {
         FixedPostUpdate,
         (
             rotate_players,
+            jump_players,
             accelerate_players,
             integrate_character,
             apply_integrated_positions,
@@ -35,12 +43,14 @@ pub struct CharacterController;
 /// The input state for a character
 ///
 /// Used to simulate a character both on the client and for prediction on the server
-#[derive(Clone, Copy, Component, Serialize, Deserialize)]
+#[derive(Clone, Copy, PartialEq, Component, Serialize, Deserialize)]
 pub struct CharacterInput {
     pub move_forward: bool,
     pub move_backward: bool,
     pub move_left: bool,
     pub move_right: bool,
+    pub jump: bool,
+    pub crouch: bool,
     pub look_direction: Dir3,
 }
 
@@ -51,6 +61,8 @@ impl Default for CharacterInput {
             move_backward: false,
             move_left: false,
             move_right: false,
+            jump: false,
+            crouch: false,
             look_direction: Dir3::NEG_Z,
         }
     }
@@ -58,15 +70,25 @@ impl Default for CharacterInput {
 
 fn rotate_players(mut player_q: Query<(&CharacterInput, &mut Rotation)>) {
     for (input, mut rotation) in player_q.iter_mut() {
-        let face_direction = Vec3 {
-            y: 0.,
-            ..input.look_direction.into()
-        }
-        .normalize();
+        rotation.0 = character_rotation(input);
+    }
+}
+
+/// Jumps characters that want to jump and are standing on the ground.
+fn jump_players(
+    mut player_q: Query<
+        (Entity, &CharacterInput, &Position, &mut LinearVelocity),
+        With<CharacterController>,
+    >,
+    spatial_query: SpatialQuery,
+    mut noise_w: EventWriter<NoiseEvent>,
+) {
+    for (player_entity, input, &Position(position), mut velocity) in player_q.iter_mut() {
+        if input.jump && on_ground(&spatial_query, position) {
+            velocity.0 += Vec3::Y * PLAYER_JUMP_SPEED;
 
-        rotation.0 = Transform::default()
-            .looking_to(face_direction, Vec3::Y)
-            .rotation;
+            noise_w.write(NoiseEvent::new(player_entity, position, NoiseKind::Jump));
+        }
     }
 }
 
@@ -76,29 +98,7 @@ fn accelerate_players(
     time: Res<Time>,
 ) {
     for (input, mut velocity, rotation) in player_q.iter_mut() {
-        let target_velocity = Vec2 {
-            x: match (input.move_left, input.move_right) {
-                (true, false) => 1.,
-                (false, true) => -1.,
-                _ => 0.,
-            },
-            y: match (input.move_backward, input.move_forward) {
-                (true, false) => 1.,
-                (false, true) => -1.,
-                _ => 0.,
-            },
-        }
-        .normalize_or_zero();
-
-        let target_velociy = Vec2::from_angle(-rotation.to_euler(EulerRot::YXZ).0)
-            .rotate(target_velocity * PLAYER_MOVE_SPEED);
-
-        let difference = target_velociy - velocity.0.xz();
-        let max_acceleration = PLAYER_ACCELERATION * time.delta_secs();
-        let delta = difference.clamp_length_max(max_acceleration);
-        **velocity += Vec3::new(delta.x, 0., delta.y);
-
-        **velocity += gravity.0 * time.delta_secs();
+        velocity.0 = accelerate(input, velocity.0, rotation.0, gravity.0, time.delta_secs());
     }
 }
 
@@ -135,59 +135,19 @@ fn integrate_character(
         collision_layers,
     ) in character_q.iter_mut()
     {
-        let mut position = **position;
-        let mut remaining_time = time.delta_secs();
-
-        for iteration in 0..MAX_INTEGRATE_ITERATIONS {
-            let Ok(direction) = Dir3::new(**velocity) else {
-                break;
-            };
-
-            let integrate_distance = remaining_time * velocity.length();
-
-            let hit = spatial_query
-                .shape_hits(
-                    collider,
-                    position,
-                    **rotation,
-                    direction,
-                    u32::MAX,
-                    &ShapeCastConfig {
-                        max_distance: integrate_distance,
-                        ..default()
-                    },
-                    &SpatialQueryFilter::from_mask(collision_layers.filters)
-                        .with_excluded_entities(std::iter::once(player_entity)),
-                )
-                .into_iter()
-                .filter(|hit| rigid_body_q.contains(hit.entity))
-                .filter(|hit| -hit.normal1.dot(direction.into()) > 0.)
-                .next();
-
-            let Some(hit) = hit else {
-                position += direction * integrate_distance;
-                break;
-            };
-
-            let hit_normal = rotation.mul_vec3(-hit.normal2);
-
-            remaining_time -= hit.distance / velocity.length();
-
-            position += direction * hit.distance;
-            position += hit_normal * PLAYER_COLLISION_MARGIN;
-
-            // let alignment = velocity.normalize_or_zero().dot(hit_normal).abs();
-            // if alignment < 0.2 {
-            //     debug!("collision alignment: {}", alignment);
-            // }
-
-            **velocity = velocity.reject_from(hit_normal);
-
-            if iteration == MAX_INTEGRATE_ITERATIONS - 1 {
-                debug!("Hit iteration limit");
-            }
-        }
+        let (position, integrated_velocity) = integrate(
+            &spatial_query,
+            &rigid_body_q,
+            player_entity,
+            collider,
+            collision_layers,
+            rotation.0,
+            position.0,
+            velocity.0,
+            time.delta_secs(),
+        );
 
+        velocity.0 = integrated_velocity;
         position_update.0 = position;
     }
 }
@@ -200,3 +160,189 @@ fn apply_integrated_positions(
         **position = integrated_position.0;
     }
 }
+
+/// The part of a character's state that is simulated by the controller.
+#[derive(Clone, Copy, Default, Debug)]
+pub struct CharacterState {
+    pub position: Vec3,
+    pub velocity: Vec3,
+}
+
+/// Everything needed to simulate a character outside of the controller systems.
+///
+/// Used to replay inputs when reconciling a predicted character with the server.
+#[derive(SystemParam)]
+pub struct CharacterSimulation<'w, 's> {
+    spatial_query: SpatialQuery<'w, 's>,
+    rigid_body_q: Query<'w, 's, (), With<RigidBody>>,
+    gravity: Res<'w, Gravity>,
+}
+
+impl<'w, 's> CharacterSimulation<'w, 's> {
+    /// Simulates a single step of a character the same way the controller systems do.
+    pub fn step(
+        &self,
+        character_entity: Entity,
+        collider: &Collider,
+        collision_layers: &CollisionLayers,
+        input: &CharacterInput,
+        state: CharacterState,
+        delta: f32,
+    ) -> CharacterState {
+        let rotation = character_rotation(input);
+        let mut velocity = state.velocity;
+
+        if input.jump && on_ground(&self.spatial_query, state.position) {
+            velocity += Vec3::Y * PLAYER_JUMP_SPEED;
+        }
+
+        let velocity = accelerate(input, velocity, rotation, self.gravity.0, delta);
+
+        let (position, velocity) = integrate(
+            &self.spatial_query,
+            &self.rigid_body_q,
+            character_entity,
+            collider,
+            collision_layers,
+            rotation,
+            state.position,
+            velocity,
+            delta,
+        );
+
+        CharacterState { position, velocity }
+    }
+}
+
+/// The rotation of a character looking in the direction of it's input.
+fn character_rotation(input: &CharacterInput) -> Quat {
+    let face_direction = Vec3 {
+        y: 0.,
+        ..input.look_direction.into()
+    }
+    .normalize();
+
+    Transform::default()
+        .looking_to(face_direction, Vec3::Y)
+        .rotation
+}
+
+pub fn on_ground(spatial_query: &SpatialQuery, position: Vec3) -> bool {
+    spatial_query
+        .cast_ray(
+            position,
+            Dir3::NEG_Y,
+            ON_GROUND_TOLERANCE,
+            true,
+            &SpatialQueryFilter::from_mask([GameLayer::World]),
+        )
+        .is_some()
+}
+
+/// Accelerates a character towards the velocity it's input wants and applies gravity.
+fn accelerate(
+    input: &CharacterInput,
+    velocity: Vec3,
+    rotation: Quat,
+    gravity: Vec3,
+    delta: f32,
+) -> Vec3 {
+    let target_velocity = Vec2 {
+        x: match (input.move_left, input.move_right) {
+            (true, false) => 1.,
+            (false, true) => -1.,
+            _ => 0.,
+        },
+        y: match (input.move_backward, input.move_forward) {
+            (true, false) => 1.,
+            (false, true) => -1.,
+            _ => 0.,
+        },
+    }
+    .normalize_or_zero();
+
+    let move_speed = if input.crouch {
+        PLAYER_CROUCH_SPEED
+    } else {
+        PLAYER_MOVE_SPEED
+    };
+
+    let target_velociy =
+        Vec2::from_angle(-rotation.to_euler(EulerRot::YXZ).0).rotate(target_velocity * move_speed);
+
+    let difference = target_velociy - velocity.xz();
+    let max_acceleration = PLAYER_ACCELERATION * delta;
+    let change = difference.clamp_length_max(max_acceleration);
+
+    velocity + Vec3::new(change.x, 0., change.y) + gravity * delta
+}
+
+/// Moves a character by it's velocity, sliding it along obstacles.
+///
+/// Returns the new position and velocity.
+#[allow(clippy::too_many_arguments)]
+fn integrate(
+    spatial_query: &SpatialQuery,
+    rigid_body_q: &Query<(), With<RigidBody>>,
+    character_entity: Entity,
+    collider: &Collider,
+    collision_layers: &CollisionLayers,
+    rotation: Quat,
+    mut position: Vec3,
+    mut velocity: Vec3,
+    delta: f32,
+) -> (Vec3, Vec3) {
+    let mut remaining_time = delta;
+
+    for iteration in 0..MAX_INTEGRATE_ITERATIONS {
+        let Ok(direction) = Dir3::new(velocity) else {
+            break;
+        };
+
+        let integrate_distance = remaining_time * velocity.length();
+
+        let hit = spatial_query
+            .shape_hits(
+                collider,
+                position,
+                rotation,
+                direction,
+                u32::MAX,
+                &ShapeCastConfig {
+                    max_distance: integrate_distance,
+                    ..default()
+                },
+                &SpatialQueryFilter::from_mask(collision_layers.filters)
+                    .with_excluded_entities(std::iter::once(character_entity)),
+            )
+            .into_iter()
+            .filter(|hit| rigid_body_q.contains(hit.entity))
+            .filter(|hit| -hit.normal1.dot(direction.into()) > 0.)
+            .next();
+
+        let Some(hit) = hit else {
+            position += direction * integrate_distance;
+            break;
+        };
+
+        let hit_normal = rotation.mul_vec3(-hit.normal2);
+
+        remaining_time -= hit.distance / velocity.length();
+
+        position += direction * hit.distance;
+        position += hit_normal * PLAYER_COLLISION_MARGIN;
+
+        // let alignment = velocity.normalize_or_zero().dot(hit_normal).abs();
+        // if alignment < 0.2 {
+        //     debug!("collision alignment: {}", alignment);
+        // }
+
+        velocity = velocity.reject_from(hit_normal);
+
+        if iteration == MAX_INTEGRATE_ITERATIONS - 1 {
+            debug!("Hit iteration limit");
+        }
+    }
+
+    (position, velocity)
+}
@@ -3,44 +3,50 @@ use bevy::prelude::*;
 use nevy::*;
 use serde::{Deserialize, Serialize};
 
-use crate::{character::controller::CharacterInput, GameLayer, ServerEntity};
+use crate::{GameLayer, ServerEntity, character::controller::CharacterInput};
 
 pub mod controller;
 
 pub fn build(app: &mut App) {
     controller::build(app);
 
-    app.add_message::<SpawnCharacter>();
     app.add_message::<SetLocalPlayer>();
     app.add_message::<CharacterStateUpdate>();
+    app.add_message::<CharacterStateAck>();
 }
 
-#[derive(Component, Default)]
+#[derive(Component, Default, Serialize, Deserialize, Clone)]
 #[require(
     Collider::capsule_endpoints(0.25, Vec3::Y * 0.25, Vec3::Y * 1.75),
     CollisionLayers::new([GameLayer::Players, GameLayer::Opaque], GameLayer::World),
 )]
 pub struct Character;
 
-/// Server -> Client message to initialize a new character.
-#[derive(Serialize, Deserialize)]
-pub struct SpawnCharacter {
-    pub server_entity: ServerEntity,
-}
-
 /// Server -> Client message to set an existing character as the local player.
 ///
-/// Ordering with [SpawnCharacter] is important.
+/// Must be sent after the [Character] component is replicated.
 #[derive(Serialize, Deserialize)]
 pub struct SetLocalPlayer {
     pub server_entity: ServerEntity,
 }
 
-/// Client -> Server message to update a character's transform and input state.
+/// Client -> Server message with the input a character used for one fixed update.
+///
+/// The client sends one of these every fixed update, numbered with an increasing `sequence`.
+/// The server replays them in order to simulate the character.
 #[derive(Serialize, Deserialize, Default)]
 pub struct CharacterStateUpdate {
+    pub sequence: u32,
+    pub input: CharacterInput,
+}
+
+/// Server -> Client message with the server's state of the local player's character
+/// after simulating the [CharacterStateUpdate] with `sequence`.
+///
+/// The client reconciles it's prediction against this.
+#[derive(Serialize, Deserialize)]
+pub struct CharacterStateAck {
+    pub sequence: u32,
     pub position: Vec3,
     pub velocity: Vec3,
-    pub rotation: Quat,
-    pub input: CharacterInput,
 }
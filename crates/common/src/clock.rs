@@ -0,0 +1,27 @@
+use std::time::Duration;
+
+use bevy::prelude::*;
+use nevy::AddMessage;
+use serde::{Deserialize, Serialize};
+
+pub fn build(app: &mut App) {
+    app.add_message::<TimePing>();
+    app.add_message::<TimePong>();
+}
+
+/// Client -> Server message to measure the round trip time and the offset between clocks.
+///
+/// The server responds straight away with a [TimePong].
+#[derive(Serialize, Deserialize)]
+pub struct TimePing {
+    pub client_time: Duration,
+}
+
+/// Server -> Client response to a [TimePing].
+#[derive(Serialize, Deserialize)]
+pub struct TimePong {
+    /// The `client_time` of the ping.
+    pub client_time: Duration,
+    /// The server's time when it responded.
+    pub server_time: Duration,
+}
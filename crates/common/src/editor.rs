@@ -5,6 +5,14 @@ use bevy::{
     prelude::*,
 };
 
+pub fn build(app: &mut App) {
+    app.register_type::<ParentGroup>();
+    app.register_type::<ElementGroup>();
+    app.register_type::<ItemInstance>();
+    app.register_type::<SaveMeta>();
+    app.register_type::<SaveType>();
+}
+
 /// points to the group an element is contained in
 #[derive(Component, Reflect, MapEntities)]
 #[reflect(Component, MapEntities)]
@@ -50,6 +58,17 @@ impl ElementGroup {
 //     }
 // }
 
+/// the root group of an item placed into a level
+///
+/// the elements in the group are copies of the item's elements,
+/// they get replaced when the item is re-synced
+#[derive(Component, Reflect, Default)]
+#[reflect(Component, Default)]
+pub struct ItemInstance {
+    /// path of the item file relative to the assets directory
+    pub path: String,
+}
+
 #[derive(Resource, Reflect, Default)]
 #[reflect(Resource)]
 pub struct SaveMeta {
@@ -62,3 +81,13 @@ pub enum SaveType {
     Level,
     Item,
 }
+
+impl SaveType {
+    /// The assets directory that files of this type are saved in.
+    pub fn directory(self) -> &'static str {
+        match self {
+            SaveType::Level => "levels",
+            SaveType::Item => "items",
+        }
+    }
+}
@@ -0,0 +1,70 @@
+//! Doors that have been locked shut.
+//!
+//! A locked door gets a barrier collider on both the server and clients,
+//! so that characters can't walk through it and agents can't see through it.
+
+use avian3d::prelude::*;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::GameLayer;
+
+/// The size of the barrier that blocks a locked door.
+pub const DOOR_SIZE: Vec3 = Vec3::new(1.2, 2.2, 0.2);
+
+pub fn build(app: &mut App) {
+    app.add_systems(Update, (remove_door_barriers, insert_door_barriers).chain());
+}
+
+/// A door that has been locked shut.
+///
+/// Replicated with where the door is so that clients can collide with it and show it.
+#[derive(Component, Serialize, Deserialize, Clone)]
+pub struct LockedDoor {
+    /// The bottom of the door.
+    pub position: Vec3,
+    pub rotation: Quat,
+}
+
+/// The barrier collider of a [LockedDoor].
+#[derive(Component)]
+#[relationship(relationship_target = DoorBarrier)]
+pub struct BarrierOfDoor(pub Entity);
+
+#[derive(Component)]
+#[relationship_target(relationship = BarrierOfDoor, linked_spawn)]
+pub struct DoorBarrier(Entity);
+
+fn insert_door_barriers(
+    mut commands: Commands,
+    door_q: Query<(Entity, &LockedDoor, Option<&DoorBarrier>), Changed<LockedDoor>>,
+) {
+    for (door_entity, door, barrier) in door_q.iter() {
+        if let Some(&DoorBarrier(barrier_entity)) = barrier {
+            commands.entity(barrier_entity).despawn();
+        }
+
+        commands.spawn((
+            BarrierOfDoor(door_entity),
+            RigidBody::Static,
+            Position(door.position + door.rotation * Vec3::Y * DOOR_SIZE.y / 2.),
+            Rotation(door.rotation),
+            Collider::cuboid(DOOR_SIZE.x, DOOR_SIZE.y, DOOR_SIZE.z),
+            CollisionLayers::new([GameLayer::World, GameLayer::Opaque], 0),
+        ));
+    }
+}
+
+fn remove_door_barriers(
+    mut commands: Commands,
+    mut unlocked_r: RemovedComponents<LockedDoor>,
+    barrier_q: Query<&DoorBarrier, Without<LockedDoor>>,
+) {
+    for door_entity in unlocked_r.read() {
+        let Ok(&DoorBarrier(barrier_entity)) = barrier_q.get(door_entity) else {
+            continue;
+        };
+
+        commands.entity(barrier_entity).despawn();
+    }
+}
@@ -1,22 +1,17 @@
 use avian3d::prelude::*;
 use bevy::{prelude::*, render::mesh::Indices};
-use nevy::AddMessage;
 use serde::{Deserialize, Serialize};
 
-use crate::ServerEntity;
-
 pub fn build(app: &mut App) {
-    app.add_systems(Update, load_mesh_colliders);
-
-    app.add_message::<InitializeGltfCollider>();
+    app.add_systems(Update, (insert_gltf_colliders, load_mesh_colliders).chain());
 }
 
-/// Server -> Client message to initialize a gltf collider.
-#[derive(Serialize, Deserialize)]
-pub struct InitializeGltfCollider {
-    pub entity: ServerEntity,
-    pub path: String,
-}
+/// The path of a gltf mesh to load as a [GltfCollider].
+///
+/// Replicated so that clients know which path to load.
+#[derive(Component, Serialize, Deserialize, Reflect, Default, Clone)]
+#[reflect(Component, Default)]
+pub struct GltfColliderPath(pub String);
 
 /// Will insert a [Collider] of the first mesh in a gltf asset.
 ///
@@ -28,6 +23,21 @@ pub struct InitializeGltfCollider {
 #[require(RigidBody::Static)]
 pub struct GltfCollider(pub Handle<Mesh>);
 
+fn insert_gltf_colliders(
+    mut commands: Commands,
+    collider_q: Query<(Entity, &GltfColliderPath), Changed<GltfColliderPath>>,
+    assets: Res<AssetServer>,
+) {
+    for (entity, path) in collider_q.iter() {
+        let handle = assets.load(&path.0);
+
+        commands
+            .entity(entity)
+            .remove::<Collider>()
+            .insert(GltfCollider(handle));
+    }
+}
+
 fn load_mesh_colliders(
     mut commands: Commands,
     collider_q: Query<(Entity, &GltfCollider), Without<Collider>>,
@@ -1,7 +1,9 @@
 use bevy::prelude::*;
 
+pub mod door;
 pub mod gltf_collider;
 
 pub fn build(app: &mut App) {
+    door::build(app);
     gltf_collider::build(app);
 }
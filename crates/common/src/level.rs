@@ -0,0 +1,159 @@
+//! Components that make up a level and are saved in level scenes.
+//!
+//! Every component here has to be registered in [build] to be loaded from a scene.
+
+use bevy::{
+    ecs::{entity::MapEntities, reflect::ReflectMapEntities},
+    prelude::*,
+};
+
+use crate::elements::gltf_collider::GltfColliderPath;
+
+pub fn build(app: &mut App) {
+    app.register_type::<GltfColliderPath>();
+    app.register_type::<NavMeshPath>();
+    app.register_type::<PatrolPoint>();
+    app.register_type::<PatrolTask>();
+    app.register_type::<AgentSpawner>();
+    app.register_type::<PlayerSpawnPoint>();
+    app.register_type::<LootSpawn>();
+    app.register_type::<Door>();
+    app.register_type::<ExtractionZone>();
+    app.register_type::<LightZone>();
+    app.register_type::<AlarmPanel>();
+    app.register_type::<SecurityCamera>();
+}
+
+/// The path of a gltf mesh to load as the nav mesh agents walk on.
+#[derive(Component, Reflect, Default)]
+#[reflect(Component, Default)]
+pub struct NavMeshPath(pub String);
+
+/// A point that an agent on a [PatrolTask] walks to and waits at.
+#[derive(Component, Reflect, Default)]
+#[reflect(Component, Default)]
+#[require(Transform)]
+pub struct PatrolPoint;
+
+/// A task for agents to walk between [PatrolPoint]s.
+#[derive(Component, Reflect, Default, MapEntities)]
+#[reflect(Component, Default, MapEntities)]
+pub struct PatrolTask {
+    #[entities]
+    pub points: Vec<Entity>,
+}
+
+/// Spawns an agent when the level is loaded that can be assigned to any of `tasks`.
+#[derive(Component, Reflect, Default, MapEntities)]
+#[reflect(Component, Default, MapEntities)]
+#[require(Transform)]
+pub struct AgentSpawner {
+    #[entities]
+    pub tasks: Vec<Entity>,
+}
+
+/// Where player characters are spawned.
+#[derive(Component, Reflect, Default)]
+#[reflect(Component, Default)]
+#[require(Transform)]
+pub struct PlayerSpawnPoint;
+
+/// Where a piece of loot is placed when the level is loaded.
+#[derive(Component, Reflect, Default)]
+#[reflect(Component, Default)]
+#[require(Transform)]
+pub struct LootSpawn;
+
+/// A door that can be opened and closed.
+#[derive(Component, Reflect, Default)]
+#[reflect(Component, Default)]
+#[require(Transform)]
+pub struct Door;
+
+/// An area the crew escapes through.
+///
+/// The heist succeeds once every connected player is inside one.
+#[derive(Component, Reflect)]
+#[reflect(Component, Default)]
+#[require(Transform)]
+pub struct ExtractionZone {
+    pub half_extents: Vec3,
+}
+
+impl Default for ExtractionZone {
+    fn default() -> Self {
+        ExtractionZone {
+            half_extents: Vec3::splat(2.),
+        }
+    }
+}
+
+impl ExtractionZone {
+    /// Whether a point is inside the zone when it has the given transform.
+    pub fn contains(&self, transform: &GlobalTransform, point: Vec3) -> bool {
+        box_contains(transform, self.half_extents, point)
+    }
+}
+
+/// An area with its own light level, such as a dark corner to hide in.
+///
+/// Places outside every light zone are fully lit.
+#[derive(Component, Reflect)]
+#[reflect(Component, Default)]
+#[require(Transform)]
+pub struct LightZone {
+    pub half_extents: Vec3,
+    /// From 0 for complete darkness to 1 for full light.
+    pub light_level: f32,
+}
+
+impl Default for LightZone {
+    fn default() -> Self {
+        LightZone {
+            half_extents: Vec3::splat(2.),
+            light_level: 0.2,
+        }
+    }
+}
+
+impl LightZone {
+    /// Whether a point is inside the zone when it has the given transform.
+    pub fn contains(&self, transform: &GlobalTransform, point: Vec3) -> bool {
+        box_contains(transform, self.half_extents, point)
+    }
+}
+
+/// A panel that guards run to to raise the alarm.
+#[derive(Component, Reflect, Default)]
+#[reflect(Component, Default)]
+#[require(Transform)]
+pub struct AlarmPanel;
+
+/// A camera that raises the alarm when it watches the crew for too long.
+///
+/// Looks along its forward direction.
+#[derive(Component, Reflect)]
+#[reflect(Component, Default)]
+#[require(Transform)]
+pub struct SecurityCamera {
+    /// The field of view measured in radians from the direction the camera is looking.
+    pub fov: f32,
+    /// The farthest distance the camera can see.
+    pub range: f32,
+}
+
+impl Default for SecurityCamera {
+    fn default() -> Self {
+        SecurityCamera {
+            fov: 30f32.to_radians(),
+            range: 15.,
+        }
+    }
+}
+
+/// Whether a point is inside a box with `half_extents` that has the given transform.
+pub fn box_contains(transform: &GlobalTransform, half_extents: Vec3, point: Vec3) -> bool {
+    let local_point = transform.affine().inverse().transform_point3(point);
+
+    local_point.abs().cmple(half_extents).all()
+}
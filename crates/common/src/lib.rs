@@ -1,14 +1,17 @@
 use avian3d::{PhysicsPlugins, prelude::*};
 use bevy::prelude::*;
-use nevy::AddMessage;
 use serde::{Deserialize, Serialize};
 
 pub mod agents;
+pub mod alarm;
 pub mod character;
+pub mod clock;
 pub mod editor;
 pub mod elements;
 pub mod level;
+pub mod match_state;
 pub mod networking;
+pub mod noise;
 pub mod physics;
 pub mod state;
 
@@ -20,13 +23,16 @@ impl Plugin for CommonPlugin {
         app.add_plugins(PhysicsPlugins::default());
 
         networking::build(app);
+        noise::build(app);
         character::build(app);
         physics::build(app);
+        clock::build(app);
         state::build(app);
+        match_state::build(app);
+        alarm::build(app);
+        level::build(app);
+        editor::build(app);
         elements::build(app);
-        agents::build(app);
-
-        app.add_message::<DebugStartLevel>();
 
         app.add_systems(Startup, spawn_debug_floor);
     }
@@ -77,6 +83,3 @@ fn spawn_debug_floor(mut commands: Commands) {
         CollisionLayers::new([GameLayer::World, GameLayer::Opaque], 0),
     ));
 }
-
-#[derive(Serialize, Deserialize)]
-pub struct DebugStartLevel;
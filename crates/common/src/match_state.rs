@@ -0,0 +1,61 @@
+use bevy::prelude::*;
+use nevy::AddMessage;
+use serde::{Deserialize, Serialize};
+
+pub fn build(app: &mut App) {
+    app.add_message::<SetMatchState>();
+    app.add_message::<LobbyPlayers>();
+    app.add_message::<SetReady>();
+    app.add_message::<StartMatch>();
+}
+
+/// The phases of a match, driven by the server.
+#[derive(States, Serialize, Deserialize, Default, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MatchState {
+    /// Players are joining and readying up.
+    #[default]
+    Lobby,
+    /// The level is being loaded.
+    Loading,
+    /// The level is loaded and players are being briefed before the heist starts.
+    Briefing,
+    InProgress,
+    /// The crew got away.
+    Extracted,
+    /// The heist failed.
+    Failed,
+    /// The outcome of the match is shown before returning to the lobby.
+    Results,
+}
+
+/// Server -> Client message sent when the [MatchState] changes and when a client joins.
+#[derive(Serialize, Deserialize)]
+pub struct SetMatchState {
+    pub state: MatchState,
+}
+
+/// Server -> Client message with the players in the lobby, sent whenever it changes.
+#[derive(Serialize, Deserialize)]
+pub struct LobbyPlayers {
+    pub players: Vec<LobbyPlayer>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct LobbyPlayer {
+    pub username: String,
+    pub ready: bool,
+    /// The host can start the match once everyone is ready.
+    pub host: bool,
+}
+
+/// Client -> Server message to ready up in the lobby.
+#[derive(Serialize, Deserialize)]
+pub struct SetReady {
+    pub ready: bool,
+}
+
+/// Client -> Server message from the host to start the match.
+///
+/// Ignored unless every player is ready.
+#[derive(Serialize, Deserialize)]
+pub struct StartMatch;
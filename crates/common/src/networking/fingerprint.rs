@@ -0,0 +1,51 @@
+use sha2::{Digest, Sha256};
+
+/// The SHA-256 hash of a DER encoded certificate.
+///
+/// The server logs the fingerprint of it's certificate on startup
+/// so that clients can pin it.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct CertificateFingerprint(pub [u8; 32]);
+
+impl CertificateFingerprint {
+    pub fn of(certificate_der: &[u8]) -> Self {
+        CertificateFingerprint(Sha256::digest(certificate_der).into())
+    }
+}
+
+impl std::fmt::Display for CertificateFingerprint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for byte in self.0 {
+            write!(f, "{:02x}", byte)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl std::str::FromStr for CertificateFingerprint {
+    type Err = &'static str;
+
+    /// Parses a hex encoded fingerprint, ignoring `:` separators.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let digits: Vec<u8> = s.bytes().filter(|&byte| byte != b':').collect();
+
+        if digits.len() != 64 {
+            return Err("A fingerprint should be 64 hex digits");
+        }
+
+        // `from_str_radix` would also accept a leading `+`
+        if !digits.iter().all(u8::is_ascii_hexdigit) {
+            return Err("Invalid hex digit");
+        }
+
+        let mut fingerprint = [0; 32];
+
+        for (byte, pair) in fingerprint.iter_mut().zip(digits.chunks(2)) {
+            let pair = std::str::from_utf8(pair).map_err(|_| "Invalid hex digit")?;
+            *byte = u8::from_str_radix(pair, 16).map_err(|_| "Invalid hex digit")?;
+        }
+
+        Ok(CertificateFingerprint(fingerprint))
+    }
+}
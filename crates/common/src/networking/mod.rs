@@ -1,6 +1,8 @@
 use bevy::prelude::*;
 use nevy::*;
 
+pub mod fingerprint;
+pub mod replicate_component;
 pub mod replicate_despawn;
 
 pub fn build(app: &mut App) {
@@ -11,6 +13,7 @@ pub fn build(app: &mut App) {
     ));
 
     replicate_despawn::build(app);
+    replicate_component::build(app);
 
     app.insert_resource(MessageStreamHeader::new(StreamHeader::Messages));
 
@@ -0,0 +1,80 @@
+//! Generic replication of components from the server to clients.
+//!
+//! Every replicated component is listed in [replicated_components].
+//! The server and client each implement [ReplicateComponents] to add their systems for every listed component,
+//! and the messages for each component are registered here so that they are in the same order on both.
+
+use std::marker::PhantomData;
+
+use bevy::prelude::*;
+use nevy::AddMessage;
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+
+use crate::{
+    ServerEntity,
+    agents::{Agent, AgentSuspicionMeter},
+    character::Character,
+    elements::{door::LockedDoor, gltf_collider::GltfColliderPath},
+};
+
+pub fn build(app: &mut App) {
+    replicated_components(app, &mut RegisterMessages);
+}
+
+/// A component that can be replicated from the server to clients.
+pub trait ReplicatedComponent: Component + Clone + Serialize + DeserializeOwned {}
+
+impl<T> ReplicatedComponent for T where T: Component + Clone + Serialize + DeserializeOwned {}
+
+/// Implemented by each side of the game to add what it needs to replicate a component.
+pub trait ReplicateComponents {
+    fn replicate<T: ReplicatedComponent>(&mut self, app: &mut App);
+}
+
+/// Calls [ReplicateComponents::replicate] for every replicated component.
+///
+/// Add new replicated components here.
+pub fn replicated_components(app: &mut App, replicate: &mut impl ReplicateComponents) {
+    replicate.replicate::<GltfColliderPath>(app);
+    replicate.replicate::<Agent>(app);
+    replicate.replicate::<AgentSuspicionMeter>(app);
+    replicate.replicate::<Character>(app);
+    replicate.replicate::<LockedDoor>(app);
+}
+
+struct RegisterMessages;
+
+impl ReplicateComponents for RegisterMessages {
+    fn replicate<T: ReplicatedComponent>(&mut self, app: &mut App) {
+        app.add_message::<UpdateComponent<T>>();
+        app.add_message::<RemoveComponent<T>>();
+    }
+}
+
+/// Server -> Client message to insert or update a component.
+///
+/// If the client doesn't have the entity yet it will be spawned.
+#[derive(Serialize, Deserialize)]
+#[serde(bound = "T: ReplicatedComponent")]
+pub struct UpdateComponent<T> {
+    pub entity: ServerEntity,
+    pub value: T,
+}
+
+/// Server -> Client message to remove a component.
+#[derive(Serialize, Deserialize)]
+#[serde(bound = "")]
+pub struct RemoveComponent<T> {
+    pub entity: ServerEntity,
+    #[serde(skip)]
+    _component: PhantomData<T>,
+}
+
+impl<T> RemoveComponent<T> {
+    pub fn new(entity: ServerEntity) -> Self {
+        RemoveComponent {
+            entity,
+            _component: PhantomData,
+        }
+    }
+}
@@ -8,8 +8,8 @@ pub fn build(app: &mut App) {
     app.add_message::<ServerEntityRemoved>();
 }
 
-/// Server -> Client message used by many parts of the game
-/// when simple despawning logic is all that is needed.
+/// Server -> Client message sent when an entity is despawned
+/// or stops being relevant to the client.
 #[derive(Serialize, Deserialize)]
 pub struct ServerEntityRemoved {
     pub entity: ServerEntity,
@@ -0,0 +1,52 @@
+//! Noises that agents can hear.
+
+use bevy::prelude::*;
+
+pub fn build(app: &mut App) {
+    app.add_event::<NoiseEvent>();
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum NoiseKind {
+    Footstep,
+    Jump,
+    Door,
+    BreakingGlass,
+    Drilling,
+}
+
+impl NoiseKind {
+    /// How far away the noise can usually be heard over open ground.
+    pub fn loudness(self) -> f32 {
+        match self {
+            NoiseKind::Footstep => 6.,
+            NoiseKind::Jump => 10.,
+            NoiseKind::Door => 12.,
+            NoiseKind::BreakingGlass => 30.,
+            NoiseKind::Drilling => 25.,
+        }
+    }
+}
+
+/// Sent when something makes a noise.
+#[derive(Event, Clone, Copy)]
+pub struct NoiseEvent {
+    /// What made the noise.
+    pub source: Entity,
+    pub position: Vec3,
+    /// How far away the noise can be heard over open ground.
+    pub loudness: f32,
+    pub kind: NoiseKind,
+}
+
+impl NoiseEvent {
+    /// A noise at the usual [loudness](NoiseKind::loudness) of it's kind.
+    pub fn new(source: Entity, position: Vec3, kind: NoiseKind) -> Self {
+        NoiseEvent {
+            source,
+            position,
+            loudness: kind.loudness(),
+            kind,
+        }
+    }
+}
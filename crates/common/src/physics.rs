@@ -6,31 +6,230 @@ use serde::{Deserialize, Serialize};
 
 use crate::ServerEntity;
 
+/// Positions are quantized to this many units per metre.
+const POSITION_RESOLUTION: f32 = 512.;
+/// Velocities are quantized to this many units per metre per second.
+const VELOCITY_RESOLUTION: f32 = 256.;
+/// The bits used for each of the three smallest components of a quantized rotation.
+const ROTATION_COMPONENT_BITS: u32 = 10;
+/// How many snapshots the server remembers for each client to use as baselines,
+/// and so how many received snapshots the client needs to keep.
+///
+/// If a client hasn't acknowledged any of these it will be sent a full snapshot.
+pub const SNAPSHOT_HISTORY_LENGTH: usize = 32;
+
 pub fn build(app: &mut App) {
     app.add_message::<PhysicsSnapshot>();
-    app.add_message::<TimeSample>();
+    app.add_message::<PhysicsSnapshotAck>();
 }
 
 /// Physics snapshot sent from server to client.
+///
+/// Bodies are sent relative to the `baseline` snapshot, the last one the client acknowledged.
+/// Bodies that haven't changed since the baseline are left out
+/// and bodies that were in the baseline but aren't replicated anymore are listed in `removed`.
+///
+/// If there is no baseline every body is sent in full.
 #[derive(Serialize, Deserialize)]
 pub struct PhysicsSnapshot {
-    pub bodies: Vec<(ServerEntity, PhysicsBodySnapshot)>,
+    pub sequence: u32,
+    pub baseline: Option<u32>,
     pub time: Duration,
+    pub bodies: Vec<(ServerEntity, BodyUpdate)>,
+    pub removed: Vec<ServerEntity>,
 }
 
-#[derive(Serialize, Deserialize, Clone, Copy)]
+/// Client -> Server message to acknowledge that a [PhysicsSnapshot] was received
+/// and can be used as a baseline.
+#[derive(Serialize, Deserialize)]
+pub struct PhysicsSnapshotAck {
+    pub sequence: u32,
+}
+
+/// The dequantized state of a physics body.
+#[derive(Clone, Copy)]
 pub struct PhysicsBodySnapshot {
     pub position: Vec3,
     pub linear_velocity: Vec3,
     pub rotation: Quat,
 }
 
-/// A sample of the server's physics time sent
-/// to clients for the clients to average.
+/// The state of a physics body quantized for sending over the network.
 ///
-/// Is it's own message and not part of snapshots
-/// to minimize message delay.
-#[derive(Serialize, Deserialize)]
-pub struct TimeSample {
-    pub time: Duration,
+/// Rotations are stored using the smallest three components of the quaternion.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct QuantizedBody {
+    pub position: IVec3,
+    pub linear_velocity: IVec3,
+    pub rotation: [u8; 4],
+}
+
+/// The state of a body in a [PhysicsSnapshot].
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub enum BodyUpdate {
+    /// The body wasn't in the baseline.
+    Full(QuantizedBody),
+    /// The difference in position and velocity from the body in the baseline.
+    ///
+    /// Small differences take up fewer bytes when encoded.
+    Delta {
+        position: IVec3,
+        linear_velocity: IVec3,
+        rotation: [u8; 4],
+    },
+}
+
+impl QuantizedBody {
+    pub fn new(position: Vec3, linear_velocity: Vec3, rotation: Quat) -> Self {
+        QuantizedBody {
+            position: (position * POSITION_RESOLUTION).round().as_ivec3(),
+            linear_velocity: (linear_velocity * VELOCITY_RESOLUTION).round().as_ivec3(),
+            rotation: quantize_rotation(rotation).to_le_bytes(),
+        }
+    }
+
+    pub fn dequantize(&self) -> PhysicsBodySnapshot {
+        PhysicsBodySnapshot {
+            position: self.position.as_vec3() / POSITION_RESOLUTION,
+            linear_velocity: self.linear_velocity.as_vec3() / VELOCITY_RESOLUTION,
+            rotation: dequantize_rotation(u32::from_le_bytes(self.rotation)),
+        }
+    }
+
+    /// Creates the update needed to get from the `baseline` state of this body to this one.
+    pub fn encode(&self, baseline: Option<&QuantizedBody>) -> BodyUpdate {
+        match baseline {
+            None => BodyUpdate::Full(*self),
+            Some(baseline) => BodyUpdate::Delta {
+                position: self.position - baseline.position,
+                linear_velocity: self.linear_velocity - baseline.linear_velocity,
+                rotation: self.rotation,
+            },
+        }
+    }
+}
+
+impl BodyUpdate {
+    /// Applies this update to the `baseline` state of the body.
+    ///
+    /// Returns `None` if this is a delta and there is no baseline.
+    pub fn decode(&self, baseline: Option<&QuantizedBody>) -> Option<QuantizedBody> {
+        match (self, baseline) {
+            (BodyUpdate::Full(body), _) => Some(*body),
+            (
+                &BodyUpdate::Delta {
+                    position,
+                    linear_velocity,
+                    rotation,
+                },
+                Some(baseline),
+            ) => Some(QuantizedBody {
+                position: baseline.position + position,
+                linear_velocity: baseline.linear_velocity + linear_velocity,
+                rotation,
+            }),
+            (BodyUpdate::Delta { .. }, None) => None,
+        }
+    }
+}
+
+/// Packs a rotation into 32 bits by dropping the largest component,
+/// which can be reconstructed because the quaternion is normalized.
+///
+/// The top two bits are the index of the dropped component.
+fn quantize_rotation(rotation: Quat) -> u32 {
+    let mut components = rotation.normalize().to_array();
+
+    let largest = (0..4)
+        .max_by(|&a, &b| components[a].abs().total_cmp(&components[b].abs()))
+        .expect("quaternion has four components");
+
+    // q and -q are the same rotation, make the dropped component positive
+    if components[largest] < 0. {
+        for component in components.iter_mut() {
+            *component = -*component;
+        }
+    }
+
+    let max_value = (1 << ROTATION_COMPONENT_BITS) - 1;
+    let mut packed = (largest as u32) << (ROTATION_COMPONENT_BITS * 3);
+
+    for (slot, component) in (0..4)
+        .filter(|&i| i != largest)
+        .map(|i| components[i])
+        .enumerate()
+    {
+        // the other components are all within this range
+        let normalized = component / std::f32::consts::FRAC_1_SQRT_2 * 0.5 + 0.5;
+        let quantized = (normalized.clamp(0., 1.) * max_value as f32).round() as u32;
+
+        packed |= quantized << (ROTATION_COMPONENT_BITS * (2 - slot as u32));
+    }
+
+    packed
+}
+
+fn dequantize_rotation(packed: u32) -> Quat {
+    let max_value = (1 << ROTATION_COMPONENT_BITS) - 1;
+    let largest = (packed >> (ROTATION_COMPONENT_BITS * 3)) as usize & 0b11;
+
+    let mut components = [0.; 4];
+    let mut sum_squares = 0.;
+
+    for (slot, index) in (0..4).filter(|&i| i != largest).enumerate() {
+        let quantized = (packed >> (ROTATION_COMPONENT_BITS * (2 - slot as u32))) & max_value;
+        let normalized = quantized as f32 / max_value as f32;
+        let component = (normalized - 0.5) * 2. * std::f32::consts::FRAC_1_SQRT_2;
+
+        components[index] = component;
+        sum_squares += component * component;
+    }
+
+    components[largest] = (1. - sum_squares).max(0.).sqrt();
+
+    Quat::from_array(components).normalize()
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::prelude::*;
+
+    use crate::physics::{BodyUpdate, QuantizedBody};
+
+    #[test]
+    fn quantized_body_round_trip() {
+        let position = Vec3::new(12.345, -0.5, 1000.25);
+        let linear_velocity = Vec3::new(-3., 0.01, 2.5);
+        let rotation = Quat::from_euler(EulerRot::YXZ, 2.5, -0.3, 0.1);
+
+        let body = QuantizedBody::new(position, linear_velocity, rotation).dequantize();
+
+        assert!(body.position.distance(position) < 0.002);
+        assert!(body.linear_velocity.distance(linear_velocity) < 0.004);
+        assert!(body.rotation.angle_between(rotation) < 0.01);
+    }
+
+    #[test]
+    fn negated_rotation_round_trip() {
+        let rotation = -Quat::from_axis_angle(Vec3::X, 1.);
+
+        let body = QuantizedBody::new(Vec3::ZERO, Vec3::ZERO, rotation).dequantize();
+
+        assert!(body.rotation.angle_between(rotation) < 0.01);
+    }
+
+    #[test]
+    fn delta_round_trip() {
+        let baseline = QuantizedBody::new(Vec3::new(5., 0., 5.), Vec3::X, Quat::IDENTITY);
+        let body = QuantizedBody::new(Vec3::new(5.2, 0., 4.9), Vec3::Z, Quat::from_rotation_y(1.));
+
+        let update = body.encode(Some(&baseline));
+
+        let BodyUpdate::Delta { .. } = update else {
+            panic!("Body with a baseline should be encoded as a delta");
+        };
+
+        assert_eq!(update.decode(Some(&baseline)), Some(body));
+        assert_eq!(update.decode(None), None);
+    }
 }
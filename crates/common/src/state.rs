@@ -2,11 +2,77 @@ use bevy::prelude::*;
 use nevy::AddMessage;
 use serde::{Deserialize, Serialize};
 
+/// Increase whenever messages change in a way that older clients or servers can't understand.
+pub const PROTOCOL_VERSION: u32 = 3;
+pub const MAX_USERNAME_LENGTH: usize = 24;
+
 pub fn build(app: &mut App) {
     app.add_message::<JoinGameRequest>();
+    app.add_message::<JoinGameResponse>();
 }
 
+/// Client -> Server message to join the game.
+///
+/// The server responds with a [JoinGameResponse].
 #[derive(Serialize, Deserialize)]
 pub struct JoinGameRequest {
+    pub protocol_version: u32,
     pub username: String,
+    /// Required if the server is private.
+    pub password: Option<String>,
+    /// The token from a previous session, used to rejoin as the same player after disconnecting.
+    pub session_token: Option<SessionToken>,
+}
+
+/// Server -> Client response to a [JoinGameRequest].
+#[derive(Serialize, Deserialize)]
+pub enum JoinGameResponse {
+    Accepted {
+        /// Send this in [JoinGameRequest::session_token] to rejoin after disconnecting.
+        session_token: SessionToken,
+    },
+    Rejected {
+        reason: JoinRejectReason,
+    },
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub enum JoinRejectReason {
+    ProtocolMismatch { server_version: u32 },
+    WrongPassword,
+    InvalidUsername,
+    UsernameTaken,
+    AlreadyJoined,
+}
+
+impl std::fmt::Display for JoinRejectReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            JoinRejectReason::ProtocolMismatch { server_version } => write!(
+                f,
+                "Server is running protocol version {} but this client is version {}",
+                server_version, PROTOCOL_VERSION
+            ),
+            JoinRejectReason::WrongPassword => write!(f, "Wrong password"),
+            JoinRejectReason::InvalidUsername => write!(f, "Invalid username"),
+            JoinRejectReason::UsernameTaken => write!(f, "Username is already taken"),
+            JoinRejectReason::AlreadyJoined => write!(f, "Already joined"),
+        }
+    }
+}
+
+/// A secret given to a client when it joins that identifies it's session.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct SessionToken(pub u128);
+
+/// Checks that a username is between 1 and [MAX_USERNAME_LENGTH] characters
+/// of letters, numbers, spaces, `-` or `_`, and doesn't start or end with a space.
+pub fn is_valid_username(username: &str) -> bool {
+    let length = username.chars().count();
+
+    (1..=MAX_USERNAME_LENGTH).contains(&length)
+        && username.trim() == username
+        && username
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == ' ' || c == '-' || c == '_')
 }
@@ -0,0 +1,230 @@
+//! Copying, pasting and deleting elements.
+//!
+//! Copies are scenes of the selected elements and the elements of selected groups.
+//! Pasting writes the scene to the world as new elements, references between the copied elements
+//! are mapped to the new elements.
+
+use bevy::{ecs::entity::EntityHashMap, prelude::*};
+use common::editor::ParentGroup;
+
+use crate::{
+    file::build_scene,
+    history::begin_edit,
+    item::group_elements,
+    level::LevelElement,
+    ui::{
+        element_selection::{SelectedElement, select_elements},
+        wants_keyboard_input,
+    },
+};
+
+pub fn build(app: &mut App) {
+    app.init_resource::<Clipboard>();
+
+    app.add_systems(Update, clipboard_shortcuts);
+}
+
+#[derive(Resource, Default)]
+pub struct Clipboard {
+    scene: Option<DynamicScene>,
+}
+
+impl Clipboard {
+    pub fn is_empty(&self) -> bool {
+        self.scene.is_none()
+    }
+}
+
+/// The selected elements and the elements in selected groups and their nested groups.
+fn selected_elements(world: &mut World) -> Vec<Entity> {
+    let selected: Vec<Entity> = world
+        .query_filtered::<Entity, (With<SelectedElement>, With<LevelElement>)>()
+        .iter(world)
+        .collect();
+
+    let mut element_entities = Vec::new();
+
+    for element_entity in selected {
+        for entity in std::iter::once(element_entity).chain(group_elements(world, element_entity)) {
+            if !element_entities.contains(&entity) {
+                element_entities.push(entity);
+            }
+        }
+    }
+
+    element_entities
+}
+
+fn copy_scene(world: &World, element_entities: &[Entity]) -> DynamicScene {
+    let mut scene = build_scene(world, element_entities.iter().copied());
+
+    // only the save meta is extracted, which a copy doesn't need
+    scene.resources.clear();
+
+    scene
+}
+
+/// Writes new copies of the elements in a scene to the world.
+///
+/// References to elements that weren't copied, like the group of a copied element,
+/// keep pointing to the same elements.
+///
+/// Returns the new elements.
+fn paste_scene(world: &mut World, scene: &DynamicScene) -> Result<Vec<Entity>> {
+    let mut entity_map: EntityHashMap<Entity> = world
+        .query_filtered::<Entity, With<LevelElement>>()
+        .iter(world)
+        .map(|element_entity| (element_entity, element_entity))
+        .collect();
+
+    // the copied elements are mapped to new entities instead of being written over
+    for scene_entity in scene.entities.iter() {
+        entity_map.remove(&scene_entity.entity);
+    }
+
+    scene.write_to_world(world, &mut entity_map)?;
+
+    let new_entities: Vec<Entity> = scene
+        .entities
+        .iter()
+        .map(|scene_entity| entity_map[&scene_entity.entity])
+        .collect();
+
+    for &element_entity in new_entities.iter() {
+        world.entity_mut(element_entity).insert(LevelElement);
+    }
+
+    // relationship hooks aren't run when writing a scene.
+    // copied groups already contain their copied elements,
+    // groups that weren't copied have to be told about their new elements
+    for &element_entity in new_entities.iter() {
+        let Some(&ParentGroup(group_entity)) = world.get::<ParentGroup>(element_entity) else {
+            continue;
+        };
+
+        if new_entities.contains(&group_entity) {
+            continue;
+        }
+
+        if world.get_entity(group_entity).is_ok() {
+            world
+                .entity_mut(element_entity)
+                .insert(ParentGroup(group_entity));
+        } else {
+            world.entity_mut(element_entity).remove::<ParentGroup>();
+        }
+    }
+
+    world.flush();
+
+    Ok(new_entities)
+}
+
+/// Pastes a scene as an edit and selects the top level elements that were pasted.
+fn paste_as_edit(world: &mut World, name: &str, scene: &DynamicScene) {
+    let edit = begin_edit(world, name, []);
+
+    let new_entities = match paste_scene(world, scene) {
+        Ok(new_entities) => new_entities,
+        Err(err) => {
+            error!("Failed to paste elements: {}", err);
+            return;
+        }
+    };
+
+    let top_level: Vec<Entity> = new_entities
+        .iter()
+        .copied()
+        .filter(|&element_entity| {
+            world
+                .get::<ParentGroup>(element_entity)
+                .is_none_or(|&ParentGroup(group_entity)| !new_entities.contains(&group_entity))
+        })
+        .collect();
+
+    edit.finish(world, new_entities);
+
+    select_elements(world, &top_level);
+}
+
+pub fn copy_selected(world: &mut World) {
+    let element_entities = selected_elements(world);
+
+    if element_entities.is_empty() {
+        return;
+    }
+
+    let scene = copy_scene(world, &element_entities);
+    world.resource_mut::<Clipboard>().scene = Some(scene);
+
+    debug!("Copied {} elements", element_entities.len());
+}
+
+pub fn paste(world: &mut World) {
+    let Some(scene) = world.resource_mut::<Clipboard>().scene.take() else {
+        return;
+    };
+
+    paste_as_edit(world, "Paste", &scene);
+
+    world.resource_mut::<Clipboard>().scene = Some(scene);
+}
+
+/// Pastes a copy of the selection without changing the clipboard.
+pub fn duplicate_selected(world: &mut World) {
+    let element_entities = selected_elements(world);
+
+    if element_entities.is_empty() {
+        return;
+    }
+
+    let scene = copy_scene(world, &element_entities);
+
+    paste_as_edit(world, "Duplicate", &scene);
+}
+
+/// Despawns the selected elements and the elements in selected groups and their nested groups.
+pub fn delete_selected(world: &mut World) {
+    let element_entities = selected_elements(world);
+
+    if element_entities.is_empty() {
+        return;
+    }
+
+    let edit = begin_edit(world, "Delete", element_entities.iter().copied());
+
+    for element_entity in element_entities {
+        world.despawn(element_entity);
+    }
+
+    edit.finish(world, []);
+}
+
+/// Ctrl+C copies, Ctrl+V pastes, Ctrl+D duplicates and Delete deletes.
+fn clipboard_shortcuts(world: &mut World) {
+    let keys = world.resource::<ButtonInput<KeyCode>>();
+
+    let control = keys.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]);
+
+    let action: Option<fn(&mut World)> = if keys.just_pressed(KeyCode::Delete) {
+        Some(delete_selected)
+    } else if control && keys.just_pressed(KeyCode::KeyC) {
+        Some(copy_selected)
+    } else if control && keys.just_pressed(KeyCode::KeyV) {
+        Some(paste)
+    } else if control && keys.just_pressed(KeyCode::KeyD) {
+        Some(duplicate_selected)
+    } else {
+        None
+    };
+
+    let Some(action) = action else {
+        return;
+    };
+
+    if wants_keyboard_input(world) {
+        return;
+    }
+
+    action(world);
+}
@@ -0,0 +1,152 @@
+//! Saving and opening the elements being edited as scene files.
+//!
+//! Files are [DynamicScene]s with a [SaveMeta] resource, so a saved level can be loaded by the server directly.
+
+use std::path::{Path, PathBuf};
+
+use bevy::{
+    asset::io::file::FileAssetReader,
+    ecs::entity::EntityHashMap,
+    prelude::*,
+    render::{primitives::Aabb, view::VisibilityClass},
+    scene::{SceneFilter, ron, serde::SceneDeserializer},
+};
+use common::editor::SaveMeta;
+use serde::de::DeserializeSeed;
+
+use crate::{history::EditorHistory, level::LevelElement};
+
+/// The assets directory relative to the asset server's base path, see [assets_directory].
+pub const ASSETS_DIRECTORY: &str = "../../assets";
+
+pub fn build(app: &mut App) {
+    app.init_resource::<CurrentFile>();
+    app.init_resource::<SaveMeta>();
+}
+
+/// The file being edited.
+#[derive(Resource, Default)]
+pub struct CurrentFile {
+    /// The path of the file relative to the assets directory, if it has been saved.
+    pub path: Option<PathBuf>,
+}
+
+/// The directory that file paths are relative to.
+///
+/// Resolved the same way as the asset server's, so that it doesn't depend on the working directory.
+pub fn assets_directory() -> PathBuf {
+    FileAssetReader::get_base_path().join(ASSETS_DIRECTORY)
+}
+
+/// Components that are derived at runtime or only used by the editor and shouldn't be saved.
+pub fn component_filter() -> SceneFilter {
+    SceneFilter::allow_all()
+        .deny::<GlobalTransform>()
+        .deny::<Visibility>()
+        .deny::<InheritedVisibility>()
+        .deny::<ViewVisibility>()
+        .deny::<VisibilityClass>()
+        .deny::<Aabb>()
+        .deny::<Mesh3d>()
+        .deny::<MeshMaterial3d<StandardMaterial>>()
+        .deny::<ChildOf>()
+        .deny::<Children>()
+}
+
+/// Creates a scene of `element_entities` and the [SaveMeta].
+pub fn build_scene(world: &World, element_entities: impl Iterator<Item = Entity>) -> DynamicScene {
+    DynamicSceneBuilder::from_world(world)
+        .with_component_filter(component_filter())
+        .deny_all_resources()
+        .allow_resource::<SaveMeta>()
+        .extract_entities(element_entities)
+        .extract_resources()
+        .build()
+}
+
+pub fn read_scene_file(world: &World, path: &Path) -> Result<DynamicScene> {
+    let serialized = std::fs::read_to_string(assets_directory().join(path))?;
+
+    let type_registry = world.resource::<AppTypeRegistry>().read();
+
+    let mut deserializer = ron::Deserializer::from_str(&serialized)?;
+
+    let scene = SceneDeserializer {
+        type_registry: &type_registry,
+    }
+    .deserialize(&mut deserializer)?;
+
+    Ok(scene)
+}
+
+pub fn write_scene_file(world: &World, scene: &DynamicScene, path: &Path) -> Result {
+    let serialized = scene.serialize(&world.resource::<AppTypeRegistry>().read())?;
+
+    std::fs::write(assets_directory().join(path), serialized)?;
+
+    Ok(())
+}
+
+/// Writes the entities of a scene to the world as elements.
+///
+/// Returns the map from the scene's entities to the spawned elements.
+pub fn spawn_elements(world: &mut World, scene: &DynamicScene) -> Result<EntityHashMap<Entity>> {
+    // entity references such as groups are mapped to the new entities
+    let mut entity_map = EntityHashMap::default();
+    scene.write_to_world(world, &mut entity_map)?;
+
+    for &element_entity in entity_map.values() {
+        world.entity_mut(element_entity).insert(LevelElement);
+    }
+
+    Ok(entity_map)
+}
+
+/// Despawns every element and starts a new file.
+pub fn new_file(world: &mut World) {
+    let element_entities: Vec<Entity> = world
+        .query_filtered::<Entity, With<LevelElement>>()
+        .iter(world)
+        .collect();
+
+    for element_entity in element_entities {
+        world.despawn(element_entity);
+    }
+
+    world.insert_resource(CurrentFile::default());
+    world.insert_resource(SaveMeta::default());
+    world.resource_mut::<EditorHistory>().clear();
+}
+
+/// Replaces every element with the ones in a file.
+pub fn open_file(world: &mut World, path: &Path) -> Result {
+    let scene = read_scene_file(world, path)?;
+
+    new_file(world);
+
+    spawn_elements(world, &scene)?;
+
+    world.resource_mut::<CurrentFile>().path = Some(path.to_owned());
+
+    info!("Opened {:?}", path);
+
+    Ok(())
+}
+
+/// Saves every element to a file.
+pub fn save_file(world: &mut World, path: &Path) -> Result {
+    let element_entities: Vec<Entity> = world
+        .query_filtered::<Entity, With<LevelElement>>()
+        .iter(world)
+        .collect();
+
+    let scene = build_scene(world, element_entities.into_iter());
+
+    write_scene_file(world, &scene, path)?;
+
+    world.resource_mut::<CurrentFile>().path = Some(path.to_owned());
+
+    info!("Saved {:?}", path);
+
+    Ok(())
+}
@@ -0,0 +1,406 @@
+//! Undoing and redoing edits to the elements.
+//!
+//! An edit is recorded as the saved components of the elements it changed, before and after the edit.
+//! Elements that were despawned are respawned as new entities when an edit is undone or redone,
+//! the history remembers which entity they are now so older edits and references to them still work.
+
+use std::{any::TypeId, time::Duration};
+
+use bevy::{
+    ecs::{entity::EntityHashMap, reflect::ReflectMapEntities, relationship::RelationshipHookMode},
+    prelude::*,
+    reflect::TypeRegistry,
+};
+use common::editor::ElementGroup;
+
+use crate::{
+    file::component_filter, level::LevelElement, ui::wants_keyboard_input,
+    viewport::gizmo::TransformGizmo,
+};
+
+/// How long after a continuous edit another one can be merged into it.
+const MERGE_TIME: Duration = Duration::from_millis(500);
+
+pub fn build(app: &mut App) {
+    app.init_resource::<EditorHistory>();
+
+    app.add_systems(Update, undo_shortcuts);
+}
+
+#[derive(Resource, Default)]
+pub struct EditorHistory {
+    undo_stack: Vec<Edit>,
+    redo_stack: Vec<Edit>,
+    /// The entities that elements were recorded as, and the entity they were respawned as.
+    respawned: EntityHashMap<Entity>,
+}
+
+struct Edit {
+    name: String,
+    before: Vec<ElementState>,
+    after: Vec<ElementState>,
+    /// Whether later changes of the same elements can be merged into this edit.
+    continuous: bool,
+    finished: Duration,
+}
+
+/// The saved components of an element, or `None` if it didn't exist.
+struct ElementState {
+    entity: Entity,
+    components: Option<Vec<Box<dyn PartialReflect>>>,
+}
+
+/// An edit that has been started with [begin_edit].
+pub struct PendingEdit {
+    name: String,
+    before: Vec<ElementState>,
+}
+
+fn capture(world: &World, element_entities: &[Entity]) -> Vec<ElementState> {
+    let scene = DynamicSceneBuilder::from_world(world)
+        .with_component_filter(component_filter())
+        .deny_all_resources()
+        .extract_entities(
+            element_entities
+                .iter()
+                .copied()
+                .filter(|&element_entity| world.get_entity(element_entity).is_ok()),
+        )
+        .build();
+
+    let mut extracted: EntityHashMap<Vec<Box<dyn PartialReflect>>> = scene
+        .entities
+        .into_iter()
+        .map(|scene_entity| (scene_entity.entity, scene_entity.components))
+        .collect();
+
+    element_entities
+        .iter()
+        .map(|&entity| ElementState {
+            entity,
+            components: extracted.remove(&entity),
+        })
+        .collect()
+}
+
+fn same_components(a: &[Box<dyn PartialReflect>], b: &[Box<dyn PartialReflect>]) -> bool {
+    a.len() == b.len()
+        && a.iter().all(|component| {
+            b.iter().any(|other| {
+                component
+                    .reflect_partial_eq(other.as_partial_reflect())
+                    .unwrap_or(false)
+            })
+        })
+}
+
+fn same_state(a: &ElementState, b: &ElementState) -> bool {
+    a.entity == b.entity
+        && match (&a.components, &b.components) {
+            (Some(a), Some(b)) => same_components(a, b),
+            (None, None) => true,
+            _ => false,
+        }
+}
+
+/// Records the elements that are about to be edited.
+///
+/// Elements spawned by the edit are given to [PendingEdit::finish] instead.
+pub fn begin_edit(
+    world: &World,
+    name: impl Into<String>,
+    element_entities: impl IntoIterator<Item = Entity>,
+) -> PendingEdit {
+    let element_entities: Vec<Entity> = element_entities.into_iter().collect();
+
+    PendingEdit {
+        name: name.into(),
+        before: capture(world, &element_entities),
+    }
+}
+
+impl PendingEdit {
+    /// Records the elements after the edit and adds it to the history if anything changed.
+    pub fn finish(self, world: &mut World, new_entities: impl IntoIterator<Item = Entity>) {
+        self.finish_edit(world, new_entities.into_iter().collect(), false);
+    }
+
+    /// Like [PendingEdit::finish], but merges into the last edit
+    /// if it was the same continuous change, like dragging a value in the inspector.
+    pub fn finish_continuous(self, world: &mut World) {
+        self.finish_edit(world, Vec::new(), true);
+    }
+
+    fn finish_edit(mut self, world: &mut World, new_entities: Vec<Entity>, continuous: bool) {
+        for &entity in new_entities.iter() {
+            if self.before.iter().all(|state| state.entity != entity) {
+                self.before.push(ElementState {
+                    entity,
+                    components: None,
+                });
+            }
+        }
+
+        let element_entities: Vec<Entity> = self.before.iter().map(|state| state.entity).collect();
+        let after = capture(world, &element_entities);
+
+        if self
+            .before
+            .iter()
+            .zip(after.iter())
+            .all(|(before, after)| same_state(before, after))
+        {
+            return;
+        }
+
+        let finished = world.resource::<Time<Real>>().elapsed();
+        let mut history = world.resource_mut::<EditorHistory>();
+
+        history.redo_stack.clear();
+
+        if let Some(last) = history.undo_stack.last_mut() {
+            if continuous
+                && last.continuous
+                && last.name == self.name
+                && finished.saturating_sub(last.finished) < MERGE_TIME
+                && last
+                    .after
+                    .iter()
+                    .map(|state| state.entity)
+                    .eq(element_entities.iter().copied())
+            {
+                last.after = after;
+                last.finished = finished;
+                return;
+            }
+        }
+
+        debug!("Recorded edit \"{}\"", self.name);
+
+        history.undo_stack.push(Edit {
+            name: self.name,
+            before: self.before,
+            after,
+            continuous,
+            finished,
+        });
+    }
+}
+
+impl EditorHistory {
+    /// The name of the edit that would be undone.
+    pub fn next_undo(&self) -> Option<&str> {
+        self.undo_stack.last().map(|edit| edit.name.as_str())
+    }
+
+    /// The name of the edit that would be redone.
+    pub fn next_redo(&self) -> Option<&str> {
+        self.redo_stack.last().map(|edit| edit.name.as_str())
+    }
+
+    /// Forgets every edit, used when the elements are replaced.
+    pub fn clear(&mut self) {
+        *self = EditorHistory::default();
+    }
+
+    fn current_entity(&self, entity: Entity) -> Entity {
+        self.respawned.get(&entity).copied().unwrap_or(entity)
+    }
+
+    fn set_respawned(&mut self, old_entity: Entity, new_entity: Entity) {
+        for entity in self.respawned.values_mut() {
+            if *entity == old_entity {
+                *entity = new_entity;
+            }
+        }
+
+        self.respawned.insert(old_entity, new_entity);
+    }
+
+    /// Puts the elements back into the recorded states.
+    fn restore(&mut self, world: &mut World, states: &[ElementState]) {
+        let type_registry = world.resource::<AppTypeRegistry>().clone();
+        let type_registry = type_registry.read();
+        let filter = component_filter();
+
+        // groups are despawned first so the hooks removing their elements from them
+        // don't remove the groups the elements are restored into
+        for state in states.iter().filter(|state| state.components.is_none()) {
+            let entity = self.current_entity(state.entity);
+
+            if world.get_entity(entity).is_ok() {
+                world.despawn(entity);
+            }
+        }
+
+        world.flush();
+
+        let mut respawned = false;
+
+        for state in states.iter().filter(|state| state.components.is_some()) {
+            let entity = self.current_entity(state.entity);
+
+            if world.get_entity(entity).is_err() {
+                let new_entity = world.spawn(LevelElement).id();
+                self.set_respawned(entity, new_entity);
+                respawned = true;
+            }
+        }
+
+        for state in states.iter() {
+            let Some(components) = &state.components else {
+                continue;
+            };
+
+            let entity = self.current_entity(state.entity);
+
+            // components are replaced rather than applied, applying a shorter list doesn't remove the extra items.
+            // element groups are rebuilt by the relationship hooks of their elements
+            let removed: Vec<&ReflectComponent> = world
+                .entity(entity)
+                .archetype()
+                .components()
+                .filter_map(|component_id| world.components().get_info(component_id)?.type_id())
+                .filter(|&type_id| {
+                    filter.is_allowed_by_id(type_id) && type_id != TypeId::of::<ElementGroup>()
+                })
+                .filter_map(|type_id| type_registry.get_type_data::<ReflectComponent>(type_id))
+                .collect();
+
+            let mut element = world.entity_mut(entity);
+
+            for reflect_component in removed {
+                reflect_component.remove(&mut element);
+            }
+
+            for component in components.iter() {
+                if component.represents::<ElementGroup>() {
+                    continue;
+                }
+
+                let Some(reflect_component) =
+                    component.get_represented_type_info().and_then(|info| {
+                        type_registry.get_type_data::<ReflectComponent>(info.type_id())
+                    })
+                else {
+                    warn!(
+                        "Couldn't restore {} of element {}",
+                        component.reflect_type_path(),
+                        entity
+                    );
+                    continue;
+                };
+
+                reflect_component.apply_or_insert_mapped(
+                    &mut element,
+                    component.as_partial_reflect(),
+                    &type_registry,
+                    &mut self.respawned,
+                    RelationshipHookMode::Run,
+                );
+            }
+        }
+
+        world.flush();
+
+        if respawned {
+            remap_references(world, &type_registry, &mut self.respawned);
+        }
+    }
+}
+
+/// Points references to respawned elements, like the points of a patrol, at their new entities.
+fn remap_references(
+    world: &mut World,
+    type_registry: &TypeRegistry,
+    respawned: &mut EntityHashMap<Entity>,
+) {
+    let element_entities: Vec<Entity> = world
+        .query_filtered::<Entity, With<LevelElement>>()
+        .iter(world)
+        .collect();
+
+    for element_entity in element_entities {
+        let reflect_components: Vec<&ReflectComponent> = world
+            .entity(element_entity)
+            .archetype()
+            .components()
+            .filter_map(|component_id| world.components().get_info(component_id))
+            .filter(|info| info.mutable())
+            .filter_map(|info| info.type_id())
+            .filter(|&type_id| {
+                type_id != TypeId::of::<ElementGroup>()
+                    && type_registry
+                        .get_type_data::<ReflectMapEntities>(type_id)
+                        .is_some()
+            })
+            .filter_map(|type_id| type_registry.get_type_data::<ReflectComponent>(type_id))
+            .collect();
+
+        let mut element = world.entity_mut(element_entity);
+
+        for reflect_component in reflect_components {
+            if let Some(mut component) = reflect_component.reflect_mut(&mut element) {
+                reflect_component.map_entities(&mut *component, respawned);
+            }
+        }
+    }
+}
+
+/// Reverts the last edit.
+pub fn undo(world: &mut World) {
+    world.resource_scope(|world: &mut World, mut history: Mut<EditorHistory>| {
+        let Some(edit) = history.undo_stack.pop() else {
+            return;
+        };
+
+        history.restore(world, &edit.before);
+
+        debug!("Undid \"{}\"", edit.name);
+
+        history.redo_stack.push(edit);
+    });
+}
+
+/// Applies the last undone edit again.
+pub fn redo(world: &mut World) {
+    world.resource_scope(|world: &mut World, mut history: Mut<EditorHistory>| {
+        let Some(edit) = history.redo_stack.pop() else {
+            return;
+        };
+
+        history.restore(world, &edit.after);
+
+        debug!("Redid \"{}\"", edit.name);
+
+        history.undo_stack.push(edit);
+    });
+}
+
+/// Ctrl+Z undoes and Ctrl+Shift+Z redoes.
+fn undo_shortcuts(world: &mut World) {
+    let keys = world.resource::<ButtonInput<KeyCode>>();
+
+    if !keys.just_pressed(KeyCode::KeyZ)
+        || !keys.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight])
+    {
+        return;
+    }
+
+    let redo_pressed = keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+
+    // text fields have their own undo
+    if wants_keyboard_input(world) {
+        return;
+    }
+
+    if world.resource::<TransformGizmo>().is_dragging() {
+        return;
+    }
+
+    if redo_pressed {
+        redo(world);
+    } else {
+        undo(world);
+    }
+}
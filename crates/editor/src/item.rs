@@ -0,0 +1,156 @@
+//! Items are element groups saved to their own file that can be placed into levels.
+//!
+//! A placed item is an [ItemInstance] group containing copies of the item's elements.
+//! Re-syncing replaces those copies with the current contents of the item file.
+
+use std::path::Path;
+
+use bevy::prelude::*;
+use common::editor::{ElementGroup, ItemInstance, ParentGroup, SaveMeta, SaveType};
+
+use crate::{
+    file::{build_scene, read_scene_file, spawn_elements, write_scene_file},
+    history::EditorHistory,
+    level::LevelElement,
+};
+
+/// Finds every element in a group and it's nested groups.
+pub fn group_elements(world: &World, group_entity: Entity) -> Vec<Entity> {
+    let mut elements = Vec::new();
+
+    if let Some(group) = world.get::<ElementGroup>(group_entity) {
+        for element_entity in group.iter() {
+            elements.push(element_entity);
+            elements.extend(group_elements(world, element_entity));
+        }
+    }
+
+    elements
+}
+
+/// Saves a group and it's elements as an item file.
+pub fn save_item(world: &mut World, group_entity: Entity, path: &Path) -> Result {
+    if world.get::<ElementGroup>(group_entity).is_none() {
+        return Err(format!("{} is not a group", group_entity).into());
+    }
+
+    let elements = group_elements(world, group_entity);
+
+    let mut scene = build_scene(world, std::iter::once(group_entity).chain(elements));
+
+    // the group is the root of the item, it can't be in a group outside the item
+    for scene_entity in scene.entities.iter_mut() {
+        if scene_entity.entity == group_entity {
+            scene_entity.components.retain(|component| {
+                !component.represents::<ParentGroup>() && !component.represents::<ItemInstance>()
+            });
+        }
+    }
+
+    scene.resources = vec![Box::new(SaveMeta {
+        save_type: SaveType::Item,
+    }) as Box<dyn PartialReflect>];
+
+    write_scene_file(world, &scene, path)?;
+
+    info!("Saved item {:?}", path);
+
+    resync_items(world, Some(path))
+}
+
+/// Spawns the elements of an item into the elements of `instance_entity`,
+/// positioned relative to it's [Transform].
+fn instance_item(world: &mut World, instance_entity: Entity, path: &Path) -> Result {
+    let mut scene = read_scene_file(world, path)?;
+
+    // the item's save meta shouldn't replace the one of the file being edited
+    scene.resources.clear();
+
+    let entity_map = spawn_elements(world, &scene)?;
+
+    let instance_transform = world
+        .get::<Transform>(instance_entity)
+        .copied()
+        .unwrap_or_default();
+
+    for &element_entity in entity_map.values() {
+        let mut element = world.entity_mut(element_entity);
+
+        if let Some(mut transform) = element.get_mut::<Transform>() {
+            *transform = instance_transform.mul_transform(*transform);
+        }
+
+        // move the elements of the item's root group into the instance
+        if element.contains::<ParentGroup>() {
+            continue;
+        }
+
+        if element.contains::<ElementGroup>() {
+            let root_elements: Vec<Entity> = element
+                .get::<ElementGroup>()
+                .map(|group| group.iter().collect())
+                .unwrap_or_default();
+
+            for root_element in root_elements {
+                world
+                    .entity_mut(root_element)
+                    .insert(ParentGroup(instance_entity));
+            }
+
+            world.despawn(element_entity);
+        }
+    }
+
+    Ok(())
+}
+
+/// Places a new instance of an item as a top level group.
+pub fn place_item(world: &mut World, path: &Path) -> Result<Entity> {
+    let instance_entity = world
+        .spawn((
+            Name::new(path.display().to_string()),
+            LevelElement,
+            ItemInstance {
+                path: path.to_string_lossy().into(),
+            },
+            Transform::default(),
+        ))
+        .id();
+
+    if let Err(err) = instance_item(world, instance_entity, path) {
+        world.despawn(instance_entity);
+        return Err(err);
+    }
+
+    info!("Placed item {:?}", path);
+
+    Ok(instance_entity)
+}
+
+/// Replaces the elements of every instance of an item with the contents of it's file.
+///
+/// Every item is re-synced if `path` is `None`.
+///
+/// The history is cleared, since the replaced elements can't be restored by it.
+pub fn resync_items(world: &mut World, path: Option<&Path>) -> Result {
+    let instances: Vec<(Entity, String)> = world
+        .query::<(Entity, &ItemInstance)>()
+        .iter(world)
+        .filter(|(_, instance)| path.is_none_or(|path| Path::new(&instance.path) == path))
+        .map(|(instance_entity, instance)| (instance_entity, instance.path.clone()))
+        .collect();
+
+    for (instance_entity, instance_path) in instances {
+        for element_entity in group_elements(world, instance_entity) {
+            world.despawn(element_entity);
+        }
+
+        instance_item(world, instance_entity, Path::new(&instance_path))?;
+
+        debug!("Re-synced item instance {}", instance_entity);
+    }
+
+    world.resource_mut::<EditorHistory>().clear();
+
+    Ok(())
+}
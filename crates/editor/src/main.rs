@@ -1,7 +1,14 @@
 use bevy::prelude::*;
 
+pub mod clipboard;
+pub mod file;
+pub mod history;
+pub mod item;
 pub mod level;
+pub mod palette;
+pub mod playtest;
 pub mod ui;
+pub mod viewport;
 
 fn main() {
     let mut app = App::new();
@@ -9,7 +16,7 @@ fn main() {
     app.add_plugins(
         DefaultPlugins
             .set(AssetPlugin {
-                file_path: "../../assets".into(),
+                file_path: file::ASSETS_DIRECTORY.into(),
                 ..default()
             })
             .set(bevy::log::LogPlugin {
@@ -21,7 +28,16 @@ fn main() {
             }),
     );
 
+    common::level::build(&mut app);
+    common::editor::build(&mut app);
+
+    file::build(&mut app);
+    history::build(&mut app);
+    clipboard::build(&mut app);
+    palette::build(&mut app);
+    playtest::build(&mut app);
     ui::build(&mut app);
+    viewport::build(&mut app);
 
     app.run();
 }
@@ -0,0 +1,227 @@
+//! Creating new elements.
+//!
+//! Elements with a position are placed by clicking in the viewport after choosing them in the palette.
+
+use bevy::{
+    picking::mesh_picking::ray_cast::{MeshRayCast, MeshRayCastSettings},
+    prelude::*,
+    window::PrimaryWindow,
+};
+use common::{
+    elements::gltf_collider::GltfColliderPath,
+    level::{
+        AgentSpawner, AlarmPanel, Door, ExtractionZone, LightZone, LootSpawn, PatrolPoint,
+        PatrolTask, PlayerSpawnPoint, SecurityCamera,
+    },
+};
+
+use crate::{
+    history::begin_edit,
+    level::LevelElement,
+    ui::element_selection::{SelectedElement, select_elements},
+    viewport::{ViewportCamera, ViewportState, cursor_ray, gizmo::UpdateTransformGizmo},
+};
+
+pub fn build(app: &mut App) {
+    app.init_resource::<Palette>();
+
+    app.add_systems(Update, place_element.before(UpdateTransformGizmo));
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ElementKind {
+    GltfCollider,
+    PatrolPoint,
+    PatrolRoute,
+    AgentSpawner,
+    PlayerSpawn,
+    Loot,
+    Door,
+    ExtractionZone,
+    LightZone,
+    AlarmPanel,
+    SecurityCamera,
+}
+
+impl ElementKind {
+    pub const ALL: [ElementKind; 11] = [
+        ElementKind::GltfCollider,
+        ElementKind::PatrolPoint,
+        ElementKind::PatrolRoute,
+        ElementKind::AgentSpawner,
+        ElementKind::PlayerSpawn,
+        ElementKind::Loot,
+        ElementKind::Door,
+        ElementKind::ExtractionZone,
+        ElementKind::LightZone,
+        ElementKind::AlarmPanel,
+        ElementKind::SecurityCamera,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ElementKind::GltfCollider => "GLTF Collider",
+            ElementKind::PatrolPoint => "Patrol Point",
+            ElementKind::PatrolRoute => "Patrol Route",
+            ElementKind::AgentSpawner => "Agent Spawner",
+            ElementKind::PlayerSpawn => "Player Spawn",
+            ElementKind::Loot => "Loot",
+            ElementKind::Door => "Door",
+            ElementKind::ExtractionZone => "Extraction Zone",
+            ElementKind::LightZone => "Light Zone",
+            ElementKind::AlarmPanel => "Alarm Panel",
+            ElementKind::SecurityCamera => "Security Camera",
+        }
+    }
+
+    /// Whether the element has a position and is placed in the viewport.
+    ///
+    /// A patrol route is made of the selected patrol points instead.
+    pub fn is_placed(self) -> bool {
+        self != ElementKind::PatrolRoute
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct Palette {
+    /// The kind of element that will be placed where the viewport is clicked next.
+    pub placing: Option<ElementKind>,
+}
+
+/// Spawns a new element as an edit and selects it.
+///
+/// `transform` is only used by kinds that are placed.
+/// Patrol routes go through the selected patrol points,
+/// agent spawners can be assigned the selected patrol routes.
+pub fn create_element(world: &mut World, kind: ElementKind, transform: Transform) -> Entity {
+    let selected_points: Vec<Entity> = world
+        .query_filtered::<Entity, (With<SelectedElement>, With<PatrolPoint>)>()
+        .iter(world)
+        .collect();
+
+    let selected_routes: Vec<Entity> = world
+        .query_filtered::<Entity, (With<SelectedElement>, With<PatrolTask>)>()
+        .iter(world)
+        .collect();
+
+    let edit = begin_edit(world, format!("Create {}", kind.name()), []);
+
+    let mut element = world.spawn((Name::new(kind.name()), LevelElement));
+
+    if kind.is_placed() {
+        element.insert(transform);
+    }
+
+    match kind {
+        ElementKind::GltfCollider => {
+            element.insert(GltfColliderPath::default());
+        }
+        ElementKind::PatrolPoint => {
+            element.insert(PatrolPoint);
+        }
+        ElementKind::PatrolRoute => {
+            element.insert(PatrolTask {
+                points: selected_points,
+            });
+        }
+        ElementKind::AgentSpawner => {
+            element.insert(AgentSpawner {
+                tasks: selected_routes,
+            });
+        }
+        ElementKind::PlayerSpawn => {
+            element.insert(PlayerSpawnPoint);
+        }
+        ElementKind::Loot => {
+            element.insert(LootSpawn);
+        }
+        ElementKind::Door => {
+            element.insert(Door);
+        }
+        ElementKind::ExtractionZone => {
+            element.insert(ExtractionZone::default());
+        }
+        ElementKind::LightZone => {
+            element.insert(LightZone::default());
+        }
+        ElementKind::AlarmPanel => {
+            element.insert(AlarmPanel);
+        }
+        ElementKind::SecurityCamera => {
+            element.insert(SecurityCamera::default());
+        }
+    }
+
+    let element_entity = element.id();
+
+    edit.finish(world, [element_entity]);
+
+    select_elements(world, &[element_entity]);
+
+    element_entity
+}
+
+/// Places the element chosen in the palette on the surface under the cursor, or on the ground.
+///
+/// Holding shift keeps placing the same kind, escape or right click stops placing.
+fn place_element(
+    mut commands: Commands,
+    mut palette: ResMut<Palette>,
+    state: Res<ViewportState>,
+    mut mouse_buttons: ResMut<ButtonInput<MouseButton>>,
+    keys: Res<ButtonInput<KeyCode>>,
+    window_q: Query<&Window, With<PrimaryWindow>>,
+    camera_q: Query<(&Camera, &GlobalTransform), With<ViewportCamera>>,
+    mut ray_cast: MeshRayCast,
+    element_q: Query<(), With<LevelElement>>,
+) -> Result {
+    let Some(kind) = palette.placing else {
+        return Ok(());
+    };
+
+    if keys.just_pressed(KeyCode::Escape) || mouse_buttons.just_pressed(MouseButton::Right) {
+        palette.placing = None;
+        return Ok(());
+    }
+
+    if !mouse_buttons.just_pressed(MouseButton::Left) {
+        return Ok(());
+    }
+
+    let window = window_q.single()?;
+    let (camera, camera_transform) = camera_q.single()?;
+
+    let Some(ray) = cursor_ray(&state, window, camera, camera_transform) else {
+        return Ok(());
+    };
+
+    let surface = ray_cast
+        .cast_ray(
+            ray,
+            &MeshRayCastSettings::default().with_filter(&|entity| element_q.contains(entity)),
+        )
+        .first()
+        .map(|(_, hit)| hit.point);
+
+    let ground = || {
+        let distance = ray.intersect_plane(Vec3::ZERO, InfinitePlane3d::new(Vec3::Y))?;
+        Some(ray.get_point(distance))
+    };
+
+    let Some(position) = surface.or_else(ground) else {
+        return Ok(());
+    };
+
+    // the click places the element, it shouldn't also select or grab something
+    mouse_buttons.clear_just_pressed(MouseButton::Left);
+
+    if !keys.pressed(KeyCode::ShiftLeft) {
+        palette.placing = None;
+    }
+
+    commands.queue(move |world: &mut World| {
+        create_element(world, kind, Transform::from_translation(position));
+    });
+
+    Ok(())
+}
@@ -0,0 +1,237 @@
+//! Play-testing the level being edited.
+//!
+//! A server is run on it's own thread with a copy of the current elements, and a client is launched to join it.
+//! The server has it's own world, so the elements being edited are left exactly as they were.
+
+use std::{
+    net::{Ipv4Addr, SocketAddr, UdpSocket},
+    path::{Path, PathBuf},
+    process::{Child, Command},
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Sender},
+    },
+    thread::JoinHandle,
+    time::Duration,
+};
+
+use bevy::prelude::*;
+use common::networking::fingerprint::CertificateFingerprint;
+use server::{config::ServerConfig, networking::load_or_create_certificate};
+
+use crate::{
+    file::{assets_directory, build_scene, write_scene_file},
+    level::LevelElement,
+};
+
+/// How long to wait for the server to start before giving up.
+const SERVER_START_TIMEOUT: Duration = Duration::from_secs(10);
+/// Where the elements are saved for the server to load, relative to the levels directory.
+const PLAYTEST_SCENE: &str = "playtest.scn.ron";
+/// The client binary, expected next to the editor's.
+const CLIENT_BINARY: &str = "client";
+
+pub fn build(app: &mut App) {
+    app.init_resource::<Playtest>();
+
+    app.add_systems(Update, stop_finished_playtest);
+    app.add_systems(Last, stop_playtest_on_exit);
+}
+
+#[derive(Resource, Default)]
+pub struct Playtest {
+    running: Option<RunningPlaytest>,
+}
+
+struct RunningPlaytest {
+    /// Set to stop the server.
+    stop: Arc<AtomicBool>,
+    server_thread: JoinHandle<()>,
+    client: Option<Child>,
+}
+
+impl Playtest {
+    pub fn is_running(&self) -> bool {
+        self.running.is_some()
+    }
+}
+
+/// Inserted into the server's app to stop it from the editor.
+#[derive(Resource)]
+struct StopServer(Arc<AtomicBool>);
+
+/// Inserted into the server's app to tell the editor once it has started.
+#[derive(Resource)]
+struct ServerStarted(Sender<()>);
+
+fn signal_server_started(started: Res<ServerStarted>) {
+    // the editor stops waiting if it times out
+    let _ = started.0.send(());
+}
+
+fn stop_server(stop: Res<StopServer>, mut exit_w: EventWriter<AppExit>) {
+    if stop.0.load(Ordering::Relaxed) {
+        exit_w.write(AppExit::Success);
+    }
+}
+
+fn playtest_scene_path() -> PathBuf {
+    Path::new("levels").join(PLAYTEST_SCENE)
+}
+
+/// Starts a server with the current elements and launches a client to join it.
+pub fn start_playtest(world: &mut World) -> Result {
+    if world.resource::<Playtest>().is_running() {
+        return Err("A play-test is already running".into());
+    }
+
+    let element_entities: Vec<Entity> = world
+        .query_filtered::<Entity, With<LevelElement>>()
+        .iter(world)
+        .collect();
+
+    let scene = build_scene(world, element_entities.into_iter());
+    write_scene_file(world, &scene, &playtest_scene_path())?;
+
+    // a port that is free right now, so a busy port doesn't stop the play-test
+    let port = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0))?
+        .local_addr()?
+        .port();
+
+    let mut config = ServerConfig::new(port);
+    config.bind_address = Ipv4Addr::LOCALHOST;
+    config.level_scene = PLAYTEST_SCENE.into();
+
+    // created before the server starts so the client can be given the fingerprint
+    let (chain, _) = load_or_create_certificate(&config)?;
+    let fingerprint = CertificateFingerprint::of(&chain[0]);
+
+    let stop = Arc::new(AtomicBool::new(false));
+    let server_stop = stop.clone();
+    let (started_tx, started_rx) = mpsc::channel();
+
+    let server_thread = std::thread::Builder::new()
+        .name("playtest server".into())
+        .spawn(move || {
+            let mut app = App::new();
+
+            app.insert_resource(config);
+            app.insert_resource(StopServer(server_stop));
+            app.insert_resource(ServerStarted(started_tx));
+
+            server::build(&mut app);
+
+            // startup errors, like failing to bind the endpoint, panic the thread before this runs
+            app.add_systems(PostStartup, signal_server_started);
+            app.add_systems(Last, stop_server);
+
+            app.run();
+        })?;
+
+    if started_rx.recv_timeout(SERVER_START_TIMEOUT).is_err() {
+        stop.store(true, Ordering::Relaxed);
+
+        if server_thread.join().is_err() {
+            error!("The play-test server panicked");
+        }
+
+        remove_playtest_scene();
+
+        return Err("The play-test server failed to start".into());
+    }
+
+    info!("Started a play-test server on port {}", port);
+
+    let server_address = SocketAddr::from((Ipv4Addr::LOCALHOST, port));
+
+    let client = match launch_client(server_address, fingerprint) {
+        Ok(client) => Some(client),
+        Err(err) => {
+            warn!(
+                "Failed to launch a client, join {} yourself: {}",
+                server_address, err
+            );
+            None
+        }
+    };
+
+    world.resource_mut::<Playtest>().running = Some(RunningPlaytest {
+        stop,
+        server_thread,
+        client,
+    });
+
+    Ok(())
+}
+
+fn launch_client(server_address: SocketAddr, fingerprint: CertificateFingerprint) -> Result<Child> {
+    let client_path = std::env::current_exe()?.with_file_name(format!(
+        "{}{}",
+        CLIENT_BINARY,
+        std::env::consts::EXE_SUFFIX
+    ));
+
+    // the client inherits `CARGO_MANIFEST_DIR` when the editor is run with cargo,
+    // so it finds the assets the same way the editor does
+    let client = Command::new(client_path)
+        .arg(server_address.to_string())
+        .args(["--fingerprint", &fingerprint.to_string()])
+        .args(["--username", "Tester"])
+        .spawn()?;
+
+    Ok(client)
+}
+
+/// Stops the server and client.
+pub fn stop_playtest(world: &mut World) {
+    let Some(mut playtest) = world.resource_mut::<Playtest>().running.take() else {
+        return;
+    };
+
+    if let Some(mut client) = playtest.client.take() {
+        if let Err(err) = client.kill().and_then(|_| client.wait()) {
+            warn!("Failed to stop the play-test client: {}", err);
+        }
+    }
+
+    playtest.stop.store(true, Ordering::Relaxed);
+
+    if playtest.server_thread.join().is_err() {
+        error!("The play-test server panicked");
+    }
+
+    remove_playtest_scene();
+
+    info!("Stopped the play-test");
+}
+
+fn remove_playtest_scene() {
+    if let Err(err) = std::fs::remove_file(assets_directory().join(playtest_scene_path())) {
+        warn!("Failed to remove the play-test level: {}", err);
+    }
+}
+
+/// Stops the play-test once the client is closed or the server stops by itself.
+fn stop_finished_playtest(world: &mut World) {
+    let mut playtest = world.resource_mut::<Playtest>();
+
+    let Some(running) = playtest.running.as_mut() else {
+        return;
+    };
+
+    let client_exited = running
+        .client
+        .as_mut()
+        .is_some_and(|client| !matches!(client.try_wait(), Ok(None)));
+
+    if client_exited || running.server_thread.is_finished() {
+        stop_playtest(world);
+    }
+}
+
+fn stop_playtest_on_exit(world: &mut World) {
+    if !world.resource::<Events<AppExit>>().is_empty() {
+        stop_playtest(world);
+    }
+}
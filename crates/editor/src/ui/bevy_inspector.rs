@@ -2,12 +2,12 @@ use bevy::prelude::*;
 use bevy_egui::egui::Ui;
 use bevy_inspector_egui::bevy_inspector::ui_for_entity_with_children;
 
-use crate::ui::element_selection::LastSelectedElement;
+use crate::{history::begin_edit, ui::element_selection::LastSelectedElement};
 
-use super::Tab;
+use super::{Tab, add_openable_tab};
 
 pub fn build(app: &mut App) {
-    // add_openable_tab::<BevyInspector>(app, "Entity Inspector");
+    add_openable_tab::<BevyInspector>(app, "Entity Inspector");
 }
 
 pub struct BevyInspector {
@@ -28,8 +28,12 @@ impl Tab for BevyInspector {
     }
 
     fn ui(&mut self, world: &mut World, ui: &mut Ui) {
-        if let Ok(entity) = self.query_state.get_single(world) {
+        if let Ok(entity) = self.query_state.single(world) {
+            let edit = begin_edit(world, "Inspector Change", [entity]);
+
             ui_for_entity_with_children(world, entity, ui);
+
+            edit.finish_continuous(world);
         }
     }
 }
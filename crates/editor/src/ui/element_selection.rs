@@ -1,12 +1,12 @@
 use bevy::{ecs::system::SystemState, prelude::*};
 use common::editor::{ElementGroup, ParentGroup};
 
-use crate::level::LevelElement;
+use crate::{clipboard::delete_selected, history::begin_edit, level::LevelElement};
 
-use super::Tab;
+use super::{Tab, add_openable_tab};
 
 pub fn build(app: &mut App) {
-    // add_openable_tab::<ElementSelectionTab>(app, "Elements");
+    add_openable_tab::<ElementSelectionTab>(app, "Elements");
 
     // let inspector = GroupComponentInspector::from_world(&mut app.world);
     // add_component_inspector(app, inspector);
@@ -179,6 +179,8 @@ impl Tab for ElementSelectionTab {
     fn ui(&mut self, world: &mut World, ui: &mut bevy_egui::egui::Ui) {
         let (mut commands, element_q) = self.group_state.get_mut(world);
 
+        let mut grouped = None;
+
         if !element_q.is_empty() {
             if ui.button("Group Selected").clicked() {
                 // all selected entities get placed into one group
@@ -235,16 +237,32 @@ impl Tab for ElementSelectionTab {
                         .entity(group_entity)
                         .insert(ParentGroup(parent_group));
                 }
+
+                grouped = Some((group_entity, elements));
             }
         }
 
+        // the commands haven't been applied yet, so the edit can start here
+        let edit = grouped
+            .as_ref()
+            .map(|(_, elements)| begin_edit(world, "Group", elements.iter().copied()));
+
         self.group_state.apply(world);
 
+        if let (Some(edit), Some((group_entity, _))) = (edit, grouped) {
+            edit.finish(world, [group_entity]);
+        }
+
         let (mut commands, group_q) = self.ungroup_state.get_mut(world);
 
+        let mut ungrouped = Vec::new();
+
         if !group_q.is_empty() {
             if ui.button("Ungroup Selected").clicked() {
                 for (group_entity, group, parent_group) in group_q.iter() {
+                    ungrouped.push(group_entity);
+                    ungrouped.extend(group.iter());
+
                     for child in group.iter() {
                         commands.entity(child).remove::<ParentGroup>();
 
@@ -262,8 +280,24 @@ impl Tab for ElementSelectionTab {
             }
         }
 
+        let edit = (!ungrouped.is_empty()).then(|| begin_edit(world, "Ungroup", ungrouped));
+
         self.ungroup_state.apply(world);
 
+        if let Some(edit) = edit {
+            edit.finish(world, []);
+        }
+
+        let has_selection = world
+            .query_filtered::<(), (With<SelectedElement>, With<LevelElement>)>()
+            .iter(world)
+            .next()
+            .is_some();
+
+        if has_selection && ui.button("Delete Selected").clicked() {
+            delete_selected(world);
+        }
+
         let (mut commands, top_level_q, element_q, selected_q, last_selected_q, input) =
             self.selection_state.get(world);
 
@@ -284,6 +318,28 @@ impl Tab for ElementSelectionTab {
     }
 }
 
+/// Selects only `element_entities`, the last one becomes the [LastSelectedElement].
+pub fn select_elements(world: &mut World, element_entities: &[Entity]) {
+    let selected: Vec<Entity> = world
+        .query_filtered::<Entity, Or<(With<SelectedElement>, With<LastSelectedElement>)>>()
+        .iter(world)
+        .collect();
+
+    for entity in selected {
+        world
+            .entity_mut(entity)
+            .remove::<(SelectedElement, LastSelectedElement)>();
+    }
+
+    for &entity in element_entities {
+        world.entity_mut(entity).insert(SelectedElement);
+    }
+
+    if let Some(&entity) = element_entities.last() {
+        world.entity_mut(entity).insert(LastSelectedElement);
+    }
+}
+
 fn show_entity(
     element_entity: Entity,
     depth: usize,
@@ -0,0 +1,262 @@
+use std::path::PathBuf;
+
+use bevy::prelude::*;
+use bevy_egui::egui;
+use common::editor::{ElementGroup, SaveMeta, SaveType};
+
+use crate::{
+    clipboard::{Clipboard, copy_selected, delete_selected, duplicate_selected, paste},
+    file::{CurrentFile, new_file, open_file, save_file},
+    history::{EditorHistory, begin_edit, redo, undo},
+    item::{group_elements, place_item, resync_items, save_item},
+    playtest::{Playtest, start_playtest, stop_playtest},
+    ui::{element_selection::LastSelectedElement, show_window_menu},
+};
+
+pub fn build(app: &mut App) {
+    app.init_resource::<FilePrompt>();
+}
+
+/// A window asking for the path of a file to open or save.
+#[derive(Resource, Default)]
+struct FilePrompt {
+    action: Option<FileAction>,
+    path: String,
+}
+
+#[derive(Clone, Copy)]
+enum FileAction {
+    Open,
+    SaveAs,
+    /// Save a group as an item.
+    SaveItem(Entity),
+    PlaceItem,
+}
+
+impl FilePrompt {
+    fn open(&mut self, world: &World, action: FileAction) {
+        let directory = match action {
+            FileAction::Open | FileAction::SaveAs => {
+                world.resource::<SaveMeta>().save_type.directory()
+            }
+            FileAction::SaveItem(_) | FileAction::PlaceItem => SaveType::Item.directory(),
+        };
+
+        self.action = Some(action);
+        self.path = format!("{}/", directory);
+    }
+}
+
+/// Draws the menu bar at the top of the window.
+pub fn show_menu_bar(world: &mut World, context: &egui::Context) {
+    world.resource_scope(|world: &mut World, mut prompt: Mut<FilePrompt>| {
+        egui::TopBottomPanel::top("menu_bar").show(context, |ui| {
+            egui::menu::bar(ui, |ui| {
+                ui.menu_button("File", |ui| {
+                    if ui.button("New").clicked() {
+                        new_file(world);
+                        ui.close_menu();
+                    }
+
+                    if ui.button("Open").clicked() {
+                        prompt.open(world, FileAction::Open);
+                        ui.close_menu();
+                    }
+
+                    if ui.button("Save").clicked() {
+                        match world.resource::<CurrentFile>().path.clone() {
+                            Some(path) => save(world, path),
+                            None => prompt.open(world, FileAction::SaveAs),
+                        }
+
+                        ui.close_menu();
+                    }
+
+                    if ui.button("Save As").clicked() {
+                        prompt.open(world, FileAction::SaveAs);
+                        ui.close_menu();
+                    }
+                });
+
+                ui.menu_button("Edit", |ui| {
+                    let history = world.resource::<EditorHistory>();
+                    let next_undo = history.next_undo().map(|name| format!("Undo {}", name));
+                    let next_redo = history.next_redo().map(|name| format!("Redo {}", name));
+
+                    let undo_button = ui.add_enabled(
+                        next_undo.is_some(),
+                        egui::Button::new(next_undo.unwrap_or_else(|| "Undo".into()))
+                            .shortcut_text("Ctrl+Z"),
+                    );
+
+                    if undo_button.clicked() {
+                        undo(world);
+                        ui.close_menu();
+                    }
+
+                    let redo_button = ui.add_enabled(
+                        next_redo.is_some(),
+                        egui::Button::new(next_redo.unwrap_or_else(|| "Redo".into()))
+                            .shortcut_text("Ctrl+Shift+Z"),
+                    );
+
+                    if redo_button.clicked() {
+                        redo(world);
+                        ui.close_menu();
+                    }
+
+                    ui.separator();
+
+                    if ui
+                        .add(egui::Button::new("Copy").shortcut_text("Ctrl+C"))
+                        .clicked()
+                    {
+                        copy_selected(world);
+                        ui.close_menu();
+                    }
+
+                    let can_paste = !world.resource::<Clipboard>().is_empty();
+
+                    if ui
+                        .add_enabled(
+                            can_paste,
+                            egui::Button::new("Paste").shortcut_text("Ctrl+V"),
+                        )
+                        .clicked()
+                    {
+                        paste(world);
+                        ui.close_menu();
+                    }
+
+                    if ui
+                        .add(egui::Button::new("Duplicate").shortcut_text("Ctrl+D"))
+                        .clicked()
+                    {
+                        duplicate_selected(world);
+                        ui.close_menu();
+                    }
+
+                    if ui
+                        .add(egui::Button::new("Delete").shortcut_text("Del"))
+                        .clicked()
+                    {
+                        delete_selected(world);
+                        ui.close_menu();
+                    }
+                });
+
+                ui.menu_button("Items", |ui| {
+                    let selected_group = world
+                        .query_filtered::<Entity, (With<LastSelectedElement>, With<ElementGroup>)>()
+                        .iter(world)
+                        .next();
+
+                    if let Some(group_entity) = selected_group {
+                        if ui.button("Save Selected Group as Item").clicked() {
+                            prompt.open(world, FileAction::SaveItem(group_entity));
+                            ui.close_menu();
+                        }
+                    }
+
+                    if ui.button("Place Item").clicked() {
+                        prompt.open(world, FileAction::PlaceItem);
+                        ui.close_menu();
+                    }
+
+                    if ui.button("Re-sync Items").clicked() {
+                        if let Err(err) = resync_items(world, None) {
+                            error!("Failed to re-sync items: {}", err);
+                        }
+
+                        ui.close_menu();
+                    }
+                });
+
+                ui.menu_button("Play", |ui| {
+                    if world.resource::<Playtest>().is_running() {
+                        if ui.button("Stop Play-test").clicked() {
+                            stop_playtest(world);
+                            ui.close_menu();
+                        }
+                    } else if ui.button("Play-test Level").clicked() {
+                        if let Err(err) = start_playtest(world) {
+                            error!("Failed to start a play-test: {}", err);
+                        }
+
+                        ui.close_menu();
+                    }
+                });
+
+                ui.menu_button("Window", |ui| show_window_menu(world, ui));
+            });
+        });
+
+        let Some(action) = prompt.action else {
+            return;
+        };
+
+        let title = match action {
+            FileAction::Open => "Open",
+            FileAction::SaveAs => "Save As",
+            FileAction::SaveItem(_) => "Save Item",
+            FileAction::PlaceItem => "Place Item",
+        };
+
+        let mut confirmed = false;
+        let mut cancelled = false;
+
+        egui::Window::new(title)
+            .collapsible(false)
+            .resizable(false)
+            .show(context, |ui| {
+                ui.label("Path relative to the assets directory");
+                ui.text_edit_singleline(&mut prompt.path);
+
+                ui.horizontal(|ui| {
+                    confirmed = ui.button(title).clicked();
+                    cancelled = ui.button("Cancel").clicked();
+                });
+            });
+
+        if confirmed {
+            let path = PathBuf::from(prompt.path.trim());
+
+            match action {
+                FileAction::Open => {
+                    if let Err(err) = open_file(world, &path) {
+                        error!("Failed to open {:?}: {}", path, err);
+                    }
+                }
+                FileAction::SaveAs => save(world, path),
+                FileAction::SaveItem(group_entity) => {
+                    if let Err(err) = save_item(world, group_entity, &path) {
+                        error!("Failed to save item {:?}: {}", path, err);
+                    }
+                }
+                FileAction::PlaceItem => {
+                    let edit = begin_edit(world, "Place Item", []);
+
+                    match place_item(world, &path) {
+                        Ok(instance_entity) => {
+                            let mut new_entities = group_elements(world, instance_entity);
+                            new_entities.push(instance_entity);
+
+                            edit.finish(world, new_entities);
+                        }
+                        Err(err) => error!("Failed to place item {:?}: {}", path, err),
+                    }
+                }
+            }
+        }
+
+        if confirmed || cancelled {
+            prompt.action = None;
+        }
+    });
+}
+
+fn save(world: &mut World, path: PathBuf) {
+    if let Err(err) = save_file(world, &path) {
+        error!("Failed to save {:?}: {}", path, err);
+    }
+}
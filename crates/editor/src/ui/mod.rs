@@ -1,26 +1,49 @@
-use bevy::{platform::collections::HashMap, prelude::*};
-use bevy_egui::{EguiContext, EguiPostUpdateSet, egui::Ui};
-use egui_dock::{DockArea, DockState};
+use std::{cell::RefCell, path::PathBuf};
+
+use bevy::{
+    asset::io::file::FileAssetReader, platform::collections::HashMap, prelude::*, scene::ron,
+};
+use bevy_egui::{EguiContext, EguiPostUpdateSet, PrimaryEguiContext, egui::Ui};
+use egui_dock::{DockArea, DockState, NodeIndex};
+
+use crate::viewport::ViewportState;
 
 pub mod bevy_inspector;
 pub mod element_selection;
+pub mod file_menu;
+pub mod palette;
+pub mod viewport;
+
+/// Where the dock layout is saved on exit, relative to the editor crate when run with cargo.
+const LAYOUT_FILE: &str = "layout.ron";
 
 pub fn build(app: &mut App) {
     app.add_plugins(bevy_egui::EguiPlugin::default());
     app.add_plugins(bevy_inspector_egui::DefaultInspectorConfigPlugin);
 
-    app.init_resource::<UiDockState>();
+    app.add_systems(Startup, spawn_ui_camera);
 
     bevy_inspector::build(app);
     element_selection::build(app);
+    file_menu::build(app);
+    palette::build(app);
+    viewport::build(app);
+
+    // tabs are registered by the modules above
+    app.init_resource::<UiDockState>();
 
     app.add_systems(PostUpdate, show_ui.before(EguiPostUpdateSet::ProcessOutput));
+    app.add_systems(Last, save_layout);
 }
 
 #[derive(Resource)]
-struct UiDockState(DockState<BoxedTab>);
+struct UiDockState(DockState<DockTab>);
 
-type BoxedTab = Box<dyn Tab>;
+/// A tab in the dock and the name it was registered with.
+struct DockTab {
+    name: &'static str,
+    tab: Box<dyn Tab>,
+}
 
 pub trait Tab: Send + Sync {
     fn title(&self) -> &'static str;
@@ -36,13 +59,173 @@ pub trait Tab: Send + Sync {
     }
 }
 
+/// The tabs that can be opened from the window menu.
+#[derive(Resource, Default)]
+struct OpenableTabs(Vec<OpenableTab>);
+
+#[derive(Clone, Copy)]
+struct OpenableTab {
+    name: &'static str,
+    open: fn(&mut World) -> Box<dyn Tab>,
+}
+
+impl OpenableTab {
+    fn open(self, world: &mut World) -> DockTab {
+        DockTab {
+            name: self.name,
+            tab: (self.open)(world),
+        }
+    }
+}
+
+/// Registers a tab so it can be opened from the window menu and restored with the saved layout.
+///
+/// `name` identifies the tab in the saved layout.
+pub fn add_openable_tab<T: Tab + FromWorld + 'static>(app: &mut App, name: &'static str) {
+    app.world_mut()
+        .get_resource_or_init::<OpenableTabs>()
+        .0
+        .push(OpenableTab {
+            name,
+            open: |world| Box::new(T::from_world(world)),
+        });
+}
+
+fn openable_tab(world: &World, name: &str) -> Option<OpenableTab> {
+    world
+        .get_resource::<OpenableTabs>()?
+        .0
+        .iter()
+        .find(|openable_tab| openable_tab.name == name)
+        .copied()
+}
+
 impl FromWorld for UiDockState {
     fn from_world(world: &mut World) -> Self {
-        let mut dock_state: DockState<BoxedTab> = DockState::new(vec![Box::new(
-            element_selection::ElementSelectionTab::from_world(world),
-        )]);
+        match load_layout(world) {
+            Ok(Some(dock_state)) => return UiDockState(dock_state),
+            Ok(None) => (),
+            Err(err) => warn!("Failed to load the saved layout: {}", err),
+        }
 
-        UiDockState(dock_state)
+        UiDockState(default_layout(world))
+    }
+}
+
+fn default_layout(world: &mut World) -> DockState<DockTab> {
+    let mut open = |name: &str| {
+        openable_tab(world, name)
+            .map(|openable_tab| openable_tab.open(world))
+            .into_iter()
+            .collect::<Vec<_>>()
+    };
+
+    let viewport = open("Viewport");
+    let elements = open("Elements");
+    let palette = open("Palette");
+    let inspector = open("Entity Inspector");
+
+    let mut dock_state = DockState::new(viewport);
+
+    let [viewport_node, elements_node] =
+        dock_state
+            .main_surface_mut()
+            .split_left(NodeIndex::root(), 0.2, elements);
+
+    dock_state
+        .main_surface_mut()
+        .split_below(elements_node, 0.6, palette);
+
+    dock_state
+        .main_surface_mut()
+        .split_right(viewport_node, 0.75, inspector);
+
+    dock_state
+}
+
+/// Resolved against the asset server's base path so that it doesn't depend on the working directory.
+fn layout_path() -> PathBuf {
+    FileAssetReader::get_base_path().join(LAYOUT_FILE)
+}
+
+/// Restores the layout saved on exit, if there is one.
+fn load_layout(world: &mut World) -> Result<Option<DockState<DockTab>>> {
+    let serialized = match std::fs::read_to_string(layout_path()) {
+        Ok(serialized) => serialized,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(err.into()),
+    };
+
+    let layout: DockState<String> = ron::from_str(&serialized)?;
+
+    // tabs that are no longer registered are left out
+    let world = RefCell::new(world);
+    let dock_state = layout.filter_map_tabs(|name: &String| {
+        let openable_tab = openable_tab(&world.borrow(), name)?;
+
+        Some(openable_tab.open(&mut world.borrow_mut()))
+    });
+
+    if dock_state.iter_all_tabs().next().is_none() {
+        return Ok(None);
+    }
+
+    Ok(Some(dock_state))
+}
+
+fn save_layout(mut exit_events: EventReader<AppExit>, dock_state: Res<UiDockState>) -> Result {
+    if exit_events.is_empty() {
+        return Ok(());
+    }
+
+    exit_events.clear();
+
+    let layout = dock_state.0.map_tabs(|tab| tab.name.to_string());
+
+    let path = layout_path();
+
+    std::fs::write(
+        &path,
+        ron::ser::to_string_pretty(&layout, ron::ser::PrettyConfig::default())?,
+    )?;
+
+    info!("Saved the layout to {}", path.display());
+
+    Ok(())
+}
+
+/// Opens a registered tab, or focuses it if it's already open.
+fn open_tab(world: &mut World, openable_tab: OpenableTab) {
+    let open_location = world
+        .resource::<UiDockState>()
+        .0
+        .find_tab_from(|tab| tab.name == openable_tab.name);
+
+    if let Some(location) = open_location {
+        world
+            .resource_mut::<UiDockState>()
+            .0
+            .set_active_tab(location);
+        return;
+    }
+
+    let tab = openable_tab.open(world);
+
+    world
+        .resource_mut::<UiDockState>()
+        .0
+        .push_to_focused_leaf(tab);
+}
+
+/// Lists the registered tabs in the menu bar.
+pub fn show_window_menu(world: &mut World, ui: &mut Ui) {
+    let openable_tabs = world.resource::<OpenableTabs>().0.clone();
+
+    for openable_tab in openable_tabs {
+        if ui.button(openable_tab.name).clicked() {
+            open_tab(world, openable_tab);
+            ui.close_menu();
+        }
     }
 }
 
@@ -62,14 +245,14 @@ impl<'a> TabViewer<'a> {
 }
 
 impl<'a> egui_dock::TabViewer for TabViewer<'a> {
-    type Tab = BoxedTab;
+    type Tab = DockTab;
 
     fn ui(&mut self, ui: &mut Ui, window: &mut Self::Tab) {
-        window.ui(self.world, ui);
+        window.tab.ui(self.world, ui);
     }
 
     fn title(&mut self, window: &mut Self::Tab) -> egui_dock::egui::WidgetText {
-        let title = window.title();
+        let title = window.tab.title();
 
         let count = if let Some(count) = self.seen_names.get_mut(&title) {
             *count += 1;
@@ -83,18 +266,39 @@ impl<'a> egui_dock::TabViewer for TabViewer<'a> {
     }
 
     fn clear_background(&self, tab: &Self::Tab) -> bool {
-        tab.clear_background()
+        tab.tab.clear_background()
     }
 
     fn closeable(&mut self, tab: &mut Self::Tab) -> bool {
-        tab.closeable()
+        tab.tab.closeable()
     }
 }
 
+/// Whether a text field has focus, keyboard shortcuts shouldn't be handled while typing.
+pub fn wants_keyboard_input(world: &mut World) -> bool {
+    world
+        .query_filtered::<&mut EguiContext, With<PrimaryEguiContext>>()
+        .single_mut(world)
+        .is_ok_and(|mut context| context.get_mut().wants_keyboard_input())
+}
+
+/// The ui is drawn by it's own camera on top of the viewport camera.
+fn spawn_ui_camera(mut commands: Commands) {
+    commands.spawn((
+        PrimaryEguiContext,
+        Camera2d,
+        Camera {
+            order: 1,
+            clear_color: ClearColorConfig::None,
+            ..default()
+        },
+    ));
+}
+
 fn show_ui(
     world: &mut World,
 
-    mut context_q: Local<QueryState<&mut EguiContext, With<bevy::window::PrimaryWindow>>>,
+    mut context_q: Local<QueryState<&mut EguiContext, With<PrimaryEguiContext>>>,
 ) {
     let Ok(context) = context_q.single(world) else {
         return;
@@ -102,6 +306,11 @@ fn show_ui(
     let mut context = context.clone();
     let context = context.get_mut();
 
+    file_menu::show_menu_bar(world, context);
+
+    // set again by the viewport tab if it is visible
+    world.insert_resource(ViewportState::default());
+
     world.resource_scope(|world: &mut World, mut dock_state: Mut<UiDockState>| {
         DockArea::new(&mut dock_state.0)
             .style(egui_dock::Style::from_egui(context.style().as_ref()))
@@ -0,0 +1,57 @@
+use bevy::prelude::*;
+use bevy_egui::egui::Ui;
+
+use crate::palette::{ElementKind, Palette, create_element};
+
+use super::{Tab, add_openable_tab};
+
+pub fn build(app: &mut App) {
+    add_openable_tab::<PaletteTab>(app, "Palette");
+}
+
+/// Buttons for each kind of element that can be created.
+pub struct PaletteTab;
+
+impl FromWorld for PaletteTab {
+    fn from_world(_: &mut World) -> Self {
+        PaletteTab
+    }
+}
+
+impl Tab for PaletteTab {
+    fn title(&self) -> &'static str {
+        "Palette"
+    }
+
+    fn ui(&mut self, world: &mut World, ui: &mut Ui) {
+        let placing = world.resource::<Palette>().placing;
+
+        for kind in ElementKind::ALL {
+            let selected = placing == Some(kind);
+
+            if !ui.selectable_label(selected, kind.name()).clicked() {
+                continue;
+            }
+
+            if kind.is_placed() {
+                world.resource_mut::<Palette>().placing = (!selected).then_some(kind);
+            } else {
+                create_element(world, kind, Transform::default());
+            }
+        }
+
+        match placing {
+            Some(kind) => {
+                ui.separator();
+                ui.label(format!(
+                    "Click in the viewport to place a {}, hold shift to place more",
+                    kind.name()
+                ));
+            }
+            None => {
+                ui.separator();
+                ui.label("Patrol routes are made of the selected patrol points");
+            }
+        }
+    }
+}
@@ -0,0 +1,81 @@
+use bevy::prelude::*;
+use bevy_egui::egui::{self, Ui};
+
+use crate::viewport::{
+    ViewportState,
+    gizmo::{GizmoMode, TransformGizmo},
+};
+
+use super::{Tab, add_openable_tab};
+
+pub fn build(app: &mut App) {
+    add_openable_tab::<ViewportTab>(app, "Viewport");
+}
+
+/// Shows the viewport camera with a toolbar for the transform gizmo.
+pub struct ViewportTab;
+
+impl FromWorld for ViewportTab {
+    fn from_world(_: &mut World) -> Self {
+        ViewportTab
+    }
+}
+
+impl Tab for ViewportTab {
+    fn title(&self) -> &'static str {
+        "Viewport"
+    }
+
+    fn ui(&mut self, world: &mut World, ui: &mut Ui) {
+        let mut gizmo = world.resource_mut::<TransformGizmo>();
+
+        ui.horizontal(|ui| {
+            ui.selectable_value(&mut gizmo.mode, GizmoMode::Translate, "Translate");
+            ui.selectable_value(&mut gizmo.mode, GizmoMode::Rotate, "Rotate");
+            ui.selectable_value(&mut gizmo.mode, GizmoMode::Scale, "Scale");
+
+            ui.separator();
+
+            ui.checkbox(&mut gizmo.snapping, "Snap");
+
+            ui.add(
+                egui::DragValue::new(&mut gizmo.translate_snap)
+                    .speed(0.05)
+                    .range(0.01..=100.)
+                    .suffix(" m"),
+            );
+
+            let mut rotate_snap = gizmo.rotate_snap.to_degrees();
+            ui.add(
+                egui::DragValue::new(&mut rotate_snap)
+                    .speed(1.)
+                    .range(1.0..=180.)
+                    .suffix("°"),
+            );
+            gizmo.rotate_snap = rotate_snap.to_radians();
+
+            ui.add(
+                egui::DragValue::new(&mut gizmo.scale_snap)
+                    .speed(0.01)
+                    .range(0.01..=10.)
+                    .prefix("×"),
+            );
+        });
+
+        // the rest of the tab is left transparent for the camera to render to
+        let rect = ui.available_rect_before_wrap();
+        let response = ui.allocate_rect(rect, egui::Sense::click_and_drag());
+
+        let mut state = world.resource_mut::<ViewportState>();
+        state.rect = Some(Rect::new(rect.min.x, rect.min.y, rect.max.x, rect.max.y));
+        state.hovered = response.hovered() || response.dragged();
+    }
+
+    fn clear_background(&self) -> bool {
+        false
+    }
+
+    fn closeable(&self) -> bool {
+        false
+    }
+}
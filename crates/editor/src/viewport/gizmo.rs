@@ -0,0 +1,375 @@
+//! Handles for translating, rotating and scaling the selected elements.
+//!
+//! The handles are aligned to the world axes and placed at the center of the selection.
+
+use bevy::{prelude::*, window::PrimaryWindow};
+
+use crate::{
+    history::{PendingEdit, begin_edit},
+    level::LevelElement,
+    ui::element_selection::SelectedElement,
+    viewport::{ViewportCamera, ViewportState, cursor_ray},
+};
+
+/// The size of the handles relative to their distance from the camera.
+const GIZMO_SCALE: f32 = 0.15;
+/// How close the cursor has to be to a handle to grab it, relative to the size of the handles.
+const GRAB_TOLERANCE: f32 = 0.08;
+
+pub fn build(app: &mut App) {
+    app.init_resource::<TransformGizmo>();
+
+    app.add_systems(
+        Update,
+        (grab_gizmo, drag_gizmo, draw_gizmo)
+            .chain()
+            .in_set(UpdateTransformGizmo),
+    );
+}
+
+/// System set in [Update] for the transform gizmo.
+#[derive(SystemSet, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct UpdateTransformGizmo;
+
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub enum GizmoMode {
+    #[default]
+    Translate,
+    Rotate,
+    Scale,
+}
+
+#[derive(Resource)]
+pub struct TransformGizmo {
+    pub mode: GizmoMode,
+    /// Snaps changes to increments. Holding control does the opposite.
+    pub snapping: bool,
+    /// The increment in metres translations are snapped to.
+    pub translate_snap: f32,
+    /// The increment in radians rotations are snapped to.
+    pub rotate_snap: f32,
+    /// The increment scale factors are snapped to.
+    pub scale_snap: f32,
+    hovered_axis: Option<usize>,
+    drag: Option<GizmoDrag>,
+}
+
+impl Default for TransformGizmo {
+    fn default() -> Self {
+        TransformGizmo {
+            mode: GizmoMode::default(),
+            snapping: false,
+            translate_snap: 0.25,
+            rotate_snap: 15f32.to_radians(),
+            scale_snap: 0.1,
+            hovered_axis: None,
+            drag: None,
+        }
+    }
+}
+
+impl TransformGizmo {
+    /// Whether the cursor is over a handle or one is being dragged.
+    pub fn is_interacting(&self) -> bool {
+        self.hovered_axis.is_some() || self.drag.is_some()
+    }
+
+    pub fn is_dragging(&self) -> bool {
+        self.drag.is_some()
+    }
+}
+
+struct GizmoDrag {
+    axis: usize,
+    center: Vec3,
+    size: f32,
+    /// The position along the axis, or the point on the rotation plane, where the handle was grabbed.
+    start: Vec3,
+    start_transforms: Vec<(Entity, Transform)>,
+    /// Recorded by a command once the drag has started.
+    edit: Option<PendingEdit>,
+}
+
+const AXES: [Vec3; 3] = [Vec3::X, Vec3::Y, Vec3::Z];
+
+fn axis_color(axis: usize) -> Color {
+    match axis {
+        0 => Color::srgb(0.9, 0.2, 0.2),
+        1 => Color::srgb(0.2, 0.9, 0.2),
+        _ => Color::srgb(0.2, 0.4, 0.9),
+    }
+}
+
+/// The parameter of the point on the line `origin + axis * s` closest to the ray.
+fn closest_on_axis(ray: Ray3d, origin: Vec3, axis: Vec3) -> Option<f32> {
+    let offset = origin - ray.origin;
+    let alignment = axis.dot(*ray.direction);
+    let denominator = 1. - alignment * alignment;
+
+    // the ray is parallel to the axis
+    if denominator < 1e-4 {
+        return None;
+    }
+
+    Some((alignment * ray.direction.dot(offset) - axis.dot(offset)) / denominator)
+}
+
+/// The point where the ray crosses the plane through `origin` with the normal `axis`.
+fn on_plane(ray: Ray3d, origin: Vec3, axis: Vec3) -> Option<Vec3> {
+    let distance = ray.intersect_plane(origin, InfinitePlane3d::new(axis))?;
+
+    Some(ray.get_point(distance))
+}
+
+fn snap(value: f32, increment: f32, snapping: bool) -> f32 {
+    if snapping && increment > 0. {
+        (value / increment).round() * increment
+    } else {
+        value
+    }
+}
+
+/// The center of the selection and the size of the handles.
+fn gizmo_placement(
+    selected_q: &Query<(Entity, &GlobalTransform), (With<SelectedElement>, With<LevelElement>)>,
+    camera_transform: &GlobalTransform,
+) -> Option<(Vec3, f32)> {
+    let count = selected_q.iter().count();
+
+    if count == 0 {
+        return None;
+    }
+
+    let center = selected_q
+        .iter()
+        .map(|(_, transform)| transform.translation())
+        .sum::<Vec3>()
+        / count as f32;
+
+    let size = center.distance(camera_transform.translation()) * GIZMO_SCALE;
+
+    Some((center, size))
+}
+
+/// Finds the handle under the cursor and starts dragging it when clicked.
+fn grab_gizmo(
+    mut commands: Commands,
+    mut gizmo: ResMut<TransformGizmo>,
+    state: Res<ViewportState>,
+    mouse_buttons: Res<ButtonInput<MouseButton>>,
+    window_q: Query<&Window, With<PrimaryWindow>>,
+    camera_q: Query<(&Camera, &GlobalTransform), With<ViewportCamera>>,
+    selected_q: Query<(Entity, &GlobalTransform), (With<SelectedElement>, With<LevelElement>)>,
+    transform_q: Query<&Transform>,
+) -> Result {
+    if gizmo.drag.is_some() {
+        return Ok(());
+    }
+
+    gizmo.hovered_axis = None;
+
+    let window = window_q.single()?;
+    let (camera, camera_transform) = camera_q.single()?;
+
+    let Some((center, size)) = gizmo_placement(&selected_q, camera_transform) else {
+        return Ok(());
+    };
+
+    let Some(ray) = cursor_ray(&state, window, camera, camera_transform) else {
+        return Ok(());
+    };
+
+    let tolerance = size * GRAB_TOLERANCE;
+
+    // the distance from the cursor to each handle, and where it was grabbed
+    let handles = AXES
+        .iter()
+        .enumerate()
+        .filter_map(|(axis_index, &axis)| match gizmo.mode {
+            GizmoMode::Translate | GizmoMode::Scale => {
+                let along = closest_on_axis(ray, center, axis)?.clamp(0., size);
+                let point = center + axis * along;
+                let distance = ray.direction.dot(point - ray.origin).max(0.);
+
+                Some((axis_index, ray.get_point(distance).distance(point), point))
+            }
+            GizmoMode::Rotate => {
+                let point = on_plane(ray, center, axis)?;
+
+                Some((axis_index, (point.distance(center) - size).abs(), point))
+            }
+        });
+
+    let Some((axis, _, start)) = handles
+        .filter(|&(_, distance, _)| distance <= tolerance)
+        .min_by(|(_, a, _), (_, b, _)| a.total_cmp(b))
+    else {
+        return Ok(());
+    };
+
+    gizmo.hovered_axis = Some(axis);
+
+    if !mouse_buttons.just_pressed(MouseButton::Left) {
+        return Ok(());
+    }
+
+    let start_transforms: Vec<(Entity, Transform)> = selected_q
+        .iter()
+        .filter_map(|(entity, _)| Some((entity, *transform_q.get(entity).ok()?)))
+        .collect();
+
+    let element_entities: Vec<Entity> =
+        start_transforms.iter().map(|&(entity, _)| entity).collect();
+
+    commands.queue(move |world: &mut World| {
+        let edit = begin_edit(world, "Transform", element_entities);
+
+        if let Some(drag) = world.resource_mut::<TransformGizmo>().drag.as_mut() {
+            drag.edit = Some(edit);
+        }
+    });
+
+    gizmo.drag = Some(GizmoDrag {
+        axis,
+        center,
+        size,
+        start,
+        start_transforms,
+        edit: None,
+    });
+
+    Ok(())
+}
+
+/// Applies the dragged handle to every selected element relative to where they were when it was grabbed.
+fn drag_gizmo(
+    mut commands: Commands,
+    mut gizmo: ResMut<TransformGizmo>,
+    state: Res<ViewportState>,
+    mouse_buttons: Res<ButtonInput<MouseButton>>,
+    keys: Res<ButtonInput<KeyCode>>,
+    window_q: Query<&Window, With<PrimaryWindow>>,
+    camera_q: Query<(&Camera, &GlobalTransform), With<ViewportCamera>>,
+    mut transform_q: Query<&mut Transform, With<LevelElement>>,
+) -> Result {
+    if !mouse_buttons.pressed(MouseButton::Left) {
+        if let Some(edit) = gizmo.drag.take().and_then(|drag| drag.edit) {
+            commands.queue(move |world: &mut World| edit.finish(world, []));
+        }
+    }
+
+    let Some(drag) = &gizmo.drag else {
+        return Ok(());
+    };
+
+    let window = window_q.single()?;
+    let (camera, camera_transform) = camera_q.single()?;
+
+    let Some(ray) = cursor_ray(&state, window, camera, camera_transform) else {
+        return Ok(());
+    };
+
+    let snapping = gizmo.snapping != keys.pressed(KeyCode::ControlLeft);
+    let axis = AXES[drag.axis];
+
+    for &(entity, start_transform) in drag.start_transforms.iter() {
+        let Ok(mut transform) = transform_q.get_mut(entity) else {
+            continue;
+        };
+
+        let relative = start_transform.translation - drag.center;
+
+        match gizmo.mode {
+            GizmoMode::Translate => {
+                let Some(along) = closest_on_axis(ray, drag.center, axis) else {
+                    continue;
+                };
+
+                let start_along = axis.dot(drag.start - drag.center);
+                let delta = snap(along - start_along, gizmo.translate_snap, snapping);
+
+                transform.translation = start_transform.translation + axis * delta;
+            }
+            GizmoMode::Rotate => {
+                let Some(point) = on_plane(ray, drag.center, axis) else {
+                    continue;
+                };
+
+                let from = drag.start - drag.center;
+                let to = point - drag.center;
+                let angle = from.cross(to).dot(axis).atan2(from.dot(to));
+                let rotation =
+                    Quat::from_axis_angle(axis, snap(angle, gizmo.rotate_snap, snapping));
+
+                transform.translation = drag.center + rotation * relative;
+                transform.rotation = rotation * start_transform.rotation;
+            }
+            GizmoMode::Scale => {
+                let Some(along) = closest_on_axis(ray, drag.center, axis) else {
+                    continue;
+                };
+
+                let start_along = axis.dot(drag.start - drag.center).max(drag.size * 0.1);
+                let factor = snap(along / start_along, gizmo.scale_snap, snapping).max(0.01);
+
+                let mut scale = Vec3::ONE;
+                scale[drag.axis] = factor;
+
+                transform.translation = drag.center + relative * scale;
+                transform.scale = start_transform.scale * scale;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn draw_gizmo(
+    mut gizmos: Gizmos,
+    gizmo: Res<TransformGizmo>,
+    camera_q: Query<&GlobalTransform, With<ViewportCamera>>,
+    selected_q: Query<(Entity, &GlobalTransform), (With<SelectedElement>, With<LevelElement>)>,
+) -> Result {
+    let camera_transform = camera_q.single()?;
+
+    let Some((center, size)) = gizmo_placement(&selected_q, camera_transform) else {
+        return Ok(());
+    };
+
+    let active_axis = gizmo
+        .drag
+        .as_ref()
+        .map(|drag| drag.axis)
+        .or(gizmo.hovered_axis);
+
+    for (axis_index, &axis) in AXES.iter().enumerate() {
+        let color = if active_axis == Some(axis_index) {
+            Color::srgb(1., 0.9, 0.2)
+        } else {
+            axis_color(axis_index)
+        };
+
+        match gizmo.mode {
+            GizmoMode::Translate => {
+                gizmos.arrow(center, center + axis * size, color);
+            }
+            GizmoMode::Rotate => {
+                gizmos.circle(
+                    Isometry3d::new(center, Quat::from_rotation_arc(Vec3::Z, axis)),
+                    size,
+                    color,
+                );
+            }
+            GizmoMode::Scale => {
+                gizmos.line(center, center + axis * size, color);
+                gizmos.cuboid(
+                    Transform::from_translation(center + axis * size)
+                        .with_scale(Vec3::splat(size * 0.1)),
+                    color,
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,272 @@
+//! The 3D view of the level being edited.
+
+use bevy::{
+    input::mouse::MouseMotion,
+    picking::mesh_picking::ray_cast::{MeshRayCast, MeshRayCastSettings},
+    prelude::*,
+    render::camera::Viewport,
+    window::PrimaryWindow,
+};
+
+use crate::{
+    level::LevelElement,
+    ui::element_selection::{LastSelectedElement, SelectedElement},
+};
+
+pub mod gizmo;
+pub mod visuals;
+
+const FLY_SPEED: f32 = 8.;
+/// How much faster the camera flies while shift is held.
+const FLY_BOOST: f32 = 4.;
+const LOOK_SENSITIVITY: f32 = 0.003;
+/// How close the cursor has to be to an element without a mesh to pick it.
+const PICK_RADIUS: f32 = 0.3;
+
+pub fn build(app: &mut App) {
+    gizmo::build(app);
+    visuals::build(app);
+
+    app.init_resource::<ViewportState>();
+
+    app.add_systems(Startup, spawn_viewport_camera);
+    app.add_systems(
+        Update,
+        (
+            update_camera_viewport,
+            fly_camera,
+            pick_elements.after(gizmo::UpdateTransformGizmo),
+        ),
+    );
+}
+
+/// The area of the window the viewport tab is shown in.
+#[derive(Resource, Default)]
+pub struct ViewportState {
+    /// The logical rect of the viewport, if the tab is visible.
+    pub rect: Option<Rect>,
+    /// If the cursor is over the viewport and not covered by other ui.
+    pub hovered: bool,
+}
+
+#[derive(Component)]
+pub struct ViewportCamera;
+
+fn spawn_viewport_camera(mut commands: Commands) {
+    commands.spawn((
+        ViewportCamera,
+        Camera3d::default(),
+        Camera {
+            is_active: false,
+            ..default()
+        },
+        Transform::from_xyz(0., 10., 15.).looking_at(Vec3::ZERO, Vec3::Y),
+    ));
+
+    commands.spawn((
+        DirectionalLight {
+            shadows_enabled: true,
+            ..default()
+        },
+        Transform::from_xyz(4., 10., 6.).looking_at(Vec3::ZERO, Vec3::Y),
+    ));
+}
+
+/// Renders the camera to the area of the viewport tab.
+fn update_camera_viewport(
+    state: Res<ViewportState>,
+    window_q: Query<&Window, With<PrimaryWindow>>,
+    mut camera_q: Query<&mut Camera, With<ViewportCamera>>,
+) -> Result {
+    let window = window_q.single()?;
+    let mut camera = camera_q.single_mut()?;
+
+    let Some(rect) = state.rect else {
+        camera.is_active = false;
+        return Ok(());
+    };
+
+    let scale_factor = window.scale_factor();
+    let physical_position = (rect.min * scale_factor).as_uvec2();
+    let physical_size = (rect.size() * scale_factor)
+        .as_uvec2()
+        .min(window.physical_size().saturating_sub(physical_position))
+        .max(UVec2::ONE);
+
+    camera.is_active = true;
+    camera.viewport = Some(Viewport {
+        physical_position,
+        physical_size,
+        ..default()
+    });
+
+    Ok(())
+}
+
+/// Flies the camera while the right mouse button is held.
+fn fly_camera(
+    state: Res<ViewportState>,
+    mouse_buttons: Res<ButtonInput<MouseButton>>,
+    keys: Res<ButtonInput<KeyCode>>,
+    mut mouse_motion: EventReader<MouseMotion>,
+    mut camera_q: Query<&mut Transform, With<ViewportCamera>>,
+    time: Res<Time>,
+    mut flying: Local<bool>,
+) -> Result {
+    let look_delta: Vec2 = mouse_motion.read().map(|motion| motion.delta).sum();
+
+    if mouse_buttons.just_pressed(MouseButton::Right) && state.hovered {
+        *flying = true;
+    }
+
+    if !mouse_buttons.pressed(MouseButton::Right) {
+        *flying = false;
+    }
+
+    if !*flying {
+        return Ok(());
+    }
+
+    let mut transform = camera_q.single_mut()?;
+
+    let (mut yaw, mut pitch, _) = transform.rotation.to_euler(EulerRot::YXZ);
+    yaw -= look_delta.x * LOOK_SENSITIVITY;
+    pitch = (pitch - look_delta.y * LOOK_SENSITIVITY)
+        .clamp(-std::f32::consts::FRAC_PI_2, std::f32::consts::FRAC_PI_2);
+    transform.rotation = Quat::from_euler(EulerRot::YXZ, yaw, pitch, 0.);
+
+    let mut direction = Vec3::ZERO;
+
+    for (key, key_direction) in [
+        (KeyCode::KeyW, transform.forward()),
+        (KeyCode::KeyS, transform.back()),
+        (KeyCode::KeyA, transform.left()),
+        (KeyCode::KeyD, transform.right()),
+        (KeyCode::KeyE, Dir3::Y),
+        (KeyCode::KeyQ, Dir3::NEG_Y),
+    ] {
+        if keys.pressed(key) {
+            direction += *key_direction;
+        }
+    }
+
+    let speed = if keys.pressed(KeyCode::ShiftLeft) {
+        FLY_SPEED * FLY_BOOST
+    } else {
+        FLY_SPEED
+    };
+
+    transform.translation += direction.normalize_or_zero() * speed * time.delta_secs();
+
+    Ok(())
+}
+
+/// The ray from the viewport camera through the cursor, if the cursor is over the viewport.
+pub fn cursor_ray(
+    state: &ViewportState,
+    window: &Window,
+    camera: &Camera,
+    camera_transform: &GlobalTransform,
+) -> Option<Ray3d> {
+    if !state.hovered {
+        return None;
+    }
+
+    let cursor_position = window.cursor_position()?;
+
+    camera
+        .viewport_to_world(camera_transform, cursor_position)
+        .ok()
+}
+
+/// Selects the element under the cursor when clicking in the viewport.
+///
+/// Elements with a mesh are picked by the mesh, other elements by their position.
+fn pick_elements(
+    mut commands: Commands,
+    state: Res<ViewportState>,
+    gizmo: Res<gizmo::TransformGizmo>,
+    mouse_buttons: Res<ButtonInput<MouseButton>>,
+    keys: Res<ButtonInput<KeyCode>>,
+    window_q: Query<&Window, With<PrimaryWindow>>,
+    camera_q: Query<(&Camera, &GlobalTransform), With<ViewportCamera>>,
+    mut ray_cast: MeshRayCast,
+    element_q: Query<
+        (Entity, &GlobalTransform, Has<Mesh3d>, Has<SelectedElement>),
+        With<LevelElement>,
+    >,
+    selected_q: Query<Entity, With<SelectedElement>>,
+    last_selected_q: Query<Entity, With<LastSelectedElement>>,
+) -> Result {
+    if !mouse_buttons.just_pressed(MouseButton::Left) || gizmo.is_interacting() {
+        return Ok(());
+    }
+
+    let window = window_q.single()?;
+    let (camera, camera_transform) = camera_q.single()?;
+
+    let Some(ray) = cursor_ray(&state, window, camera, camera_transform) else {
+        return Ok(());
+    };
+
+    let mesh_hit = ray_cast
+        .cast_ray(
+            ray,
+            &MeshRayCastSettings::default().with_filter(&|entity| element_q.contains(entity)),
+        )
+        .first()
+        .map(|(entity, hit)| (*entity, hit.distance));
+
+    let point_hit = element_q
+        .iter()
+        .filter(|(_, _, has_mesh, _)| !has_mesh)
+        .filter_map(|(entity, transform, _, _)| {
+            let offset = transform.translation() - ray.origin;
+            let distance = offset.dot(*ray.direction);
+
+            let closest = ray.get_point(distance);
+
+            (distance > 0. && closest.distance(transform.translation()) <= PICK_RADIUS)
+                .then_some((entity, distance))
+        })
+        .min_by(|(_, a), (_, b)| a.total_cmp(b));
+
+    let hit = match (mesh_hit, point_hit) {
+        (Some(mesh_hit), Some(point_hit)) => Some(if point_hit.1 < mesh_hit.1 {
+            point_hit
+        } else {
+            mesh_hit
+        }),
+        (mesh_hit, point_hit) => mesh_hit.or(point_hit),
+    };
+
+    if !keys.pressed(KeyCode::ControlLeft) {
+        for entity in selected_q.iter() {
+            commands
+                .entity(entity)
+                .remove::<(SelectedElement, LastSelectedElement)>();
+        }
+    }
+
+    let Some((element_entity, _)) = hit else {
+        return Ok(());
+    };
+
+    let (_, _, _, is_selected) = element_q.get(element_entity)?;
+
+    if is_selected && keys.pressed(KeyCode::ControlLeft) {
+        commands
+            .entity(element_entity)
+            .remove::<(SelectedElement, LastSelectedElement)>();
+    } else {
+        for entity in last_selected_q.iter() {
+            commands.entity(entity).remove::<LastSelectedElement>();
+        }
+
+        commands
+            .entity(element_entity)
+            .insert((SelectedElement, LastSelectedElement));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,212 @@
+//! How elements are shown in the viewport.
+//!
+//! Meshes added here are only for the editor and aren't saved.
+
+use bevy::prelude::*;
+use common::{
+    elements::gltf_collider::GltfColliderPath,
+    level::{
+        AgentSpawner, AlarmPanel, Door, ExtractionZone, LightZone, LootSpawn, PatrolPoint,
+        PatrolTask, PlayerSpawnPoint, SecurityCamera,
+    },
+};
+
+use crate::{level::LevelElement, ui::element_selection::SelectedElement};
+
+const MARKER_RADIUS: f32 = 0.2;
+
+pub fn build(app: &mut App) {
+    app.init_resource::<ElementMaterial>();
+
+    app.add_systems(
+        Update,
+        (
+            show_gltf_colliders,
+            draw_markers,
+            draw_patrol_tasks,
+            draw_extraction_zones,
+            draw_light_zones,
+            draw_security_cameras,
+        ),
+    );
+}
+
+#[derive(Resource)]
+struct ElementMaterial(Handle<StandardMaterial>);
+
+impl FromWorld for ElementMaterial {
+    fn from_world(world: &mut World) -> Self {
+        let mut materials = world.resource_mut::<Assets<StandardMaterial>>();
+
+        ElementMaterial(materials.add(Color::srgb(0.7, 0.7, 0.7)))
+    }
+}
+
+fn show_gltf_colliders(
+    mut commands: Commands,
+    collider_q: Query<(Entity, &GltfColliderPath), (With<LevelElement>, Changed<GltfColliderPath>)>,
+    material: Res<ElementMaterial>,
+    asset_server: Res<AssetServer>,
+) {
+    for (element_entity, GltfColliderPath(path)) in collider_q.iter() {
+        // colliders created from the palette don't have a path yet
+        if path.is_empty() {
+            commands
+                .entity(element_entity)
+                .remove::<(Mesh3d, MeshMaterial3d<StandardMaterial>)>();
+            continue;
+        }
+
+        commands.entity(element_entity).insert((
+            Mesh3d(asset_server.load(path)),
+            MeshMaterial3d(material.0.clone()),
+        ));
+    }
+}
+
+/// Draws a marker at elements that don't have a mesh.
+fn draw_markers(
+    mut gizmos: Gizmos,
+    marker_q: Query<
+        (
+            &GlobalTransform,
+            Has<SelectedElement>,
+            Has<PatrolPoint>,
+            Has<AgentSpawner>,
+            Has<PlayerSpawnPoint>,
+            Has<LootSpawn>,
+            Has<Door>,
+            Has<AlarmPanel>,
+            Has<SecurityCamera>,
+        ),
+        (With<LevelElement>, Without<Mesh3d>),
+    >,
+) {
+    for (
+        transform,
+        selected,
+        patrol_point,
+        agent_spawner,
+        player_spawn,
+        loot,
+        door,
+        alarm_panel,
+        security_camera,
+    ) in marker_q.iter()
+    {
+        let color = if selected {
+            Color::srgb(1., 0.6, 0.1)
+        } else if agent_spawner {
+            Color::srgb(0.9, 0.2, 0.2)
+        } else if player_spawn {
+            Color::srgb(0.2, 0.5, 0.9)
+        } else if loot {
+            Color::srgb(0.3, 0.9, 0.4)
+        } else if door {
+            Color::srgb(0.6, 0.4, 0.2)
+        } else if alarm_panel {
+            Color::srgb(0.8, 0.1, 0.6)
+        } else if security_camera {
+            Color::srgb(0.5, 0.2, 0.9)
+        } else if patrol_point {
+            Color::srgb(0.9, 0.8, 0.2)
+        } else {
+            Color::WHITE
+        };
+
+        gizmos.sphere(
+            Isometry3d::from_translation(transform.translation()),
+            MARKER_RADIUS,
+            color,
+        );
+    }
+}
+
+/// Draws the route of each patrol.
+fn draw_patrol_tasks(
+    mut gizmos: Gizmos,
+    task_q: Query<&PatrolTask, With<LevelElement>>,
+    point_q: Query<&GlobalTransform, With<PatrolPoint>>,
+) {
+    for task in task_q.iter() {
+        let points: Vec<Vec3> = task
+            .points
+            .iter()
+            .filter_map(|&point_entity| Some(point_q.get(point_entity).ok()?.translation()))
+            .collect();
+
+        if points.len() < 2 {
+            continue;
+        }
+
+        gizmos.linestrip(
+            points.iter().chain(points.first()).copied(),
+            Color::srgb(0.9, 0.8, 0.2),
+        );
+    }
+}
+
+/// Draws the bounds of each extraction zone.
+fn draw_extraction_zones(
+    mut gizmos: Gizmos,
+    zone_q: Query<(&GlobalTransform, &ExtractionZone, Has<SelectedElement>), With<LevelElement>>,
+) {
+    for (transform, zone, selected) in zone_q.iter() {
+        let color = if selected {
+            Color::srgb(1., 0.6, 0.1)
+        } else {
+            Color::srgb(0.2, 0.9, 0.9)
+        };
+
+        gizmos.cuboid(
+            transform.mul_transform(Transform::from_scale(zone.half_extents * 2.)),
+            color,
+        );
+    }
+}
+
+/// Draws the bounds of each light zone, brighter for brighter zones.
+fn draw_light_zones(
+    mut gizmos: Gizmos,
+    zone_q: Query<(&GlobalTransform, &LightZone, Has<SelectedElement>), With<LevelElement>>,
+) {
+    for (transform, zone, selected) in zone_q.iter() {
+        let color = if selected {
+            Color::srgb(1., 0.6, 0.1)
+        } else {
+            let brightness = 0.3 + 0.7 * zone.light_level;
+            Color::srgb(brightness, brightness, brightness * 0.5)
+        };
+
+        gizmos.cuboid(
+            transform.mul_transform(Transform::from_scale(zone.half_extents * 2.)),
+            color,
+        );
+    }
+}
+
+/// Draws the edges of what each security camera can see.
+fn draw_security_cameras(
+    mut gizmos: Gizmos,
+    camera_q: Query<(&GlobalTransform, &SecurityCamera, Has<SelectedElement>), With<LevelElement>>,
+) {
+    for (transform, camera, selected) in camera_q.iter() {
+        let color = if selected {
+            Color::srgb(1., 0.6, 0.1)
+        } else {
+            Color::srgb(0.5, 0.2, 0.9)
+        };
+
+        let origin = transform.translation();
+
+        for axis in [Vec3::X, Vec3::NEG_X, Vec3::Y, Vec3::NEG_Y] {
+            let edge = Quat::from_axis_angle(axis.cross(Vec3::NEG_Z), camera.fov) * Vec3::NEG_Z;
+
+            gizmos.line(
+                origin,
+                transform.transform_point(edge * camera.range),
+                color,
+            );
+        }
+    }
+}
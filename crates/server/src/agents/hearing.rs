@@ -0,0 +1,172 @@
+//! Agents hearing [NoiseEvent]s.
+//!
+//! Noises travel in a straight line, and every wall they go through muffles them.
+//! A noise on the other side of a wall can still be heard the long way around along the nav mesh
+//! if that is loud enough.
+
+use avian3d::prelude::*;
+use bevy::prelude::*;
+use bevy_landmass::Archipelago3d;
+use common::{
+    GameLayer,
+    noise::{NoiseEvent, NoiseKind},
+};
+
+use crate::agents::navigation::{MainArchipelago, path_distance};
+
+/// How much of a noise's range is left for each wall it goes through.
+const WALL_ATTENUATION: f32 = 0.5;
+/// The most walls that muffle a noise, any more can't be heard through anyway.
+const MAX_WALLS: u32 = 4;
+/// How high above where a noise was made to check for walls from, so the floor doesn't count.
+const NOISE_HEIGHT: f32 = 0.5;
+
+pub fn build(app: &mut App) {
+    app.add_systems(Update, hear_noises.in_set(UpdateAgentHearing));
+}
+
+/// System set in [Update] where [AgentHearing] is updated.
+#[derive(SystemSet, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct UpdateAgentHearing;
+
+/// The noises an agent heard this frame.
+#[derive(Component, Default)]
+pub struct AgentHearing {
+    noises: Vec<HeardNoise>,
+}
+
+#[derive(Clone, Copy)]
+pub struct HeardNoise {
+    pub source: Entity,
+    pub position: Vec3,
+    pub kind: NoiseKind,
+    /// How loud the noise was to the agent, from 0 at the edge of where it could be heard to 1.
+    pub volume: f32,
+}
+
+impl AgentHearing {
+    pub fn noises(&self) -> impl Iterator<Item = &HeardNoise> + '_ {
+        self.noises.iter()
+    }
+}
+
+#[derive(Component)]
+#[require(AgentHearing)]
+pub struct AgentEars {
+    /// The translation of the agent's ears.
+    pub offset: Vec3,
+    /// Multiplies how far away the agent can hear noises.
+    pub sensitivity: f32,
+}
+
+fn hear_noises(
+    mut noise_r: EventReader<NoiseEvent>,
+    mut agent_q: Query<(Entity, &GlobalTransform, &AgentEars, &mut AgentHearing)>,
+    archipelago_q: Query<&Archipelago3d, With<MainArchipelago>>,
+    spatial_query: SpatialQuery,
+) -> Result {
+    for (_, _, _, mut hearing) in agent_q.iter_mut() {
+        hearing.noises.clear();
+    }
+
+    let noises: Vec<NoiseEvent> = noise_r.read().copied().collect();
+
+    if noises.is_empty() {
+        return Ok(());
+    }
+
+    let archipelago = archipelago_q.single()?;
+
+    for (agent_entity, agent_transform, ears, mut hearing) in agent_q.iter_mut() {
+        let ear_position = *agent_transform * ears.offset;
+
+        for noise in noises.iter() {
+            if noise.source == agent_entity {
+                continue;
+            }
+
+            let range = noise.loudness * ears.sensitivity;
+            let straight_distance = ear_position.distance(noise.position);
+
+            // neither walls nor the long way around make a noise carry further than a straight line
+            if straight_distance > range {
+                continue;
+            }
+
+            let noise_origin = noise.position + Vec3::Y * NOISE_HEIGHT;
+
+            let walls = Dir3::new_and_length(ear_position - noise_origin).map_or(
+                0,
+                |(direction, distance)| {
+                    spatial_query
+                        .ray_hits(
+                            noise_origin,
+                            direction,
+                            distance,
+                            MAX_WALLS,
+                            true,
+                            &SpatialQueryFilter::from_mask([GameLayer::World]),
+                        )
+                        .len()
+                },
+            );
+
+            let around_distance = if walls > 0 {
+                path_distance(archipelago, agent_transform.translation(), noise.position)
+            } else {
+                None
+            };
+
+            let distance = heard_distance(straight_distance, walls, around_distance);
+
+            if distance > range {
+                continue;
+            }
+
+            hearing.noises.push(HeardNoise {
+                source: noise.source,
+                position: noise.position,
+                kind: noise.kind,
+                volume: 1. - distance / range,
+            });
+        }
+    }
+
+    Ok(())
+}
+
+/// How far a noise effectively travels to be heard `straight_distance` away through `walls`.
+///
+/// Each wall muffles the noise as much as travelling further would,
+/// unless the `path_distance` around the walls is shorter.
+fn heard_distance(straight_distance: f32, walls: usize, path_distance: Option<f32>) -> f32 {
+    let muffled_distance = straight_distance / WALL_ATTENUATION.powi(walls as i32);
+
+    path_distance.map_or(muffled_distance, |path_distance| {
+        path_distance.min(muffled_distance)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use common::noise::NoiseKind;
+
+    use crate::agents::hearing::heard_distance;
+
+    #[test]
+    fn occluded_noise_is_heard_at_shorter_range() {
+        let range = NoiseKind::Door.loudness();
+
+        // heard over open ground but not through a wall
+        assert!(heard_distance(range * 0.8, 0, None) <= range);
+        assert!(heard_distance(range * 0.8, 1, None) > range);
+
+        // closer noises can be heard through a wall, but not through two
+        assert!(heard_distance(range * 0.4, 1, None) <= range);
+        assert!(heard_distance(range * 0.4, 2, None) > range);
+
+        // unless the way around the walls is short enough
+        assert!(heard_distance(range * 0.8, 1, Some(range * 0.9)) <= range);
+        assert!(heard_distance(range * 0.8, 1, Some(range * 2.)) > range);
+    }
+}
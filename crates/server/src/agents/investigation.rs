@@ -1,7 +1,43 @@
+use std::time::Duration;
+
 use bevy::{platform::collections::HashMap, prelude::*};
+use bevy_landmass::{AgentState, AgentTarget, AgentTarget3d};
+use common::agents::SuspicionLevel;
+
+use crate::agents::{
+    hearing::AgentHearing,
+    navigation::{random_point_near, reached},
+    sight::AgentSight,
+    suspicion::{AgentSuspicion, UpdateAgentSuspicion},
+    tasks::{AssignedTo, AvailableTasks, FinishTask, TaskOutcome, TaskPriority},
+};
+
+/// How long an agent searches around where they last saw a target.
+const SEARCH_DURATION: Duration = Duration::from_secs(10);
+/// How far from where a target was last seen an agent will search.
+const SEARCH_RADIUS: f32 = 4.0;
+
+pub fn build(app: &mut App) {
+    app.add_event::<InvestigationEscalated>();
 
-pub fn build(app: &mut App) {}
+    app.add_observer(start_investigating);
+    app.add_observer(stop_investigating);
+    app.add_observer(despawn_investigation_tasks);
 
+    app.add_systems(
+        Update,
+        (
+            spot_investigation_targets,
+            escalate_investigations,
+            reach_last_seen_positions,
+            search_last_seen_positions,
+        )
+            .chain()
+            .after(UpdateAgentSuspicion),
+    );
+}
+
+/// Something that agents will investigate once they are suspicious enough of it.
 #[derive(Component)]
 pub struct InvestigationTarget;
 
@@ -11,5 +47,344 @@ pub struct AgentInvestigationState {
     targets: HashMap<Entity, Entity>,
 }
 
+/// A task for one agent to investigate where they last saw an [InvestigationTarget].
+///
+/// Created with [TaskPriority::Investigate] so it pulls the agent off of lower priority tasks,
+/// and despawned once the agent has searched the area without finding the target.
 #[derive(Component)]
-pub struct InvestigationTask {}
+pub struct InvestigationTask {
+    pub target: Entity,
+    pub last_seen: Vec3,
+    phase: InvestigationPhase,
+    /// Whether [InvestigationEscalated] has been sent for this investigation.
+    escalated: bool,
+}
+
+enum InvestigationPhase {
+    /// Walking to where the target was last seen.
+    MovingTo,
+    /// Walking between random points around where the target was last seen.
+    Searching { until: Duration },
+    /// Done, the task is despawned once the agent is unassigned from it.
+    Finished,
+}
+
+/// Sent when an agent becomes alarmed by the target they are investigating.
+#[derive(Event)]
+pub struct InvestigationEscalated {
+    pub agent_entity: Entity,
+    pub target_entity: Entity,
+    pub position: Vec3,
+}
+
+fn start_investigating(
+    trigger: Trigger<OnInsert, AssignedTo>,
+    mut agent_q: Query<(&AssignedTo, &mut AgentTarget3d)>,
+    mut task_q: Query<&mut InvestigationTask>,
+) -> Result {
+    let agent_entity = trigger.target();
+    let (&AssignedTo(task_entity), mut agent_target) = agent_q.get_mut(agent_entity)?;
+
+    let Ok(mut task) = task_q.get_mut(task_entity) else {
+        return Ok(());
+    };
+
+    debug!(
+        "Agent {} started investigating {}",
+        agent_entity, task.target
+    );
+
+    task.phase = InvestigationPhase::MovingTo;
+    *agent_target = AgentTarget::Point(task.last_seen);
+
+    Ok(())
+}
+
+/// Stops an agent walking to where they were investigating when they are pulled off of or finish an investigation.
+///
+/// Finished investigations are despawned,
+/// others are resumed from where the target was last seen if the agent is assigned to them again.
+fn stop_investigating(
+    trigger: Trigger<OnReplace, AssignedTo>,
+    mut commands: Commands,
+    mut agent_q: Query<(&AssignedTo, &mut AgentTarget3d)>,
+    task_q: Query<&InvestigationTask>,
+) {
+    let agent_entity = trigger.target();
+
+    let Ok((&AssignedTo(task_entity), mut agent_target)) = agent_q.get_mut(agent_entity) else {
+        return;
+    };
+
+    let Ok(task) = task_q.get(task_entity) else {
+        return;
+    };
+
+    debug!("Agent {} stopped investigating", agent_entity);
+
+    *agent_target = AgentTarget::None;
+
+    if let InvestigationPhase::Finished = task.phase {
+        commands.entity(task_entity).despawn();
+    }
+}
+
+/// Despawns the investigation tasks of an agent when they are despawned.
+fn despawn_investigation_tasks(
+    trigger: Trigger<OnRemove, AgentInvestigationState>,
+    mut commands: Commands,
+    agent_q: Query<&AgentInvestigationState>,
+) -> Result {
+    let investigation_state = agent_q.get(trigger.target())?;
+
+    for &task_entity in investigation_state.targets.values() {
+        commands.entity(task_entity).try_despawn();
+    }
+
+    Ok(())
+}
+
+/// Creates an investigation task for every [InvestigationTarget] an agent sees or hears and is suspicious enough of,
+/// and updates where the target was last seen or heard.
+fn spot_investigation_targets(
+    mut commands: Commands,
+    mut agent_q: Query<(
+        Entity,
+        &AgentSight,
+        Option<&AgentHearing>,
+        &AgentSuspicion,
+        &mut AgentInvestigationState,
+        &mut AvailableTasks,
+        Option<&AssignedTo>,
+        &mut AgentTarget3d,
+    )>,
+    mut task_q: Query<&mut InvestigationTask>,
+    target_q: Query<&GlobalTransform, With<InvestigationTarget>>,
+) -> Result {
+    for (
+        agent_entity,
+        sight,
+        hearing,
+        suspicion,
+        mut investigation_state,
+        mut available_tasks,
+        assigned_to,
+        mut agent_target,
+    ) in &mut agent_q
+    {
+        let seen = sight.targets().filter_map(|target_entity| {
+            let target_transform = target_q.get(target_entity).ok()?;
+            Some((target_entity, target_transform.translation()))
+        });
+
+        // targets that are heard but not seen are investigated where the noise was
+        let heard = hearing
+            .into_iter()
+            .flat_map(AgentHearing::noises)
+            .filter(|noise| target_q.contains(noise.source) && !sight.can_see(noise.source))
+            .map(|noise| (noise.source, noise.position));
+
+        for (target_entity, position) in seen.chain(heard) {
+            if !investigation_state.targets.contains_key(&target_entity)
+                && suspicion.level(target_entity) < SuspicionLevel::Investigating
+            {
+                continue;
+            }
+
+            let Some(&task_entity) = investigation_state.targets.get(&target_entity) else {
+                debug!(
+                    "Agent {} is suspicious enough of {} to investigate",
+                    agent_entity, target_entity
+                );
+
+                let task_entity = commands
+                    .spawn((
+                        InvestigationTask {
+                            target: target_entity,
+                            last_seen: position,
+                            phase: InvestigationPhase::MovingTo,
+                            escalated: false,
+                        },
+                        TaskPriority::Investigate,
+                    ))
+                    .id();
+
+                investigation_state
+                    .targets
+                    .insert(target_entity, task_entity);
+                available_tasks.tasks.insert(task_entity);
+
+                continue;
+            };
+
+            let mut task = task_q.get_mut(task_entity)?;
+
+            task.last_seen = position;
+
+            // the target was just seen or heard, so go there rather than searching
+            if assigned_to.is_some_and(|&AssignedTo(assigned_task)| assigned_task == task_entity) {
+                task.phase = InvestigationPhase::MovingTo;
+                *agent_target = AgentTarget::Point(position);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn escalate_investigations(
+    agent_q: Query<(Entity, &AssignedTo, &AgentSuspicion)>,
+    mut task_q: Query<&mut InvestigationTask>,
+    mut escalated_w: EventWriter<InvestigationEscalated>,
+) {
+    for (agent_entity, &AssignedTo(task_entity), suspicion) in &agent_q {
+        let Ok(mut task) = task_q.get_mut(task_entity) else {
+            continue;
+        };
+
+        if task.escalated || suspicion.level(task.target) < SuspicionLevel::Alarmed {
+            continue;
+        }
+
+        info!(
+            "Agent {} was alarmed by {} while investigating",
+            agent_entity, task.target
+        );
+
+        task.escalated = true;
+
+        escalated_w.write(InvestigationEscalated {
+            agent_entity,
+            target_entity: task.target,
+            position: task.last_seen,
+        });
+    }
+}
+
+fn reach_last_seen_positions(
+    mut agent_q: Query<(
+        Entity,
+        &AssignedTo,
+        &AgentState,
+        &GlobalTransform,
+        &AgentSight,
+        &mut AgentTarget3d,
+    )>,
+    mut task_q: Query<&mut InvestigationTask>,
+    time: Res<Time>,
+) {
+    for (
+        agent_entity,
+        &AssignedTo(task_entity),
+        nav_state,
+        agent_transform,
+        sight,
+        mut agent_target,
+    ) in &mut agent_q
+    {
+        let Ok(mut task) = task_q.get_mut(task_entity) else {
+            continue;
+        };
+
+        let InvestigationPhase::MovingTo = task.phase else {
+            continue;
+        };
+
+        let reached = reached(agent_transform, task.last_seen);
+
+        match nav_state {
+            _ if reached => (),
+            AgentState::Idle | AgentState::Moving | AgentState::ReachedTarget => continue,
+            AgentState::AgentNotOnNavMesh => {
+                warn!("Agent {} is not on the nav mesh", agent_entity);
+                continue;
+            }
+            AgentState::TargetNotOnNavMesh | AgentState::NoPath => {
+                debug!(
+                    "Agent {} couldn't reach where they last saw {}, searching nearby",
+                    agent_entity, task.target
+                );
+            }
+        }
+
+        // the target is still here, keep watching them
+        if reached && sight.can_see(task.target) {
+            continue;
+        }
+
+        task.phase = InvestigationPhase::Searching {
+            until: time.elapsed() + SEARCH_DURATION,
+        };
+        *agent_target = AgentTarget::Point(random_point_near(task.last_seen, SEARCH_RADIUS));
+    }
+}
+
+/// Walks agents between points around where they last saw their target,
+/// and sends them back to their other tasks once they have searched for long enough.
+fn search_last_seen_positions(
+    mut finish_w: EventWriter<FinishTask>,
+    mut agent_q: Query<(
+        Entity,
+        &AssignedTo,
+        &AgentState,
+        &GlobalTransform,
+        &mut AgentTarget3d,
+        &mut AgentInvestigationState,
+        &mut AvailableTasks,
+    )>,
+    mut task_q: Query<&mut InvestigationTask>,
+    time: Res<Time>,
+) {
+    for (
+        agent_entity,
+        &AssignedTo(task_entity),
+        nav_state,
+        agent_transform,
+        mut agent_target,
+        mut investigation_state,
+        mut available_tasks,
+    ) in &mut agent_q
+    {
+        let Ok(mut task) = task_q.get_mut(task_entity) else {
+            continue;
+        };
+
+        let InvestigationPhase::Searching { until } = task.phase else {
+            continue;
+        };
+
+        if time.elapsed() >= until {
+            debug!(
+                "Agent {} finished investigating {}",
+                agent_entity, task.target
+            );
+
+            finish_w.write(FinishTask {
+                agent_entity,
+                task_entity,
+                outcome: TaskOutcome::Completed,
+            });
+
+            // investigations are only done once
+            investigation_state.targets.remove(&task.target);
+            available_tasks.tasks.remove(&task_entity);
+            task.phase = InvestigationPhase::Finished;
+
+            continue;
+        }
+
+        let search_point_reached = match *agent_target {
+            AgentTarget::Point(point) => reached(agent_transform, point),
+            _ => true,
+        };
+
+        if search_point_reached
+            || matches!(
+                nav_state,
+                AgentState::TargetNotOnNavMesh | AgentState::NoPath
+            )
+        {
+            *agent_target = AgentTarget::Point(random_point_near(task.last_seen, SEARCH_RADIUS));
+        }
+    }
+}
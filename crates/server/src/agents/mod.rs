@@ -1,39 +1,39 @@
-use bevy::{platform::collections::HashSet, prelude::*};
-use common::{
-    agents::{Agent, InitializeAgent},
-    networking::StreamHeader,
-};
-use nevy::*;
+use bevy::prelude::*;
+use common::{agents::Agent, level::AgentSpawner};
 
 use crate::{
     agents::{
-        investigation::AgentInvestigationState,
-        navigation::NavMeshPath,
-        patrolling::{PatrolPoint, PatrolTask},
-        sight::AgentEyes,
-        tasks::{AvailableTasks, TaskPriority},
+        hearing::AgentEars, investigation::AgentInvestigationState, sight::AgentEyes,
+        suspicion::AgentSuspicion, tasks::AvailableTasks,
     },
+    level::{GameLevelLoaded, GameLevelUnloaded, UpdateGameLevel},
     physics_replication::ReplicateBody,
-    state::initialize_pairs::InitializePairs,
 };
 
+pub mod hearing;
 pub mod investigation;
 pub mod navigation;
 pub mod patrolling;
+pub mod response;
 pub mod sight;
+pub mod suspicion;
 pub mod tasks;
 
 pub fn build(app: &mut App) {
     navigation::build(app);
     sight::build(app);
+    hearing::build(app);
+    suspicion::build(app);
     tasks::build(app);
     patrolling::build(app);
     investigation::build(app);
+    response::build(app);
 
     app.add_systems(Update, init_agents);
-    app.add_systems(PostUpdate, initialize_agents.before(UpdateEndpoints));
-
-    app.add_systems(Startup, (debug_spawn_nav_mesh, debug_spawn_agents));
+    app.add_systems(
+        Update,
+        (despawn_agents, spawn_agents).after(UpdateGameLevel),
+    );
 }
 
 fn init_agents(mut commands: Commands, agent_q: Query<Entity, Added<Agent>>) {
@@ -42,63 +42,52 @@ fn init_agents(mut commands: Commands, agent_q: Query<Entity, Added<Agent>>) {
     }
 }
 
-fn initialize_agents(
-    pairs: InitializePairs<Agent>,
-    mut messages: LocalMessageSender,
-    message_id: Res<MessageId<InitializeAgent>>,
-) -> Result {
-    messages.flush()?;
-
-    for (client_entity, agent_entity) in pairs.iter() {
-        messages.write(
-            StreamHeader::Messages,
-            client_entity,
-            *message_id,
-            true,
-            &InitializeAgent {
-                entity: agent_entity.into(),
-            },
-        )?;
+/// Agents aren't part of the level scene, so they are despawned separately when it is unloaded.
+fn despawn_agents(
+    mut commands: Commands,
+    mut unloaded_r: EventReader<GameLevelUnloaded>,
+    agent_q: Query<Entity, With<Agent>>,
+) {
+    if unloaded_r.read().count() == 0 {
+        return;
     }
 
-    Ok(())
-}
-
-fn debug_spawn_nav_mesh(mut commands: Commands) {
-    commands.spawn(NavMeshPath("bank_nav_mesh.gltf#Mesh0/Primitive0".into()));
+    for agent_entity in agent_q.iter() {
+        commands.entity(agent_entity).despawn();
+    }
 }
 
-fn debug_spawn_agents(mut commands: Commands) {
-    // -2 0 0
-    // -1.5 0 -7.5
-    // 3.5 0 -5
-
-    let points = vec![
-        commands
-            .spawn((PatrolPoint::default(), Transform::from_xyz(-2., 0., 0.)))
-            .id(),
-        commands
-            .spawn((PatrolPoint::default(), Transform::from_xyz(-1.5, 0., -7.5)))
-            .id(),
-        commands
-            .spawn((PatrolPoint::default(), Transform::from_xyz(3.5, 0., -5.)))
-            .id(),
-    ];
+/// Spawns an agent at every [AgentSpawner] once a level has loaded.
+fn spawn_agents(
+    mut commands: Commands,
+    mut loaded_r: EventReader<GameLevelLoaded>,
+    spawner_q: Query<(Entity, &AgentSpawner, &GlobalTransform)>,
+) {
+    if loaded_r.read().count() == 0 {
+        return;
+    }
 
-    let task_entity = commands
-        .spawn((PatrolTask { points }, TaskPriority::Idle))
-        .id();
+    for (spawner_entity, spawner, spawner_transform) in spawner_q.iter() {
+        debug!("Spawning agent from spawner {}", spawner_entity);
 
-    commands.spawn((
-        Agent,
-        AvailableTasks {
-            tasks: HashSet::from_iter([task_entity].into_iter()),
-        },
-        AgentEyes {
-            offset: Vec3::Y * 1.8,
-            fov: 45f32.to_radians(),
-            range: f32::MAX,
-        },
-        AgentInvestigationState::default(),
-    ));
+        commands.spawn((
+            Agent,
+            Transform::from_translation(spawner_transform.translation()),
+            AvailableTasks {
+                tasks: spawner.tasks.iter().copied().collect(),
+                ..default()
+            },
+            AgentEyes {
+                offset: Vec3::Y * 1.8,
+                fov: 45f32.to_radians(),
+                range: f32::MAX,
+            },
+            AgentEars {
+                offset: Vec3::Y * 1.7,
+                sensitivity: 1.0,
+            },
+            AgentSuspicion::default(),
+            AgentInvestigationState::default(),
+        ));
+    }
 }
@@ -1,4 +1,4 @@
-use std::sync::Arc;
+use std::{f32::consts::TAU, sync::Arc};
 
 use avian3d::prelude::*;
 use bevy::prelude::*;
@@ -8,12 +8,17 @@ use bevy_landmass::{
     NavMeshHandle3d, Velocity3d, nav_mesh::bevy_mesh_to_landmass_nav_mesh,
 };
 
-use common::agents::Agent;
+use common::{agents::Agent, level::NavMeshPath};
+use rand::{Rng, rng};
+
+use crate::level::{GameLevelUnloaded, UpdateGameLevel};
 
 const AGENT_RADIUS: f32 = 0.25;
 const AGENT_DESIRED_SPEED: f32 = 2.0;
 const AGENT_MAX_SPEED: f32 = 3.0;
 const AGENT_MAX_ACCELERATION: f32 = 50.0;
+/// How close an agent needs to get to a position to have reached it.
+const REACH_DISTANCE: f32 = 1.0;
 
 pub fn build(app: &mut App) {
     app.add_plugins(Landmass3dPlugin::default());
@@ -28,19 +33,18 @@ pub fn build(app: &mut App) {
             (update_agent_velocities, integrate_agents).chain(),
         ),
     );
+    app.add_systems(Update, reset_archipelago.after(UpdateGameLevel));
 }
 
-#[derive(Component)]
-pub struct NavMeshPath(pub String);
-
 #[derive(Component)]
 struct ConvertNavMesh {
     mesh: Handle<Mesh>,
     nav_mesh: Handle<NavMesh3d>,
 }
 
+/// The archipelago that agents navigate the level with.
 #[derive(Component)]
-struct MainArchipelago;
+pub struct MainArchipelago;
 
 fn spawn_archipelago(mut commands: Commands) {
     commands.spawn((
@@ -49,6 +53,55 @@ fn spawn_archipelago(mut commands: Commands) {
     ));
 }
 
+/// The length of the shortest path between two points over the nav mesh,
+/// or `None` if either point isn't on it or there is no path.
+pub fn path_distance(archipelago: &Archipelago3d, from: Vec3, to: Vec3) -> Option<f32> {
+    let point_sample_distance = AgentOptions::from_agent_radius(AGENT_RADIUS).point_sample_distance;
+
+    let start = archipelago
+        .sample_point(from, &point_sample_distance)
+        .ok()?;
+    let end = archipelago.sample_point(to, &point_sample_distance).ok()?;
+
+    let path = archipelago.find_path(&start, &end, &default()).ok()?;
+
+    Some(
+        path.windows(2)
+            .map(|points| points[0].distance(points[1]))
+            .sum(),
+    )
+}
+
+/// Whether an agent is close enough to a position to have reached it, ignoring height.
+pub fn reached(agent_transform: &GlobalTransform, position: Vec3) -> bool {
+    (agent_transform.translation() - position).xz().length() < REACH_DISTANCE
+}
+
+/// A random point on the same height as `center` at most `radius` away from it.
+pub fn random_point_near(center: Vec3, radius: f32) -> Vec3 {
+    let mut rng = rng();
+    let offset = Vec2::from_angle(rng.random_range(0. ..TAU)) * rng.random_range(0. ..radius);
+
+    center + Vec3::new(offset.x, 0., offset.y)
+}
+
+/// Replaces the archipelago so that nothing from an unloaded level is left in it.
+fn reset_archipelago(
+    mut commands: Commands,
+    mut unloaded_r: EventReader<GameLevelUnloaded>,
+    archipelago_q: Query<Entity, With<MainArchipelago>>,
+) {
+    if unloaded_r.read().count() == 0 {
+        return;
+    }
+
+    for archipelago_entity in archipelago_q.iter() {
+        commands.entity(archipelago_entity).despawn();
+    }
+
+    spawn_archipelago(commands);
+}
+
 fn load_nav_meshes(
     mut commands: Commands,
     mesh_q: Query<(Entity, &NavMeshPath), Without<NavMeshHandle3d>>,
@@ -2,34 +2,36 @@ use std::time::Duration;
 
 use bevy::prelude::*;
 use bevy_landmass::{AgentState, AgentTarget, AgentTarget3d};
+use common::level::{PatrolPoint, PatrolTask};
 use rand::{Rng, rng};
 
-use crate::agents::tasks::{AssignedAgents, AssignedTo};
+use crate::{
+    agents::tasks::{AssignedTo, FinishTask, TaskOutcome, TaskPriority},
+    level::{GameLevelUnloaded, UpdateGameLevel},
+};
 
 const POINT_WAIT_DURATION: Duration = Duration::from_secs(3);
 
 pub fn build(app: &mut App) {
     app.add_observer(start_patroling);
+    app.add_observer(stop_patroling);
 
     app.add_systems(
         Update,
         (
+            init_patrol_tasks,
+            init_patrol_points,
             assign_patrol_points,
             reach_patrol_points,
             leave_patrol_points,
         ),
     );
+    app.add_systems(Update, reset_patrol_points.after(UpdateGameLevel));
 }
 
-#[derive(Component)]
-#[require(AssignedAgents)]
-pub struct PatrolTask {
-    pub points: Vec<Entity>,
-}
-
+/// The agent that is walking to or waiting at a [PatrolPoint].
 #[derive(Component, Default)]
-#[require(Transform)]
-pub struct PatrolPoint {
+pub struct PatrolPointAgent {
     assigned_agent: Option<Entity>,
 }
 
@@ -68,6 +70,39 @@ fn start_patroling(
     Ok(())
 }
 
+/// Frees the patrol point an agent was walking to or waiting at when they stop patrolling.
+fn stop_patroling(
+    trigger: Trigger<OnReplace, AssignedTo>,
+    mut commands: Commands,
+    mut agent_q: Query<(&AgentPatrolState, &mut AgentTarget3d)>,
+    mut point_q: Query<&mut PatrolPointAgent>,
+) {
+    let agent_entity = trigger.target();
+
+    let Ok((patrol_state, mut agent_target)) = agent_q.get_mut(agent_entity) else {
+        return;
+    };
+
+    debug!("Agent {} stopped patroling", agent_entity);
+
+    if let AgentPatrolState::MovingTo { point_entity }
+    | AgentPatrolState::Arrived { point_entity, .. } = *patrol_state
+    {
+        if let Ok(mut point) = point_q.get_mut(point_entity) {
+            if point.assigned_agent == Some(agent_entity) {
+                point.assigned_agent = None;
+            }
+        }
+    }
+
+    *agent_target = AgentTarget::None;
+
+    // the agent might be being despawned
+    commands
+        .entity(agent_entity)
+        .try_remove::<AgentPatrolState>();
+}
+
 // fn debug_set_agent_target(
 //     mut agent_q: Query<&mut AgentTarget3d>,
 //     character_q: Query<&Transform, With<Character>>,
@@ -81,7 +116,36 @@ fn start_patroling(
 //     }
 // }
 
+fn init_patrol_tasks(mut commands: Commands, task_q: Query<Entity, Added<PatrolTask>>) {
+    for task_entity in task_q.iter() {
+        commands.entity(task_entity).insert(TaskPriority::Idle);
+    }
+}
+
+fn init_patrol_points(mut commands: Commands, point_q: Query<Entity, Added<PatrolPoint>>) {
+    for point_entity in point_q.iter() {
+        commands
+            .entity(point_entity)
+            .insert(PatrolPointAgent::default());
+    }
+}
+
+/// Frees patrol points outside of an unloaded level from agents that were despawned with it.
+fn reset_patrol_points(
+    mut unloaded_r: EventReader<GameLevelUnloaded>,
+    mut point_q: Query<&mut PatrolPointAgent>,
+) {
+    if unloaded_r.read().count() == 0 {
+        return;
+    }
+
+    for mut point in point_q.iter_mut() {
+        point.assigned_agent = None;
+    }
+}
+
 fn assign_patrol_points(
+    mut finish_w: EventWriter<FinishTask>,
     mut agent_q: Query<(
         Entity,
         &AssignedTo,
@@ -89,7 +153,7 @@ fn assign_patrol_points(
         &mut AgentTarget3d,
     )>,
     task_q: Query<&PatrolTask>,
-    mut point_q: Query<&mut PatrolPoint>,
+    mut point_q: Query<&mut PatrolPointAgent>,
 ) -> Result {
     for (agent_entity, &AssignedTo(task_entity), mut patrol_state, mut agent_target) in &mut agent_q
     {
@@ -116,6 +180,12 @@ fn assign_patrol_points(
                 agent_entity, task_entity
             );
 
+            finish_w.write(FinishTask {
+                agent_entity,
+                task_entity,
+                outcome: TaskOutcome::Failed,
+            });
+
             continue;
         }
 
@@ -174,7 +244,7 @@ fn reach_patrol_points(
 
 fn leave_patrol_points(
     mut agent_q: Query<(&mut AgentPatrolState, &mut AgentTarget3d)>,
-    mut point_q: Query<&mut PatrolPoint>,
+    mut point_q: Query<&mut PatrolPointAgent>,
     time: Res<Time>,
 ) -> Result {
     for (mut agent_state, mut agent_target) in &mut agent_q {
@@ -197,3 +267,82 @@ fn leave_patrol_points(
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use bevy::prelude::*;
+    use bevy_landmass::{AgentTarget, AgentTarget3d};
+    use common::level::{PatrolPoint, PatrolTask};
+
+    use crate::{
+        agents::{
+            patrolling::{self, PatrolPointAgent},
+            tasks::{self, AssignedTo, AvailableTasks, TaskPriority},
+        },
+        level::GameLevelUnloaded,
+    };
+
+    #[test]
+    fn preempted_patrol_releases_point() {
+        let mut app = App::new();
+        app.init_resource::<Time>();
+        app.add_event::<GameLevelUnloaded>();
+        tasks::build(&mut app);
+        patrolling::build(&mut app);
+
+        let point_entity = app.world_mut().spawn(PatrolPoint).id();
+        let patrol_task = app
+            .world_mut()
+            .spawn(PatrolTask {
+                points: vec![point_entity],
+            })
+            .id();
+
+        // let the point and task initialize before an agent can be assigned to them
+        app.update();
+
+        let agent_entity = app
+            .world_mut()
+            .spawn((
+                AvailableTasks {
+                    tasks: [patrol_task].into_iter().collect(),
+                    ..default()
+                },
+                AgentTarget3d::None,
+            ))
+            .id();
+
+        app.update();
+        app.update();
+
+        let assigned_agent = |app: &App| {
+            app.world()
+                .get::<PatrolPointAgent>(point_entity)
+                .unwrap()
+                .assigned_agent
+        };
+
+        assert_eq!(assigned_agent(&app), Some(agent_entity));
+
+        let respond_task = app.world_mut().spawn(TaskPriority::Respond).id();
+        app.world_mut()
+            .get_mut::<AvailableTasks>(agent_entity)
+            .unwrap()
+            .tasks
+            .insert(respond_task);
+
+        app.update();
+
+        assert_eq!(
+            app.world()
+                .get::<AssignedTo>(agent_entity)
+                .map(|&AssignedTo(task_entity)| task_entity),
+            Some(respond_task)
+        );
+        assert_eq!(assigned_agent(&app), None);
+        assert!(matches!(
+            app.world().get::<AgentTarget3d>(agent_entity),
+            Some(AgentTarget::None)
+        ));
+    }
+}
@@ -0,0 +1,360 @@
+//! Guards responding to the [AlarmState].
+//!
+//! A guard alarmed by someone they are investigating radios it in, making the level suspicious,
+//! then runs to the nearest [AlarmPanel] to raise the alarm.
+//! While the level isn't calm every guard searches around where the crew was last reported.
+
+use bevy::prelude::*;
+use bevy_landmass::{AgentState, AgentTarget, AgentTarget3d};
+use common::{alarm::AlarmLevel, level::AlarmPanel};
+
+use crate::{
+    agents::{
+        investigation::InvestigationEscalated,
+        navigation::{random_point_near, reached},
+        tasks::{AssignedTo, AvailableTasks, FinishTask, TaskOutcome, TaskPriority},
+    },
+    alarm::{AlarmState, RaiseAlarm, UpdateAlarm},
+    level::{GameLevelUnloaded, UpdateGameLevel},
+};
+
+/// How far from where the crew was last reported guards search.
+const RESPONSE_SEARCH_RADIUS: f32 = 8.0;
+
+pub fn build(app: &mut App) {
+    app.add_observer(start_raising_alarm);
+    app.add_observer(stop_raising_alarm);
+    app.add_observer(stop_responding);
+
+    app.add_systems(
+        Update,
+        (
+            radio_escalations.before(UpdateAlarm),
+            reach_alarm_panels.before(UpdateAlarm),
+            (update_response_task, search_response_area)
+                .chain()
+                .after(UpdateAlarm),
+        ),
+    );
+    app.add_systems(Update, despawn_raise_alarm_tasks.after(UpdateGameLevel));
+}
+
+/// A task for one agent to run to an [AlarmPanel] and raise the alarm.
+#[derive(Component)]
+pub struct RaiseAlarmTask {
+    /// Where the agent saw the crew.
+    pub position: Vec3,
+    panel: Option<Entity>,
+    /// Despawned once the agent is unassigned from it.
+    finished: bool,
+}
+
+/// A task shared by every agent to search where the crew was last reported.
+///
+/// Exists while the level isn't calm, and is more important once the alarm is raised.
+#[derive(Component)]
+pub struct AlarmResponseTask {
+    pub position: Vec3,
+}
+
+/// Radios in escalated investigations and sends the agent to raise the alarm.
+fn radio_escalations(
+    mut commands: Commands,
+    mut escalated_r: EventReader<InvestigationEscalated>,
+    mut raise_alarm_w: EventWriter<RaiseAlarm>,
+    mut agent_q: Query<&mut AvailableTasks>,
+    alarm: Res<AlarmState>,
+) {
+    for escalated in escalated_r.read() {
+        debug!(
+            "Agent {} radioed in {}",
+            escalated.agent_entity, escalated.target_entity
+        );
+
+        raise_alarm_w.write(RaiseAlarm {
+            level: AlarmLevel::Suspicious,
+            position: escalated.position,
+        });
+
+        if alarm.level() >= AlarmLevel::Alarm {
+            continue;
+        }
+
+        let Ok(mut available_tasks) = agent_q.get_mut(escalated.agent_entity) else {
+            continue;
+        };
+
+        let task_entity = commands
+            .spawn((
+                RaiseAlarmTask {
+                    position: escalated.position,
+                    panel: None,
+                    finished: false,
+                },
+                TaskPriority::Respond,
+            ))
+            .id();
+
+        available_tasks.tasks.insert(task_entity);
+    }
+}
+
+/// Sends an agent to the nearest alarm panel, or has them radio the alarm in if there aren't any.
+fn start_raising_alarm(
+    trigger: Trigger<OnInsert, AssignedTo>,
+    mut agent_q: Query<(&AssignedTo, &GlobalTransform, &mut AgentTarget3d)>,
+    mut task_q: Query<&mut RaiseAlarmTask>,
+    panel_q: Query<(Entity, &GlobalTransform), With<AlarmPanel>>,
+    mut raise_alarm_w: EventWriter<RaiseAlarm>,
+    mut finish_w: EventWriter<FinishTask>,
+) -> Result {
+    let agent_entity = trigger.target();
+    let (&AssignedTo(task_entity), agent_transform, mut agent_target) =
+        agent_q.get_mut(agent_entity)?;
+
+    let Ok(mut task) = task_q.get_mut(task_entity) else {
+        return Ok(());
+    };
+
+    let nearest_panel = panel_q.iter().min_by(|(_, a), (_, b)| {
+        let a = a.translation().distance(agent_transform.translation());
+        let b = b.translation().distance(agent_transform.translation());
+        a.total_cmp(&b)
+    });
+
+    let Some((panel_entity, _)) = nearest_panel else {
+        debug!(
+            "Agent {} radioed in the alarm, there are no alarm panels",
+            agent_entity
+        );
+
+        raise_alarm_w.write(RaiseAlarm {
+            level: AlarmLevel::Alarm,
+            position: task.position,
+        });
+
+        task.finished = true;
+        finish_w.write(FinishTask {
+            agent_entity,
+            task_entity,
+            outcome: TaskOutcome::Completed,
+        });
+
+        return Ok(());
+    };
+
+    debug!(
+        "Agent {} is running to alarm panel {}",
+        agent_entity, panel_entity
+    );
+
+    task.panel = Some(panel_entity);
+    *agent_target = AgentTarget::Entity(panel_entity);
+
+    Ok(())
+}
+
+fn stop_raising_alarm(
+    trigger: Trigger<OnReplace, AssignedTo>,
+    mut commands: Commands,
+    mut agent_q: Query<(&AssignedTo, &mut AgentTarget3d, &mut AvailableTasks)>,
+    task_q: Query<&RaiseAlarmTask>,
+) {
+    let agent_entity = trigger.target();
+
+    let Ok((&AssignedTo(task_entity), mut agent_target, mut available_tasks)) =
+        agent_q.get_mut(agent_entity)
+    else {
+        return;
+    };
+
+    let Ok(task) = task_q.get(task_entity) else {
+        return;
+    };
+
+    *agent_target = AgentTarget::None;
+
+    if task.finished {
+        available_tasks.tasks.remove(&task_entity);
+        commands.entity(task_entity).despawn();
+    }
+}
+
+/// Despawns the tasks of agents that were despawned with an unloaded level before they raised the alarm.
+fn despawn_raise_alarm_tasks(
+    mut commands: Commands,
+    mut unloaded_r: EventReader<GameLevelUnloaded>,
+    task_q: Query<Entity, With<RaiseAlarmTask>>,
+) {
+    if unloaded_r.read().count() == 0 {
+        return;
+    }
+
+    for task_entity in task_q.iter() {
+        commands.entity(task_entity).despawn();
+    }
+}
+
+/// Raises the alarm when an agent reaches an alarm panel.
+///
+/// Agents that can't reach their panel radio the alarm in instead.
+fn reach_alarm_panels(
+    agent_q: Query<(Entity, &AssignedTo, &AgentState, &GlobalTransform)>,
+    mut task_q: Query<&mut RaiseAlarmTask>,
+    panel_q: Query<&GlobalTransform, With<AlarmPanel>>,
+    mut raise_alarm_w: EventWriter<RaiseAlarm>,
+    mut finish_w: EventWriter<FinishTask>,
+    alarm: Res<AlarmState>,
+) {
+    for (agent_entity, &AssignedTo(task_entity), nav_state, agent_transform) in &agent_q {
+        let Ok(mut task) = task_q.get_mut(task_entity) else {
+            continue;
+        };
+
+        let Some(panel_entity) = task.panel else {
+            continue;
+        };
+
+        if task.finished {
+            continue;
+        }
+
+        let raise_alarm = if alarm.level() >= AlarmLevel::Alarm {
+            // someone else already raised it
+            false
+        } else if panel_q
+            .get(panel_entity)
+            .is_ok_and(|panel_transform| reached(agent_transform, panel_transform.translation()))
+        {
+            info!("Agent {} raised the alarm", agent_entity);
+            true
+        } else if let AgentState::TargetNotOnNavMesh | AgentState::NoPath = nav_state {
+            debug!(
+                "Agent {} couldn't reach alarm panel {}, radioing in the alarm",
+                agent_entity, panel_entity
+            );
+            true
+        } else {
+            continue;
+        };
+
+        if raise_alarm {
+            raise_alarm_w.write(RaiseAlarm {
+                level: AlarmLevel::Alarm,
+                position: task.position,
+            });
+        }
+
+        task.finished = true;
+        finish_w.write(FinishTask {
+            agent_entity,
+            task_entity,
+            outcome: TaskOutcome::Completed,
+        });
+    }
+}
+
+/// Keeps the response task in line with the [AlarmState],
+/// giving it to every agent while the level isn't calm.
+fn update_response_task(
+    mut commands: Commands,
+    alarm: Res<AlarmState>,
+    mut task_q: Query<(Entity, &mut AlarmResponseTask, &TaskPriority)>,
+    mut agent_q: Query<(&mut AvailableTasks, Option<&AssignedTo>, &mut AgentTarget3d)>,
+) {
+    let priority = match alarm.level() {
+        AlarmLevel::Calm => None,
+        AlarmLevel::Suspicious => Some(TaskPriority::Investigate),
+        AlarmLevel::Alarm | AlarmLevel::Lockdown => Some(TaskPriority::Respond),
+    };
+
+    match (task_q.single_mut().ok(), priority.zip(alarm.position())) {
+        (Some((task_entity, mut task, &current_priority)), Some((priority, position))) => {
+            if task.position != position {
+                task.position = position;
+            }
+
+            if current_priority != priority {
+                debug!("Response to the alarm is now {:?}", priority);
+
+                commands.entity(task_entity).insert(priority);
+            }
+        }
+        (None, Some((priority, position))) => {
+            debug!("Agents are responding to the alarm");
+
+            let task_entity = commands
+                .spawn((AlarmResponseTask { position }, priority))
+                .id();
+
+            for (mut available_tasks, _, _) in agent_q.iter_mut() {
+                available_tasks.tasks.insert(task_entity);
+            }
+        }
+        (Some((task_entity, _, _)), None) => {
+            debug!("Agents stopped responding to the alarm");
+
+            for (mut available_tasks, assigned_to, mut agent_target) in agent_q.iter_mut() {
+                available_tasks.tasks.remove(&task_entity);
+
+                if assigned_to
+                    .is_some_and(|&AssignedTo(assigned_task)| assigned_task == task_entity)
+                {
+                    *agent_target = AgentTarget::None;
+                }
+            }
+
+            // despawning the task unassigns the agents so they go back to their other tasks
+            commands.entity(task_entity).despawn();
+        }
+        (None, None) => (),
+    }
+}
+
+fn stop_responding(
+    trigger: Trigger<OnReplace, AssignedTo>,
+    mut agent_q: Query<(&AssignedTo, &mut AgentTarget3d)>,
+    task_q: Query<(), With<AlarmResponseTask>>,
+) {
+    let agent_entity = trigger.target();
+
+    let Ok((&AssignedTo(task_entity), mut agent_target)) = agent_q.get_mut(agent_entity) else {
+        return;
+    };
+
+    if task_q.contains(task_entity) {
+        *agent_target = AgentTarget::None;
+    }
+}
+
+/// Walks agents between random points around where the crew was last reported.
+fn search_response_area(
+    mut agent_q: Query<(
+        &AssignedTo,
+        &AgentState,
+        &GlobalTransform,
+        &mut AgentTarget3d,
+    )>,
+    task_q: Query<&AlarmResponseTask>,
+) {
+    for (&AssignedTo(task_entity), nav_state, agent_transform, mut agent_target) in &mut agent_q {
+        let Ok(task) = task_q.get(task_entity) else {
+            continue;
+        };
+
+        let search_point_reached = match *agent_target {
+            AgentTarget::Point(point) => reached(agent_transform, point),
+            _ => true,
+        };
+
+        if search_point_reached
+            || matches!(
+                nav_state,
+                AgentState::TargetNotOnNavMesh | AgentState::NoPath
+            )
+        {
+            *agent_target =
+                AgentTarget::Point(random_point_near(task.position, RESPONSE_SEARCH_RADIUS));
+        }
+    }
+}
@@ -1,17 +1,48 @@
 use std::iter::once;
 
 use avian3d::prelude::*;
-use bevy::{platform::collections::HashSet, prelude::*};
+use bevy::{platform::collections::HashMap, prelude::*};
 use common::GameLayer;
 
 pub fn build(app: &mut App) {
-    app.add_systems(Update, cast_sight);
+    app.add_systems(Update, cast_sight.in_set(UpdateAgentSight));
 }
 
-/// Contains a list of [SightTarget]s that an agent can see.
+/// System set in [Update] where [AgentSight] is updated.
+#[derive(SystemSet, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct UpdateAgentSight;
+
+/// Contains the [SightTarget]s that an agent can see and how well they can see them.
 #[derive(Component, Default)]
 pub struct AgentSight {
-    targets: HashSet<Entity>,
+    targets: HashMap<Entity, Sighting>,
+}
+
+/// How an agent sees a [SightTarget].
+#[derive(Clone, Copy)]
+pub struct Sighting {
+    /// The distance to the closest visible part of the target.
+    pub distance: f32,
+    /// The smallest angle in radians between where the agent is looking and a visible part of the target.
+    pub angle: f32,
+    /// The portion of the target that isn't behind cover, from 0 to 1.
+    pub exposure: f32,
+}
+
+impl AgentSight {
+    pub fn targets(&self) -> impl Iterator<Item = Entity> + '_ {
+        self.targets.keys().copied()
+    }
+
+    pub fn sightings(&self) -> impl Iterator<Item = (Entity, &Sighting)> + '_ {
+        self.targets
+            .iter()
+            .map(|(&entity, sighting)| (entity, sighting))
+    }
+
+    pub fn can_see(&self, entity: Entity) -> bool {
+        self.targets.contains_key(&entity)
+    }
 }
 
 #[derive(Component)]
@@ -32,13 +63,26 @@ pub struct AgentEyes {
 pub struct SightTarget;
 
 /// A target that agent's will cast rays at and record if they can see a [SightTarget].
-#[derive(Component, Default)]
-pub struct SightCastTarget;
+///
+/// A ray is cast at each point, the more that reach the target the less it is in cover.
+#[derive(Component)]
+pub struct SightCastTarget {
+    /// Points relative to the target's transform.
+    pub points: Vec<Vec3>,
+}
+
+impl Default for SightCastTarget {
+    fn default() -> Self {
+        SightCastTarget {
+            points: vec![Vec3::ZERO],
+        }
+    }
+}
 
 fn cast_sight(
     mut agent_q: Query<(Entity, &GlobalTransform, &AgentEyes, &mut AgentSight)>,
     sight_targets: Query<(), With<SightTarget>>,
-    cast_targets: Query<&GlobalTransform, With<SightCastTarget>>,
+    cast_targets: Query<(&GlobalTransform, &SightCastTarget)>,
     spatial_query: SpatialQuery,
 ) {
     agent_q.par_iter_mut().for_each(
@@ -48,30 +92,47 @@ fn cast_sight(
 
             agent_sight.targets.clear();
 
-            for cast_transform in &cast_targets {
-                let (cast_direction, _) =
-                    Dir3::new_and_length(cast_transform.translation() - look_origin)
-                        .unwrap_or((Dir3::NEG_Z, 0.));
+            for (cast_transform, cast_target) in &cast_targets {
+                let point_exposure = 1. / cast_target.points.len() as f32;
 
-                let angle_to = cast_direction.angle_between(look_direction.into());
+                for &point in cast_target.points.iter() {
+                    let (cast_direction, _) =
+                        Dir3::new_and_length(cast_transform.transform_point(point) - look_origin)
+                            .unwrap_or((Dir3::NEG_Z, 0.));
 
-                if angle_to > agent_eyes.fov {
-                    continue;
-                }
+                    let angle_to = cast_direction.angle_between(look_direction.into());
+
+                    if angle_to > agent_eyes.fov {
+                        continue;
+                    }
+
+                    let Some(RayHitData {
+                        entity, distance, ..
+                    }) = spatial_query.cast_ray(
+                        look_origin,
+                        cast_direction,
+                        agent_eyes.range,
+                        false,
+                        &SpatialQueryFilter::from_mask([GameLayer::Opaque])
+                            .with_excluded_entities(once(agent_entity)),
+                    )
+                    else {
+                        continue;
+                    };
+
+                    if !sight_targets.contains(entity) {
+                        continue;
+                    }
+
+                    let sighting = agent_sight.targets.entry(entity).or_insert(Sighting {
+                        distance,
+                        angle: angle_to,
+                        exposure: 0.,
+                    });
 
-                let Some(RayHitData { entity, .. }) = spatial_query.cast_ray(
-                    look_origin,
-                    cast_direction,
-                    agent_eyes.range,
-                    false,
-                    &SpatialQueryFilter::from_mask([GameLayer::Opaque])
-                        .with_excluded_entities(once(agent_entity)),
-                ) else {
-                    continue;
-                };
-
-                if sight_targets.contains(entity) {
-                    agent_sight.targets.insert(entity);
+                    sighting.distance = sighting.distance.min(distance);
+                    sighting.angle = sighting.angle.min(angle_to);
+                    sighting.exposure = (sighting.exposure + point_exposure).min(1.);
                 }
             }
         },
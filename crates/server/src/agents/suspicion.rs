@@ -0,0 +1,236 @@
+//! Agents become suspicious of the [SightTarget](super::sight::SightTarget)s they see over time,
+//! and of whatever made the noises they hear.
+//!
+//! Suspicion builds faster the closer, more central, less covered, better lit and faster moving
+//! a target is, slower while the target is crouching, and fades while the target is out of sight.
+
+use avian3d::prelude::*;
+use bevy::{platform::collections::HashMap, prelude::*};
+use common::{
+    agents::{AgentSuspicionMeter, SuspicionLevel},
+    character::controller::{CharacterInput, PLAYER_MOVE_SPEED},
+    level::LightZone,
+    noise::NoiseKind,
+};
+
+use crate::agents::{
+    hearing::{AgentHearing, UpdateAgentHearing},
+    sight::{AgentEyes, AgentSight, UpdateAgentSight},
+};
+
+/// How much suspicion builds per second for an exposed target in the middle of an agent's view
+/// that is closer than [CLOSE_DISTANCE] and moving at walking speed.
+const SUSPICION_RATE: f32 = 1.0;
+/// How much suspicion fades per second while a target is out of sight.
+const SUSPICION_DECAY: f32 = 0.1;
+/// Targets farther than this build suspicion slower the farther away they are.
+const CLOSE_DISTANCE: f32 = 3.0;
+/// How fast suspicion builds at the edge of an agent's view compared to the middle.
+const PERIPHERAL_RATE: f32 = 0.3;
+/// How fast suspicion builds of a target standing still compared to one walking.
+const STILL_RATE: f32 = 0.4;
+/// The most suspicion can be sped up by a target moving faster than walking speed.
+const MAX_MOVING_RATE: f32 = 1.5;
+/// How fast suspicion builds of a crouching target compared to one standing.
+const CROUCHING_RATE: f32 = 0.6;
+/// The light level of places outside every [LightZone].
+const AMBIENT_LIGHT_LEVEL: f32 = 1.0;
+/// How fast suspicion builds of a target in complete darkness compared to one in full light.
+const DARK_RATE: f32 = 0.25;
+
+pub fn build(app: &mut App) {
+    app.add_systems(
+        Update,
+        (update_suspicion, update_suspicion_meters)
+            .chain()
+            .in_set(UpdateAgentSuspicion)
+            .after(UpdateAgentSight)
+            .after(UpdateAgentHearing),
+    );
+}
+
+/// System set in [Update] where [AgentSuspicion] is updated.
+#[derive(SystemSet, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct UpdateAgentSuspicion;
+
+/// How suspicious an agent is of each target they have seen, from 0 to 1.
+#[derive(Component, Default)]
+#[require(AgentSuspicionMeter)]
+pub struct AgentSuspicion {
+    targets: HashMap<Entity, f32>,
+}
+
+impl AgentSuspicion {
+    pub fn suspicion(&self, target_entity: Entity) -> f32 {
+        self.targets.get(&target_entity).copied().unwrap_or(0.)
+    }
+
+    pub fn level(&self, target_entity: Entity) -> SuspicionLevel {
+        SuspicionLevel::of(self.suspicion(target_entity))
+    }
+}
+
+/// How much suspicion a noise adds when heard at full volume.
+fn noise_suspicion(kind: NoiseKind) -> f32 {
+    match kind {
+        NoiseKind::Footstep => 0.15,
+        NoiseKind::Jump => 0.25,
+        NoiseKind::Door => 0.2,
+        NoiseKind::BreakingGlass => 0.7,
+        NoiseKind::Drilling => 0.7,
+    }
+}
+
+/// Raises an agent's suspicion of a target, logging when it reaches a new level.
+fn raise_suspicion(
+    agent_entity: Entity,
+    suspicion: &mut AgentSuspicion,
+    target_entity: Entity,
+    amount: f32,
+) {
+    let target_suspicion = suspicion.targets.entry(target_entity).or_default();
+    let old_level = SuspicionLevel::of(*target_suspicion);
+
+    *target_suspicion = (*target_suspicion + amount).min(1.);
+
+    let new_level = SuspicionLevel::of(*target_suspicion);
+
+    if new_level > old_level {
+        debug!(
+            "Agent {} is now {:?} of {}",
+            agent_entity, new_level, target_entity
+        );
+    }
+}
+
+fn update_suspicion(
+    mut agent_q: Query<(
+        Entity,
+        &AgentEyes,
+        &AgentSight,
+        Option<&AgentHearing>,
+        &mut AgentSuspicion,
+    )>,
+    target_q: Query<(&Position, &LinearVelocity, Option<&CharacterInput>)>,
+    light_q: Query<(&GlobalTransform, &LightZone)>,
+    time: Res<Time>,
+) {
+    let delta_seconds = time.delta_secs();
+
+    for (agent_entity, eyes, sight, hearing, mut suspicion) in agent_q.iter_mut() {
+        for noise in hearing.into_iter().flat_map(AgentHearing::noises) {
+            raise_suspicion(
+                agent_entity,
+                &mut suspicion,
+                noise.source,
+                noise_suspicion(noise.kind) * noise.volume,
+            );
+        }
+
+        for (target_entity, sighting) in sight.sightings() {
+            let distance_rate = (CLOSE_DISTANCE / sighting.distance).min(1.);
+            let angle_rate = PERIPHERAL_RATE.lerp(1., 1. - sighting.angle / eyes.fov);
+
+            let (speed, crouching, light_level) = target_q
+                .get(target_entity)
+                .map(|(&Position(position), velocity, input)| {
+                    (
+                        velocity.length(),
+                        input.is_some_and(|input| input.crouch),
+                        light_level(light_q.iter(), position),
+                    )
+                })
+                .unwrap_or((0., false, AMBIENT_LIGHT_LEVEL));
+            let moving_rate = STILL_RATE
+                .lerp(1., speed / PLAYER_MOVE_SPEED)
+                .min(MAX_MOVING_RATE);
+            let stance_rate = if crouching { CROUCHING_RATE } else { 1. };
+            let lighting_rate = DARK_RATE.lerp(1., light_level);
+
+            let rate = SUSPICION_RATE
+                * distance_rate
+                * angle_rate
+                * sighting.exposure
+                * moving_rate
+                * stance_rate
+                * lighting_rate;
+
+            raise_suspicion(
+                agent_entity,
+                &mut suspicion,
+                target_entity,
+                rate * delta_seconds,
+            );
+        }
+
+        // suspicion of targets out of sight fades until they are forgotten
+        suspicion
+            .targets
+            .retain(|&target_entity, target_suspicion| {
+                if !sight.can_see(target_entity) {
+                    *target_suspicion -= SUSPICION_DECAY * delta_seconds;
+                }
+
+                *target_suspicion > 0.
+            });
+    }
+}
+
+/// The light level at a point, which is the brightest of the zones containing it.
+fn light_level<'a>(
+    zones: impl Iterator<Item = (&'a GlobalTransform, &'a LightZone)>,
+    point: Vec3,
+) -> f32 {
+    zones
+        .filter(|(zone_transform, zone)| zone.contains(zone_transform, point))
+        .map(|(_, zone)| zone.light_level.clamp(0., 1.))
+        .reduce(f32::max)
+        .unwrap_or(AMBIENT_LIGHT_LEVEL)
+}
+
+fn update_suspicion_meters(mut agent_q: Query<(&AgentSuspicion, &mut AgentSuspicionMeter)>) {
+    for (suspicion, mut meter) in agent_q.iter_mut() {
+        let highest = suspicion.targets.values().copied().fold(0., f32::max);
+
+        meter.set_if_neq(AgentSuspicionMeter::new(highest));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::prelude::*;
+    use common::level::LightZone;
+
+    use crate::agents::suspicion::{AMBIENT_LIGHT_LEVEL, light_level};
+
+    #[test]
+    fn brightest_light_zone_is_used() {
+        let zones = [
+            (
+                GlobalTransform::IDENTITY,
+                LightZone {
+                    half_extents: Vec3::splat(2.),
+                    light_level: 0.2,
+                },
+            ),
+            (
+                GlobalTransform::from_translation(Vec3::X * 3.),
+                LightZone {
+                    half_extents: Vec3::splat(2.),
+                    light_level: 0.8,
+                },
+            ),
+        ];
+        let light_at = |point| {
+            light_level(
+                zones.iter().map(|(transform, zone)| (transform, zone)),
+                point,
+            )
+        };
+
+        assert_eq!(light_at(Vec3::ZERO), 0.2);
+        // inside both zones
+        assert_eq!(light_at(Vec3::X * 1.5), 0.8);
+        assert_eq!(light_at(Vec3::X * 10.), AMBIENT_LIGHT_LEVEL);
+    }
+}
@@ -1,14 +1,31 @@
-use bevy::{platform::collections::HashSet, prelude::*};
+use std::time::Duration;
+
+use bevy::{
+    platform::collections::{HashMap, HashSet},
+    prelude::*,
+};
 use rand::{Rng, rng};
 
+use crate::level::UpdateGameLevel;
+
+/// How long an agent waits before trying a task they failed again.
+const RETRY_DELAY: Duration = Duration::from_secs(5);
+
 pub fn build(app: &mut App) {
-    app.add_systems(Update, assign_tasks);
+    app.add_event::<FinishTask>();
+
+    app.add_systems(
+        Update,
+        (finish_tasks, assign_tasks).chain().after(UpdateGameLevel),
+    );
 }
 
 /// Exists on an agent when they have been assigned to a task.
 ///
 /// Use the lifecycle events of this component to trigger state
 /// upadates for starting and stopping tasks.
+/// Each kind of task should release what it holds for the agent in an [OnReplace] observer,
+/// which runs when the agent is pulled off a task by a higher priority one, finishes it or is despawned.
 #[derive(Component)]
 #[relationship(relationship_target = AssignedAgents)]
 pub struct AssignedTo(pub Entity);
@@ -23,25 +40,101 @@ pub struct AssignedAgents(Vec<Entity>);
 #[derive(Component, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 #[require(AssignedAgents)]
 pub enum TaskPriority {
+    /// Routine work like patrolling.
     #[default]
     Idle,
+    /// Checking on something that seemed off.
+    Curious,
+    /// Looking into something an agent is suspicious of.
+    Investigate,
+    /// Responding to an alarm or a call for help.
+    Respond,
+    /// Dealing with an intruder.
+    Combat,
 }
 
 /// A list of which tasks this agent can be assigned to.
 #[derive(Component, Default)]
 pub struct AvailableTasks {
     pub tasks: HashSet<Entity>,
+    /// Tasks the agent failed and when they can be assigned to them again.
+    retry_at: HashMap<Entity, Duration>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TaskOutcome {
+    Completed,
+    /// The agent won't be assigned to the task again for a while.
+    Failed,
+}
+
+/// Send to unassign an agent from a task they have finished,
+/// so that they fall back to their other available tasks.
+#[derive(Event)]
+pub struct FinishTask {
+    pub agent_entity: Entity,
+    pub task_entity: Entity,
+    pub outcome: TaskOutcome,
+}
+
+fn finish_tasks(
+    mut commands: Commands,
+    mut finish_r: EventReader<FinishTask>,
+    mut agent_q: Query<(&AssignedTo, &mut AvailableTasks)>,
+    time: Res<Time>,
+) {
+    for &FinishTask {
+        agent_entity,
+        task_entity,
+        outcome,
+    } in finish_r.read()
+    {
+        let Ok((&AssignedTo(assigned_task), mut available_tasks)) = agent_q.get_mut(agent_entity)
+        else {
+            continue;
+        };
+
+        // the agent was already pulled off of the task
+        if assigned_task != task_entity {
+            continue;
+        }
+
+        debug!(
+            "Agent {} finished task {}: {:?}",
+            agent_entity, task_entity, outcome
+        );
+
+        if outcome == TaskOutcome::Failed {
+            available_tasks
+                .retry_at
+                .insert(task_entity, time.elapsed() + RETRY_DELAY);
+        }
+
+        commands.entity(agent_entity).remove::<AssignedTo>();
+    }
 }
 
 fn assign_tasks(
     mut commands: Commands,
-    agent_q: Query<(Entity, &AvailableTasks, Option<&AssignedTo>)>,
+    mut agent_q: Query<(Entity, &mut AvailableTasks, Option<&AssignedTo>)>,
     task_q: Query<&TaskPriority>,
-) -> Result {
-    for (agent_entity, available_tasks, assigned_to) in &agent_q {
+    time: Res<Time>,
+) {
+    for (agent_entity, mut available_tasks, assigned_to) in &mut agent_q {
+        // tasks are despawned with their level
+        available_tasks
+            .tasks
+            .retain(|&task_entity| task_q.contains(task_entity));
+        available_tasks
+            .retry_at
+            .retain(|_, &mut retry_at| retry_at > time.elapsed());
+
         let current_priority = match assigned_to {
             Some(&AssignedTo(task_entity)) => {
-                let &priority = task_q.get(task_entity)?;
+                // the agent is unassigned once the despawned task's relationship is cleaned up
+                let Ok(&priority) = task_q.get(task_entity) else {
+                    continue;
+                };
                 Some(priority)
             }
             None => None,
@@ -51,7 +144,13 @@ fn assign_tasks(
         let mut possible_tasks = Vec::new();
 
         for &task_entity in &available_tasks.tasks {
-            let &priority = task_q.get(task_entity)?;
+            if available_tasks.retry_at.contains_key(&task_entity) {
+                continue;
+            }
+
+            let Ok(&priority) = task_q.get(task_entity) else {
+                continue;
+            };
 
             if let Some(current_priority) = current_priority {
                 if current_priority >= priority {
@@ -95,6 +194,108 @@ fn assign_tasks(
             .entity(agent_entity)
             .insert(AssignedTo(task_entity));
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bevy::prelude::*;
+
+    use crate::agents::tasks::{
+        self, AssignedTo, AvailableTasks, FinishTask, RETRY_DELAY, TaskOutcome, TaskPriority,
+    };
+
+    fn assigned_task(app: &App, agent_entity: Entity) -> Option<Entity> {
+        app.world()
+            .get::<AssignedTo>(agent_entity)
+            .map(|&AssignedTo(task_entity)| task_entity)
+    }
+
+    #[test]
+    fn failed_task_is_retried_after_delay() {
+        let mut app = App::new();
+        app.init_resource::<Time>();
+        tasks::build(&mut app);
+
+        let task_entity = app.world_mut().spawn(TaskPriority::Idle).id();
+        let agent_entity = app
+            .world_mut()
+            .spawn(AvailableTasks {
+                tasks: [task_entity].into_iter().collect(),
+                ..default()
+            })
+            .id();
+
+        app.update();
+        assert_eq!(assigned_task(&app, agent_entity), Some(task_entity));
+
+        app.world_mut().send_event(FinishTask {
+            agent_entity,
+            task_entity,
+            outcome: TaskOutcome::Failed,
+        });
+
+        app.update();
+        assert_eq!(assigned_task(&app, agent_entity), None);
+
+        app.world_mut()
+            .resource_mut::<Time>()
+            .advance_by(RETRY_DELAY - Duration::from_millis(1));
+
+        app.update();
+        assert_eq!(assigned_task(&app, agent_entity), None);
+
+        app.world_mut()
+            .resource_mut::<Time>()
+            .advance_by(Duration::from_millis(1));
+
+        app.update();
+        assert_eq!(assigned_task(&app, agent_entity), Some(task_entity));
+    }
+
+    #[test]
+    fn higher_priority_task_preempts() {
+        let mut app = App::new();
+        app.init_resource::<Time>();
+        tasks::build(&mut app);
 
-    Ok(())
+        let idle_task = app.world_mut().spawn(TaskPriority::Idle).id();
+        let respond_task = app.world_mut().spawn(TaskPriority::Respond).id();
+        let agent_entity = app
+            .world_mut()
+            .spawn(AvailableTasks {
+                tasks: [idle_task].into_iter().collect(),
+                ..default()
+            })
+            .id();
+
+        app.update();
+
+        app.world_mut()
+            .get_mut::<AvailableTasks>(agent_entity)
+            .unwrap()
+            .tasks
+            .insert(respond_task);
+
+        app.update();
+        assert_eq!(assigned_task(&app, agent_entity), Some(respond_task));
+
+        // finishing a task the agent was pulled off of does nothing
+        app.world_mut().send_event(FinishTask {
+            agent_entity,
+            task_entity: idle_task,
+            outcome: TaskOutcome::Failed,
+        });
+
+        app.update();
+        assert_eq!(assigned_task(&app, agent_entity), Some(respond_task));
+        assert!(
+            !app.world()
+                .get::<AvailableTasks>(agent_entity)
+                .unwrap()
+                .retry_at
+                .contains_key(&idle_task)
+        );
+    }
 }
@@ -0,0 +1,203 @@
+//! How alert the level is to the crew.
+//!
+//! Guards raise the alarm by radio and at [AlarmPanel](common::level::AlarmPanel)s,
+//! [SecurityCamera](common::level::SecurityCamera)s and anything else that notices the crew send [RaiseAlarm] too.
+//! Once the alarm is raised the police are on their way, and when they arrive the doors are locked.
+//! Every change of [AlarmLevel] is sent to clients for the HUD and audio cues.
+
+use std::time::Duration;
+
+use bevy::prelude::*;
+use common::{
+    alarm::{AlarmLevel, SetAlarmState},
+    elements::door::LockedDoor,
+    level::Door,
+    networking::StreamHeader,
+};
+use nevy::*;
+
+use crate::{
+    elements::door::DoorOpen,
+    level::{GameLevelUnloaded, UpdateGameLevel},
+    relevancy::Replicated,
+    state::JoinedClient,
+};
+
+/// How long the level stays suspicious if nothing else is noticed.
+const SUSPICIOUS_DURATION: Duration = Duration::from_secs(60);
+/// How long after the alarm is raised the police arrive.
+const POLICE_RESPONSE_DURATION: Duration = Duration::from_secs(120);
+
+pub fn build(app: &mut App) {
+    app.init_resource::<AlarmState>();
+    app.add_event::<RaiseAlarm>();
+
+    app.add_systems(
+        Update,
+        (raise_alarm, calm_down, arrive_police, lock_doors)
+            .chain()
+            .in_set(UpdateAlarm),
+    );
+    app.add_systems(Update, reset_alarm.after(UpdateGameLevel));
+
+    app.add_systems(PostUpdate, send_alarm_state.before(UpdateEndpoints));
+}
+
+/// System set in [Update] where the [AlarmState] is updated.
+#[derive(SystemSet, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct UpdateAlarm;
+
+/// Only changed when the level changes, so clients are only sent changes that matter to them.
+#[derive(Resource, Default)]
+pub struct AlarmState {
+    level: AlarmLevel,
+    /// Where the crew was last reported.
+    position: Option<Vec3>,
+    /// When the crew was last reported.
+    raised_at: Duration,
+    police_arrive_at: Option<Duration>,
+}
+
+impl AlarmState {
+    pub fn level(&self) -> AlarmLevel {
+        self.level
+    }
+
+    /// Where the crew was last reported, if they have been.
+    pub fn position(&self) -> Option<Vec3> {
+        self.position
+    }
+
+    pub fn police_arrive_at(&self) -> Option<Duration> {
+        self.police_arrive_at
+    }
+}
+
+/// Send to raise the alarm to at least `level`.
+#[derive(Event)]
+pub struct RaiseAlarm {
+    pub level: AlarmLevel,
+    /// Where the crew was noticed.
+    pub position: Vec3,
+}
+
+fn raise_alarm(
+    mut raise_r: EventReader<RaiseAlarm>,
+    mut alarm: ResMut<AlarmState>,
+    time: Res<Time>,
+) {
+    for &RaiseAlarm { level, position } in raise_r.read() {
+        if level < alarm.level {
+            continue;
+        }
+
+        let state = alarm.bypass_change_detection();
+        state.position = Some(position);
+        state.raised_at = time.elapsed();
+
+        if level == alarm.level {
+            continue;
+        }
+
+        info!("The alarm was raised to {:?}", level);
+
+        alarm.level = level;
+
+        if level >= AlarmLevel::Alarm && alarm.police_arrive_at.is_none() {
+            alarm.police_arrive_at = Some(time.elapsed() + POLICE_RESPONSE_DURATION);
+        }
+    }
+}
+
+/// Returns to calm if nothing else is noticed while suspicious.
+fn calm_down(mut alarm: ResMut<AlarmState>, time: Res<Time>) {
+    if alarm.level != AlarmLevel::Suspicious
+        || time.elapsed() < alarm.raised_at + SUSPICIOUS_DURATION
+    {
+        return;
+    }
+
+    info!("The guards calmed down");
+
+    *alarm = AlarmState::default();
+}
+
+fn arrive_police(mut alarm: ResMut<AlarmState>, time: Res<Time>) {
+    let Some(police_arrive_at) = alarm.police_arrive_at else {
+        return;
+    };
+
+    if time.elapsed() < police_arrive_at || alarm.level >= AlarmLevel::Lockdown {
+        return;
+    }
+
+    info!("The police have arrived, locking down");
+
+    alarm.level = AlarmLevel::Lockdown;
+}
+
+/// Locks every door shut during a lockdown.
+///
+/// Doors are only replicated once they have been locked, clients don't need to know about them before that.
+fn lock_doors(
+    mut commands: Commands,
+    alarm: Res<AlarmState>,
+    door_q: Query<(Entity, &GlobalTransform, Has<LockedDoor>), With<Door>>,
+) {
+    let locked = alarm.level >= AlarmLevel::Lockdown;
+
+    for (door_entity, door_transform, is_locked) in door_q.iter() {
+        if locked && !is_locked {
+            commands.entity(door_entity).remove::<DoorOpen>().insert((
+                LockedDoor {
+                    position: door_transform.translation(),
+                    rotation: door_transform.rotation(),
+                },
+                Replicated,
+            ));
+        } else if !locked && is_locked {
+            commands.entity(door_entity).remove::<LockedDoor>();
+        }
+    }
+}
+
+/// Calms the level down when it is unloaded.
+fn reset_alarm(mut unloaded_r: EventReader<GameLevelUnloaded>, mut alarm: ResMut<AlarmState>) {
+    if unloaded_r.read().count() == 0 {
+        return;
+    }
+
+    *alarm = AlarmState::default();
+}
+
+/// Sends the [AlarmLevel] to every client when it changes and to clients when they join.
+fn send_alarm_state(
+    client_q: Query<(Entity, Ref<JoinedClient>)>,
+    alarm: Res<AlarmState>,
+    mut messages: LocalMessageSender,
+    message_id: Res<MessageId<SetAlarmState>>,
+    time: Res<Time>,
+) -> Result {
+    messages.flush()?;
+
+    for (client_entity, joined) in client_q.iter() {
+        if !alarm.is_changed() && !joined.is_added() {
+            continue;
+        }
+
+        messages.write(
+            StreamHeader::Messages,
+            client_entity,
+            *message_id,
+            true,
+            &SetAlarmState {
+                level: alarm.level,
+                police_arrive_in: alarm
+                    .police_arrive_at
+                    .map(|arrive_at| arrive_at.saturating_sub(time.elapsed())),
+            },
+        )?;
+    }
+
+    Ok(())
+}
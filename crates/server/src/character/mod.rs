@@ -1,27 +1,67 @@
+use std::{collections::VecDeque, time::Duration};
+
 use avian3d::prelude::*;
 use bevy::prelude::*;
 use common::{
     character::{
-        CharacterStateUpdate, SetLocalPlayer, SpawnCharacter,
-        controller::{CharacterController, CharacterInput},
+        Character, CharacterStateAck, CharacterStateUpdate, SetLocalPlayer,
+        controller::{CharacterController, CharacterInput, PLAYER_MOVE_SPEED, on_ground},
     },
+    level::PlayerSpawnPoint,
     networking::StreamHeader,
+    noise::{NoiseEvent, NoiseKind},
+    state::SessionToken,
 };
 use nevy::*;
+use rand::{Rng, rng};
 
 use crate::{
     agents::{
         investigation::InvestigationTarget,
         sight::{SightCastTarget, SightTarget},
     },
+    level::{GameLevelLoaded, UpdateGameLevel},
     physics_replication::ReplicateBody,
-    state::{JoinedClient, initialize_pairs::InitializePairs},
+    replication::SendComponentUpdates,
+    state::{JoinedClient, Sessions, initialize_pairs::InitializePairs},
 };
 
+/// The most inputs that can be waiting to be simulated for a character.
+///
+/// A client that sends inputs faster than the server simulates them will have the oldest ones dropped,
+/// so they can't move faster by speeding up their clock.
+const MAX_BUFFERED_INPUTS: usize = 8;
+/// How many inputs are buffered before a character starts simulating them,
+/// so that inputs arriving late don't leave the buffer empty.
+const JITTER_BUFFER_INPUTS: usize = 2;
+/// How many ticks a character keeps moving with it's last input when no new input has arrived.
+const MAX_EXTRAPOLATED_INPUTS: u32 = 4;
+const ACK_INTERVAL: Duration = Duration::from_millis(50);
+/// How far a character walks between footsteps.
+const STEP_LENGTH: f32 = 0.7;
+
 pub fn build(app: &mut App) {
-    app.add_systems(Update, (receive_character_updates, spawn_characters));
+    app.add_systems(
+        Update,
+        (
+            receive_character_updates,
+            spawn_characters,
+            disconnect_characters,
+            despawn_expired_characters,
+            emit_footsteps,
+        ),
+    );
+    app.add_systems(Update, move_characters_to_spawn.after(UpdateGameLevel));
+    app.add_systems(FixedPreUpdate, apply_character_inputs);
 
-    app.add_systems(PostUpdate, initliaze_characters.before(UpdateEndpoints));
+    app.add_systems(
+        PostUpdate,
+        (
+            set_local_players.after(SendComponentUpdates),
+            send_character_acks,
+        )
+            .before(UpdateEndpoints),
+    );
 }
 
 #[derive(Component, Deref)]
@@ -32,72 +72,236 @@ pub struct CharacterOfClient(pub Entity);
 #[relationship_target(relationship = CharacterOfClient)]
 pub struct ClientOfCharacter(Entity);
 
-fn spawn_characters(mut commands: Commands, new_clients: Query<Entity, Added<JoinedClient>>) {
-    for client_entity in new_clients.iter() {
+/// The session of the player a character belongs to,
+/// so that the player can reclaim it after reconnecting.
+#[derive(Component, Deref)]
+pub struct CharacterSession(pub SessionToken);
+
+/// Marker component for characters whose player has disconnected.
+///
+/// They stay where they are until the player rejoins or their session expires.
+#[derive(Component)]
+pub struct DisconnectedCharacter;
+
+/// Inputs received from a client that haven't been simulated yet.
+#[derive(Component, Default)]
+pub struct CharacterInputBuffer {
+    /// Ordered by sequence.
+    inputs: VecDeque<(u32, CharacterInput)>,
+    /// The sequence of the last input that was simulated, which is the sequence that is acknowledged.
+    last_simulated: Option<u32>,
+    /// How many ticks in a row have been simulated without an input.
+    extrapolated: u32,
+}
+
+/// How far a character has walked since their last footstep.
+#[derive(Component, Default)]
+pub struct Footsteps {
+    distance: f32,
+}
+
+/// Makes a footstep noise every [STEP_LENGTH] a character walks on the ground,
+/// louder the faster they are moving.
+fn emit_footsteps(
+    mut character_q: Query<(Entity, &Position, &LinearVelocity, &mut Footsteps)>,
+    spatial_query: SpatialQuery,
+    mut noise_w: EventWriter<NoiseEvent>,
+    time: Res<Time>,
+) {
+    for (character_entity, &Position(position), velocity, mut footsteps) in character_q.iter_mut() {
+        if !on_ground(&spatial_query, position) {
+            continue;
+        }
+
+        let speed = velocity.xz().length();
+        footsteps.distance += speed * time.delta_secs();
+
+        if footsteps.distance < STEP_LENGTH {
+            continue;
+        }
+
+        footsteps.distance = 0.;
+
+        let mut noise = NoiseEvent::new(character_entity, position, NoiseKind::Footstep);
+        noise.loudness *= speed / PLAYER_MOVE_SPEED;
+
+        noise_w.write(noise);
+    }
+}
+
+/// Picks a random [PlayerSpawnPoint], or the origin if there are none.
+fn random_spawn_position(spawn_point_q: &Query<&GlobalTransform, With<PlayerSpawnPoint>>) -> Vec3 {
+    let spawn_points: Vec<Vec3> = spawn_point_q
+        .iter()
+        .map(|transform| transform.translation())
+        .collect();
+
+    if spawn_points.is_empty() {
+        return Vec3::ZERO;
+    }
+
+    spawn_points[rng().random_range(0..spawn_points.len())]
+}
+
+fn spawn_characters(
+    mut commands: Commands,
+    new_clients: Query<(Entity, &JoinedClient), Added<JoinedClient>>,
+    session_character_q: Query<(Entity, &CharacterSession)>,
+    spawn_point_q: Query<&GlobalTransform, With<PlayerSpawnPoint>>,
+) {
+    for (client_entity, joined) in new_clients.iter() {
+        // the character may still belong to the old connection if it hasn't closed yet
+        if let Some((character_entity, _)) = session_character_q
+            .iter()
+            .find(|(_, session)| ***session == joined.session_token)
+        {
+            debug!(
+                "Client {} reclaimed character {}",
+                client_entity, character_entity
+            );
+
+            commands
+                .entity(character_entity)
+                .remove::<DisconnectedCharacter>()
+                .insert((
+                    CharacterOfClient(client_entity),
+                    // the new connection numbers it's inputs from the start again
+                    CharacterInputBuffer::default(),
+                ));
+
+            continue;
+        }
+
         commands.spawn((
             CharacterController,
+            Position(random_spawn_position(&spawn_point_q)),
+            CharacterInputBuffer::default(),
             CharacterOfClient(client_entity),
+            CharacterSession(joined.session_token),
             ReplicateBody,
             SightTarget,
-            SightCastTarget,
+            // feet, chest and head
+            SightCastTarget {
+                points: vec![Vec3::Y * 0.3, Vec3::Y, Vec3::Y * 1.7],
+            },
             InvestigationTarget,
+            Footsteps::default(),
         ));
     }
 }
 
-fn initliaze_characters(
-    pairs: InitializePairs<CharacterController>,
+/// Moves every character to a spawn point once a level has loaded.
+fn move_characters_to_spawn(
+    mut loaded_r: EventReader<GameLevelLoaded>,
+    mut character_q: Query<(&mut Position, &mut LinearVelocity), With<CharacterSession>>,
+    spawn_point_q: Query<&GlobalTransform, With<PlayerSpawnPoint>>,
+) {
+    if loaded_r.read().count() == 0 {
+        return;
+    }
+
+    for (mut position, mut velocity) in character_q.iter_mut() {
+        position.0 = random_spawn_position(&spawn_point_q);
+        velocity.0 = Vec3::ZERO;
+    }
+}
+
+/// Stops characters whose client disconnected.
+fn disconnect_characters(
+    mut commands: Commands,
+    mut removed_clients: RemovedComponents<CharacterOfClient>,
+    mut character_q: Query<&mut CharacterInput, With<CharacterSession>>,
+) {
+    for character_entity in removed_clients.read() {
+        // the character was despawned
+        let Ok(mut input) = character_q.get_mut(character_entity) else {
+            continue;
+        };
+
+        debug!("Character {} was disconnected", character_entity);
+
+        *input = CharacterInput {
+            look_direction: input.look_direction,
+            ..default()
+        };
+
+        commands
+            .entity(character_entity)
+            .insert(DisconnectedCharacter);
+    }
+}
+
+/// Despawns disconnected characters once their player can't rejoin anymore.
+fn despawn_expired_characters(
+    mut commands: Commands,
+    character_q: Query<(Entity, &CharacterSession), With<DisconnectedCharacter>>,
+    sessions: Res<Sessions>,
+) {
+    for (character_entity, session) in character_q.iter() {
+        if sessions.get(**session).is_none() {
+            debug!("Despawning expired character {}", character_entity);
+
+            commands.entity(character_entity).despawn();
+        }
+    }
+}
+
+/// Tells clients which character is theirs once it has been replicated to them.
+fn set_local_players(
+    pairs: InitializePairs<Character>,
     character_q: Query<&CharacterOfClient>,
     mut messages: LocalMessageSender,
-    spawn_character: Res<MessageId<SpawnCharacter>>,
-    set_local_player: Res<MessageId<SetLocalPlayer>>,
+    message_id: Res<MessageId<SetLocalPlayer>>,
 ) -> Result {
     messages.flush()?;
 
-    for (client_entity, character_entity) in pairs.iter() {
+    for (client_entity, character_entity) in local_players(pairs.iter(), &character_q) {
         messages.write(
             StreamHeader::Messages,
             client_entity,
-            *spawn_character,
+            *message_id,
             true,
-            &SpawnCharacter {
+            &SetLocalPlayer {
                 server_entity: character_entity.into(),
             },
         )?;
+    }
+
+    Ok(())
+}
 
-        let character_of_client = character_q.get(character_entity)?;
+/// Keeps the (client, character) pairs where the character belongs to the client.
+fn local_players(
+    pairs: impl Iterator<Item = (Entity, Entity)>,
+    character_q: &Query<&CharacterOfClient>,
+) -> Vec<(Entity, Entity)> {
+    let mut local_players = Vec::new();
+
+    for (client_entity, character_entity) in pairs {
+        // disconnected characters don't belong to a client
+        let Ok(character_of_client) = character_q.get(character_entity) else {
+            continue;
+        };
 
         if **character_of_client == client_entity {
-            messages.write(
-                StreamHeader::Messages,
-                client_entity,
-                *set_local_player,
-                true,
-                &SetLocalPlayer {
-                    server_entity: character_entity.into(),
-                },
-            )?;
+            local_players.push((client_entity, character_entity));
         }
     }
 
-    Ok(())
+    local_players
 }
 
+/// Receives character inputs from clients and buffers them to be simulated.
 fn receive_character_updates(
     mut client_q: Query<(
         Entity,
         &mut ReceivedMessages<CharacterStateUpdate>,
         Option<&ClientOfCharacter>,
     )>,
-    mut character_q: Query<(
-        &mut Position,
-        &mut LinearVelocity,
-        &mut Rotation,
-        &mut CharacterInput,
-    )>,
+    mut character_q: Query<&mut CharacterInputBuffer>,
 ) -> Result {
     for (client_entity, mut messages, character_of) in client_q.iter_mut() {
-        for state_update in messages.drain() {
+        for CharacterStateUpdate { sequence, input } in messages.drain() {
             let Some(character_of) = character_of else {
                 warn!(
                     "Client {} sent a character state update when they don't have a character",
@@ -106,15 +310,205 @@ fn receive_character_updates(
                 continue;
             };
 
-            let (mut position, mut velocity, mut rotation, mut input) =
-                character_q.get_mut(**character_of)?;
+            if !input.look_direction.is_normalized() {
+                warn!("Client {} sent an invalid character input", client_entity);
+                continue;
+            }
+
+            let mut buffer = character_q.get_mut(**character_of)?;
+
+            // inputs are too late once the character has been simulated and acknowledged past them
+            if buffer.last_simulated.is_some_and(|last| sequence <= last) {
+                continue;
+            }
+
+            // late inputs are put back in order, duplicates are ignored
+            let Err(index) = buffer
+                .inputs
+                .binary_search_by_key(&sequence, |&(sequence, _)| sequence)
+            else {
+                continue;
+            };
+
+            buffer.inputs.insert(index, (sequence, input));
 
-            position.0 = state_update.position;
-            velocity.0 = state_update.velocity;
-            rotation.0 = state_update.rotation;
-            *input = state_update.input;
+            if buffer.inputs.len() > MAX_BUFFERED_INPUTS {
+                buffer.inputs.pop_front();
+            }
         }
     }
 
     Ok(())
 }
+
+/// Applies one buffered input to each character every fixed update.
+///
+/// Characters wait for [JITTER_BUFFER_INPUTS] before simulating their first input.
+/// If no input has arrived the character keeps simulating it's last input for [MAX_EXTRAPOLATED_INPUTS] ticks and then stops.
+/// Extrapolated ticks don't use up a sequence, so an input that arrives late is still simulated.
+fn apply_character_inputs(
+    mut character_q: Query<(&mut CharacterInputBuffer, &mut CharacterInput)>,
+) {
+    for (mut buffer, mut input) in character_q.iter_mut() {
+        if buffer.last_simulated.is_none() && buffer.inputs.len() < JITTER_BUFFER_INPUTS {
+            continue;
+        }
+
+        if let Some((sequence, next_input)) = buffer.inputs.pop_front() {
+            *input = next_input;
+            buffer.last_simulated = Some(sequence);
+            buffer.extrapolated = 0;
+
+            continue;
+        }
+
+        buffer.extrapolated = buffer.extrapolated.saturating_add(1);
+
+        *input = if buffer.extrapolated <= MAX_EXTRAPOLATED_INPUTS {
+            // a jump is only for the tick it was pressed
+            CharacterInput {
+                jump: false,
+                ..*input
+            }
+        } else {
+            CharacterInput {
+                look_direction: input.look_direction,
+                ..default()
+            }
+        };
+    }
+}
+
+/// Sends each client the server's state of their character so they can reconcile their prediction.
+fn send_character_acks(
+    character_q: Query<(
+        &CharacterOfClient,
+        &CharacterInputBuffer,
+        &Position,
+        &LinearVelocity,
+    )>,
+    mut messages: LocalMessageSender,
+    message_id: Res<MessageId<CharacterStateAck>>,
+    time: Res<Time>,
+    mut last_ack: Local<Duration>,
+) -> Result {
+    if time.elapsed() - *last_ack < ACK_INTERVAL {
+        return Ok(());
+    }
+
+    *last_ack = time.elapsed();
+
+    for (&CharacterOfClient(client_entity), buffer, position, velocity) in character_q.iter() {
+        let Some(sequence) = buffer.last_simulated else {
+            continue;
+        };
+
+        // if out of bandwidth don't send, another ack will be sent soon
+        messages.write(
+            StreamHeader::Messages,
+            client_entity,
+            *message_id,
+            false,
+            &CharacterStateAck {
+                sequence,
+                position: position.0,
+                velocity: velocity.0,
+            },
+        )?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::{ecs::system::RunSystemOnce, prelude::*};
+    use common::character::controller::CharacterInput;
+
+    use crate::character::{
+        CharacterInputBuffer, CharacterOfClient, DisconnectedCharacter, apply_character_inputs,
+        local_players,
+    };
+
+    fn moving_forward() -> CharacterInput {
+        CharacterInput {
+            move_forward: true,
+            ..default()
+        }
+    }
+
+    #[test]
+    fn late_input_is_simulated() {
+        let mut app = App::new();
+        app.add_systems(Update, apply_character_inputs);
+
+        let character_entity = app
+            .world_mut()
+            .spawn((
+                CharacterInputBuffer {
+                    inputs: [
+                        (0, CharacterInput::default()),
+                        (1, CharacterInput::default()),
+                    ]
+                    .into_iter()
+                    .collect(),
+                    ..default()
+                },
+                CharacterInput::default(),
+            ))
+            .id();
+
+        app.update();
+        app.update();
+
+        // input 2 hasn't arrived yet
+        app.update();
+
+        let buffer = app
+            .world()
+            .get::<CharacterInputBuffer>(character_entity)
+            .unwrap();
+        assert_eq!(buffer.last_simulated, Some(1));
+
+        app.world_mut()
+            .get_mut::<CharacterInputBuffer>(character_entity)
+            .unwrap()
+            .inputs
+            .push_back((2, moving_forward()));
+
+        app.update();
+
+        let buffer = app
+            .world()
+            .get::<CharacterInputBuffer>(character_entity)
+            .unwrap();
+        assert_eq!(buffer.last_simulated, Some(2));
+        assert!(app.world().get::<CharacterInput>(character_entity).unwrap() == &moving_forward());
+    }
+
+    #[test]
+    fn joining_client_skips_disconnected_characters() {
+        let mut world = World::new();
+
+        let connected_client = world.spawn_empty().id();
+        let connected_character = world.spawn(CharacterOfClient(connected_client)).id();
+        let disconnected_character = world.spawn(DisconnectedCharacter).id();
+
+        let joining_client = world.spawn_empty().id();
+        let joining_character = world.spawn(CharacterOfClient(joining_client)).id();
+
+        let pairs = vec![
+            (joining_client, connected_character),
+            (joining_client, disconnected_character),
+            (joining_client, joining_character),
+        ];
+
+        let local_players = world
+            .run_system_once(move |character_q: Query<&CharacterOfClient>| {
+                local_players(pairs.clone().into_iter(), &character_q)
+            })
+            .unwrap();
+
+        assert_eq!(local_players, vec![(joining_client, joining_character)]);
+    }
+}
@@ -0,0 +1,35 @@
+use bevy::prelude::*;
+use common::{
+    clock::{TimePing, TimePong},
+    networking::StreamHeader,
+};
+use nevy::*;
+
+pub fn build(app: &mut App) {
+    app.add_systems(Update, respond_to_pings);
+}
+
+fn respond_to_pings(
+    mut client_q: Query<(Entity, &mut ReceivedMessages<TimePing>)>,
+    mut messages: LocalMessageSender,
+    message_id: Res<MessageId<TimePong>>,
+    time: Res<Time>,
+) -> Result {
+    for (client_entity, mut pings) in client_q.iter_mut() {
+        for TimePing { client_time } in pings.drain() {
+            // if out of bandwidth don't send, the client will ping again
+            messages.write(
+                StreamHeader::Messages,
+                client_entity,
+                *message_id,
+                false,
+                &TimePong {
+                    client_time,
+                    server_time: time.elapsed(),
+                },
+            )?;
+        }
+    }
+
+    Ok(())
+}
@@ -1,18 +1,69 @@
+use std::{net::Ipv4Addr, path::PathBuf};
+
 use bevy::prelude::*;
 
+/// The level that is loaded when a match starts if no other level is given.
+const DEFAULT_LEVEL_SCENE: &str = "bank.scn.ron";
+
 #[derive(Resource)]
 pub struct ServerConfig {
+    pub bind_address: Ipv4Addr,
     pub bind_port: u16,
+    /// PEM file of the server's certificate.
+    ///
+    /// If it or the private key doesn't exist a self signed certificate is generated and saved to both paths.
+    pub certificate_path: PathBuf,
+    /// PEM file of the server's private key.
+    pub private_key_path: PathBuf,
+    /// The name to generate a self signed certificate for.
+    pub server_name: String,
+    /// If set clients need this password to join.
+    pub password: Option<String>,
+    /// The level scene loaded when a match starts, relative to the levels directory.
+    pub level_scene: String,
 }
 
 impl ServerConfig {
+    /// The default config for a server on `bind_port`.
+    pub fn new(bind_port: u16) -> Self {
+        ServerConfig {
+            bind_address: Ipv4Addr::UNSPECIFIED,
+            bind_port,
+            certificate_path: "certificate.pem".into(),
+            private_key_path: "private_key.pem".into(),
+            server_name: "localhost".into(),
+            password: None,
+            level_scene: DEFAULT_LEVEL_SCENE.into(),
+        }
+    }
+
+    /// Loads the config from command line arguments.
+    ///
+    /// `server <bind port> [--certificate <path>] [--private-key <path>] [--server-name <name>] [--password <password>] [--level <path>]`
     pub fn load() -> Result<Self> {
-        let bind_port = std::env::args()
-            .nth(1)
-            .ok_or("Expected bind port as first argument")?;
+        let mut args = std::env::args().skip(1);
+
+        let bind_port = args.next().ok_or("Expected bind port as first argument")?;
 
         let bind_port = bind_port.parse().map_err(|_| "Invalid bind port format")?;
 
-        Ok(ServerConfig { bind_port })
+        let mut config = ServerConfig::new(bind_port);
+
+        while let Some(arg) = args.next() {
+            let value = args
+                .next()
+                .ok_or_else(|| format!("Expected a value after \"{}\"", arg))?;
+
+            match arg.as_str() {
+                "--certificate" => config.certificate_path = value.into(),
+                "--private-key" => config.private_key_path = value.into(),
+                "--server-name" => config.server_name = value,
+                "--password" => config.password = Some(value),
+                "--level" => config.level_scene = value,
+                _ => return Err(format!("Unknown argument \"{}\"", arg).into()),
+            }
+        }
+
+        Ok(config)
     }
 }
@@ -0,0 +1,62 @@
+//! Doors open while a character is standing at them and close again once they leave.
+//!
+//! Opening a door makes a noise that guards can hear.
+//! [LockedDoor]s stay shut.
+
+use avian3d::prelude::*;
+use bevy::prelude::*;
+use common::{
+    character::Character,
+    elements::door::LockedDoor,
+    level::Door,
+    noise::{NoiseEvent, NoiseKind},
+};
+
+/// How close a character has to be to a door to open it.
+const DOOR_OPEN_DISTANCE: f32 = 1.5;
+
+pub fn build(app: &mut App) {
+    app.add_systems(Update, open_doors);
+}
+
+/// Exists on a [Door] while it is open.
+#[derive(Component)]
+pub struct DoorOpen;
+
+fn open_doors(
+    mut commands: Commands,
+    door_q: Query<(Entity, &GlobalTransform, Has<DoorOpen>), (With<Door>, Without<LockedDoor>)>,
+    character_q: Query<(Entity, &Position), With<Character>>,
+    mut noise_w: EventWriter<NoiseEvent>,
+) {
+    for (door_entity, door_transform, open) in door_q.iter() {
+        let door_position = door_transform.translation();
+
+        let nearest_character = character_q
+            .iter()
+            .map(|(character_entity, &Position(position))| {
+                (character_entity, position.distance(door_position))
+            })
+            .filter(|&(_, distance)| distance <= DOOR_OPEN_DISTANCE)
+            .min_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(character_entity, _)| character_entity);
+
+        match (nearest_character, open) {
+            (Some(character_entity), false) => {
+                debug!("Character {} opened door {}", character_entity, door_entity);
+
+                commands.entity(door_entity).insert(DoorOpen);
+
+                noise_w.write(NoiseEvent::new(
+                    character_entity,
+                    door_position,
+                    NoiseKind::Door,
+                ));
+            }
+            (None, true) => {
+                commands.entity(door_entity).remove::<DoorOpen>();
+            }
+            _ => (),
+        }
+    }
+}
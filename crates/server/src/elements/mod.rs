@@ -1,7 +1,11 @@
 use bevy::prelude::*;
 
+pub mod door;
 pub mod gltf_collider;
+pub mod security_camera;
 
 pub fn build(app: &mut App) {
+    door::build(app);
     gltf_collider::build(app);
+    security_camera::build(app);
 }
@@ -0,0 +1,82 @@
+//! Security cameras raise the alarm when they watch the crew for too long.
+//!
+//! Cameras see with [AgentEyes] the same way agents do.
+
+use std::time::Duration;
+
+use bevy::prelude::*;
+use common::{alarm::AlarmLevel, level::SecurityCamera};
+
+use crate::{
+    agents::sight::{AgentEyes, AgentSight, UpdateAgentSight},
+    alarm::{RaiseAlarm, UpdateAlarm},
+};
+
+/// How long a camera has to watch the crew before it raises the alarm.
+const CAMERA_DETECTION_DURATION: Duration = Duration::from_secs(3);
+
+pub fn build(app: &mut App) {
+    app.add_systems(
+        Update,
+        (
+            init_security_cameras.before(UpdateAgentSight),
+            watch_security_cameras
+                .after(UpdateAgentSight)
+                .before(UpdateAlarm),
+        ),
+    );
+}
+
+/// How long a [SecurityCamera] has been watching the crew without looking away.
+#[derive(Component, Default)]
+pub struct CameraWatch {
+    watching_for: Duration,
+}
+
+fn init_security_cameras(
+    mut commands: Commands,
+    camera_q: Query<(Entity, &SecurityCamera), Changed<SecurityCamera>>,
+) {
+    for (camera_entity, camera) in camera_q.iter() {
+        commands.entity(camera_entity).insert((
+            AgentEyes {
+                offset: Vec3::ZERO,
+                fov: camera.fov,
+                range: camera.range,
+            },
+            CameraWatch::default(),
+        ));
+    }
+}
+
+fn watch_security_cameras(
+    mut camera_q: Query<(Entity, &AgentSight, &mut CameraWatch), With<SecurityCamera>>,
+    target_q: Query<&GlobalTransform>,
+    mut raise_alarm_w: EventWriter<RaiseAlarm>,
+    time: Res<Time>,
+) {
+    for (camera_entity, sight, mut watch) in camera_q.iter_mut() {
+        let Some(target_transform) = sight
+            .targets()
+            .find_map(|target_entity| target_q.get(target_entity).ok())
+        else {
+            watch.watching_for = Duration::ZERO;
+            continue;
+        };
+
+        watch.watching_for += time.delta();
+
+        if watch.watching_for < CAMERA_DETECTION_DURATION {
+            continue;
+        }
+
+        debug!("Security camera {} spotted the crew", camera_entity);
+
+        raise_alarm_w.write(RaiseAlarm {
+            level: AlarmLevel::Alarm,
+            position: target_transform.translation(),
+        });
+
+        watch.watching_for = Duration::ZERO;
+    }
+}
@@ -1,25 +1,99 @@
 use std::path::PathBuf;
 
-use bevy::prelude::*;
-use common::DebugStartLevel;
-use nevy::ReceivedMessages;
+use bevy::{prelude::*, scene::SceneInstanceReady};
 
 pub fn build(app: &mut App) {
     app.add_event::<LoadGameLevel>();
+    app.add_event::<UnloadGameLevel>();
+    app.add_event::<SwitchGameLevel>();
+    app.add_event::<GameLevelLoaded>();
+    app.add_event::<GameLevelUnloaded>();
 
-    app.add_systems(Update, load_levels);
-
-    app.add_systems(Update, debug_start_level);
+    app.add_systems(
+        Update,
+        (switch_levels, unload_levels, load_levels)
+            .chain()
+            .in_set(UpdateGameLevel),
+    );
 }
 
+/// System set in [Update] that loads and unloads levels.
+///
+/// Systems that reset state when a level is unloaded should run after this.
+#[derive(SystemSet, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct UpdateGameLevel;
+
 #[derive(Event)]
 pub struct LoadGameLevel {
     pub level_scene: String,
 }
 
+/// Despawns the loaded level and everything in it.
+#[derive(Event)]
+pub struct UnloadGameLevel;
+
+/// Unloads the current level, if there is one, and loads another in the same tick.
+#[derive(Event)]
+pub struct SwitchGameLevel {
+    pub level_scene: String,
+}
+
+/// Sent once the scene of a [LoadGameLevel] has been spawned.
+#[derive(Event)]
+pub struct GameLevelLoaded {
+    pub level_entity: Entity,
+}
+
+/// Sent when a level has been unloaded so that anything relating to it can be reset.
+#[derive(Event)]
+pub struct GameLevelUnloaded;
+
+/// The root of a loaded level.
+///
+/// Every entity in the level is a descendant of this entity and is despawned with it.
 #[derive(Component)]
 pub struct GameLevelRoot;
 
+fn switch_levels(
+    mut switch_level_r: EventReader<SwitchGameLevel>,
+    mut unload_level_w: EventWriter<UnloadGameLevel>,
+    mut load_level_w: EventWriter<LoadGameLevel>,
+) {
+    for SwitchGameLevel { level_scene } in switch_level_r.read() {
+        info!("Switching to level \"{}\"", level_scene);
+
+        unload_level_w.write(UnloadGameLevel);
+        load_level_w.write(LoadGameLevel {
+            level_scene: level_scene.clone(),
+        });
+    }
+}
+
+fn unload_levels(
+    mut commands: Commands,
+    level_q: Query<Entity, With<GameLevelRoot>>,
+    mut unload_level_r: EventReader<UnloadGameLevel>,
+    mut unloaded_w: EventWriter<GameLevelUnloaded>,
+) {
+    if unload_level_r.read().count() == 0 {
+        return;
+    }
+
+    if level_q.is_empty() {
+        debug!("Attempted to unload a level while none was loaded");
+        return;
+    }
+
+    for level_entity in level_q.iter() {
+        info!("Unloading level {}", level_entity);
+
+        // replicated entities in the level are removed from clients by relevancy
+        commands.entity(level_entity).despawn();
+    }
+
+    unloaded_w.write(GameLevelUnloaded);
+}
+
 fn load_levels(
     mut commands: Commands,
     level_q: Query<(), With<GameLevelRoot>>,
@@ -28,14 +102,11 @@ fn load_levels(
 ) {
     let mut consecutive = false;
 
-    for LoadGameLevel {
-        level_scene: level_name,
-    } in load_level_r.read()
-    {
+    for LoadGameLevel { level_scene } in load_level_r.read() {
         if !level_q.is_empty() || consecutive {
             error!(
                 "Attempted to load level \"{}\" while another level was already loaded",
-                level_name
+                level_scene
             );
 
             continue;
@@ -44,25 +115,23 @@ fn load_levels(
         consecutive = true;
 
         let mut path = PathBuf::from("levels");
-        path.push("level_name");
+        path.push(level_scene);
 
-        info!("Loading level at \"{:?}\"", path);
+        info!("Loading level at {:?}", path);
 
         let scene = asset_server.load(path);
 
-        commands.spawn((GameLevelRoot, DynamicSceneRoot(scene)));
+        commands
+            .spawn((GameLevelRoot, DynamicSceneRoot(scene)))
+            .observe(send_level_loaded);
     }
 }
 
-fn debug_start_level(
-    mut client_q: Query<&mut ReceivedMessages<DebugStartLevel>>,
-    mut load_level_w: EventWriter<LoadGameLevel>,
+fn send_level_loaded(
+    trigger: Trigger<SceneInstanceReady>,
+    mut loaded_w: EventWriter<GameLevelLoaded>,
 ) {
-    for mut messages in client_q.iter_mut() {
-        for DebugStartLevel in messages.drain() {
-            load_level_w.write(LoadGameLevel {
-                level_scene: "bank.scn.ron".into(),
-            });
-        }
-    }
+    loaded_w.write(GameLevelLoaded {
+        level_entity: trigger.target(),
+    });
 }
@@ -0,0 +1,56 @@
+//! The game server, run by the `server` binary or in-process by the editor to play-test a level.
+
+use bevy::{gltf::GltfPlugin, prelude::*, render::mesh::MeshPlugin, scene::ScenePlugin};
+use common::CommonPlugin;
+
+pub mod agents;
+pub mod alarm;
+pub mod character;
+pub mod clock;
+pub mod config;
+pub mod elements;
+pub mod level;
+pub mod match_state;
+pub mod networking;
+pub mod physics_replication;
+pub mod relevancy;
+pub mod replication;
+pub mod state;
+
+/// Adds everything the server needs except logging.
+///
+/// A [ServerConfig](config::ServerConfig) has to be inserted.
+pub fn build(app: &mut App) {
+    app.add_plugins((
+        MinimalPlugins,
+        AssetPlugin {
+            file_path: "../../assets".into(),
+            ..default()
+        },
+        MeshPlugin,
+        ScenePlugin,
+        bevy::state::app::StatesPlugin,
+    ));
+
+    app.init_asset::<Shader>();
+
+    app.add_plugins((
+        GltfPlugin::default(),
+        MaterialPlugin::<StandardMaterial>::default(),
+    ));
+
+    app.add_plugins(CommonPlugin);
+
+    networking::build(app);
+    state::build(app);
+    clock::build(app);
+    physics_replication::build(app);
+    character::build(app);
+    agents::build(app);
+    level::build(app);
+    match_state::build(app);
+    alarm::build(app);
+    elements::build(app);
+    relevancy::build(app);
+    replication::build(app);
+}
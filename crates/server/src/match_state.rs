@@ -0,0 +1,306 @@
+//! The lifecycle of a match, from readying up in the lobby to the results.
+//!
+//! Every change of [MatchState] is sent to clients so they can follow along.
+
+use std::time::Duration;
+
+use avian3d::prelude::*;
+use bevy::prelude::*;
+use common::{
+    level::ExtractionZone,
+    match_state::{LobbyPlayer, LobbyPlayers, MatchState, SetMatchState, SetReady, StartMatch},
+    networking::StreamHeader,
+};
+use nevy::*;
+
+use crate::{
+    character::CharacterOfClient,
+    config::ServerConfig,
+    level::{GameLevelLoaded, GameLevelRoot, LoadGameLevel, UnloadGameLevel},
+    state::{JoinedClient, Sessions},
+};
+
+const BRIEFING_DURATION: Duration = Duration::from_secs(10);
+/// How long the outcome of a heist is shown before the results.
+const OUTCOME_DURATION: Duration = Duration::from_secs(5);
+const RESULTS_DURATION: Duration = Duration::from_secs(15);
+
+pub fn build(app: &mut App) {
+    app.init_state::<MatchState>();
+
+    app.add_systems(
+        Update,
+        (
+            assign_host,
+            receive_ready,
+            receive_start_match.run_if(in_state(MatchState::Lobby)),
+            finish_loading.run_if(in_state(MatchState::Loading)),
+            (fail_abandoned_match, extract_crew).run_if(in_state(MatchState::InProgress)),
+            advance_timed_phases,
+        )
+            .chain(),
+    );
+
+    app.add_systems(
+        OnEnter(MatchState::Lobby),
+        (reset_ready, unload_match_level),
+    );
+    app.add_systems(OnEnter(MatchState::Loading), load_match_level);
+
+    app.add_systems(
+        PostUpdate,
+        (send_match_state, send_lobby_players).before(UpdateEndpoints),
+    );
+}
+
+/// Marker component for the client that can start the match.
+///
+/// Given to the first joined client whenever nobody is the host.
+#[derive(Component)]
+pub struct Host;
+
+/// Whether a joined client is ready to start the match.
+#[derive(Component, Default)]
+pub struct Ready(pub bool);
+
+/// How long a phase lasts before automatically moving to the next one, if it does.
+fn timed_phase(state: MatchState) -> Option<(Duration, MatchState)> {
+    match state {
+        MatchState::Briefing => Some((BRIEFING_DURATION, MatchState::InProgress)),
+        MatchState::Extracted | MatchState::Failed => Some((OUTCOME_DURATION, MatchState::Results)),
+        MatchState::Results => Some((RESULTS_DURATION, MatchState::Lobby)),
+        MatchState::Lobby | MatchState::Loading | MatchState::InProgress => None,
+    }
+}
+
+fn assign_host(
+    mut commands: Commands,
+    host_q: Query<(), (With<Host>, With<JoinedClient>)>,
+    client_q: Query<(Entity, &JoinedClient)>,
+) {
+    if !host_q.is_empty() {
+        return;
+    }
+
+    let Some((client_entity, joined)) = client_q.iter().next() else {
+        return;
+    };
+
+    info!("\"{}\" is now the host", joined.username);
+
+    commands.entity(client_entity).insert(Host);
+}
+
+fn receive_ready(
+    mut client_q: Query<(Entity, &mut ReceivedMessages<SetReady>, Option<&mut Ready>)>,
+    state: Res<State<MatchState>>,
+) {
+    for (client_entity, mut messages, ready) in client_q.iter_mut() {
+        let Some(mut ready) = ready else {
+            for _ in messages.drain() {
+                warn!("Client {} tried to ready up before joining", client_entity);
+            }
+
+            continue;
+        };
+
+        for SetReady { ready: is_ready } in messages.drain() {
+            if *state.get() != MatchState::Lobby {
+                debug!(
+                    "Client {} tried to ready up outside of the lobby",
+                    client_entity
+                );
+
+                continue;
+            }
+
+            ready.0 = is_ready;
+        }
+    }
+}
+
+fn receive_start_match(
+    mut client_q: Query<(Entity, &mut ReceivedMessages<StartMatch>, Has<Host>)>,
+    ready_q: Query<&Ready, With<JoinedClient>>,
+    mut next_state: ResMut<NextState<MatchState>>,
+) {
+    for (client_entity, mut messages, host) in client_q.iter_mut() {
+        for StartMatch in messages.drain() {
+            if !host {
+                warn!(
+                    "Client {} tried to start the match without being the host",
+                    client_entity
+                );
+
+                continue;
+            }
+
+            if !ready_q.iter().all(|ready| ready.0) {
+                info!("Host tried to start the match before everyone was ready");
+
+                continue;
+            }
+
+            info!("Starting match");
+
+            next_state.set(MatchState::Loading);
+        }
+    }
+}
+
+fn unload_match_level(mut unload_level_w: EventWriter<UnloadGameLevel>) {
+    unload_level_w.write(UnloadGameLevel);
+}
+
+fn load_match_level(mut load_level_w: EventWriter<LoadGameLevel>, config: Res<ServerConfig>) {
+    load_level_w.write(LoadGameLevel {
+        level_scene: config.level_scene.clone(),
+    });
+}
+
+/// Moves to the briefing once the level has spawned, or back to the lobby if it failed to load.
+fn finish_loading(
+    mut loaded_r: EventReader<GameLevelLoaded>,
+    level_q: Query<&DynamicSceneRoot, With<GameLevelRoot>>,
+    asset_server: Res<AssetServer>,
+    mut next_state: ResMut<NextState<MatchState>>,
+) {
+    if loaded_r.read().count() > 0 {
+        next_state.set(MatchState::Briefing);
+        return;
+    }
+
+    for DynamicSceneRoot(scene) in level_q.iter() {
+        if asset_server.load_state(scene).is_failed() {
+            error!("Failed to load the level, returning to the lobby");
+
+            // the level is unloaded when entering the lobby
+            next_state.set(MatchState::Lobby);
+        }
+    }
+}
+
+/// Fails the match once every player has left and can't rejoin.
+fn fail_abandoned_match(sessions: Res<Sessions>, mut next_state: ResMut<NextState<MatchState>>) {
+    if sessions.is_empty() {
+        info!("Every player left the match");
+
+        next_state.set(MatchState::Failed);
+    }
+}
+
+/// Succeeds the match once every connected player's character is in an [ExtractionZone].
+fn extract_crew(
+    character_q: Query<&Position, With<CharacterOfClient>>,
+    zone_q: Query<(&GlobalTransform, &ExtractionZone)>,
+    mut next_state: ResMut<NextState<MatchState>>,
+) {
+    if character_q.is_empty() {
+        return;
+    }
+
+    let extracted = character_q.iter().all(|&Position(position)| {
+        zone_q
+            .iter()
+            .any(|(zone_transform, zone)| zone.contains(zone_transform, position))
+    });
+
+    if extracted {
+        info!("The crew extracted");
+
+        next_state.set(MatchState::Extracted);
+    }
+}
+
+fn advance_timed_phases(
+    state: Res<State<MatchState>>,
+    mut next_state: ResMut<NextState<MatchState>>,
+    time: Res<Time>,
+    mut timer: Local<Timer>,
+) {
+    let Some((duration, next)) = timed_phase(*state.get()) else {
+        return;
+    };
+
+    if state.is_changed() {
+        *timer = Timer::new(duration, TimerMode::Once);
+    }
+
+    if timer.tick(time.delta()).just_finished() {
+        next_state.set(next);
+    }
+}
+
+fn reset_ready(mut ready_q: Query<&mut Ready>) {
+    for mut ready in ready_q.iter_mut() {
+        ready.0 = false;
+    }
+}
+
+/// Sends the [MatchState] to every client when it changes and to clients when they join.
+fn send_match_state(
+    client_q: Query<(Entity, Ref<JoinedClient>)>,
+    state: Res<State<MatchState>>,
+    mut messages: LocalMessageSender,
+    message_id: Res<MessageId<SetMatchState>>,
+) -> Result {
+    messages.flush()?;
+
+    for (client_entity, joined) in client_q.iter() {
+        if !state.is_changed() && !joined.is_added() {
+            continue;
+        }
+
+        messages.write(
+            StreamHeader::Messages,
+            client_entity,
+            *message_id,
+            true,
+            &SetMatchState {
+                state: *state.get(),
+            },
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Sends the [LobbyPlayers] to every client when a player joins, leaves or readies up.
+fn send_lobby_players(
+    client_q: Query<(Entity, &JoinedClient, &Ready, Has<Host>)>,
+    changed_q: Query<(), (With<JoinedClient>, Or<(Changed<Ready>, Added<Host>)>)>,
+    mut removed_clients: RemovedComponents<JoinedClient>,
+    mut messages: LocalMessageSender,
+    message_id: Res<MessageId<LobbyPlayers>>,
+) -> Result {
+    messages.flush()?;
+
+    let clients_left = removed_clients.read().count() > 0;
+
+    if changed_q.is_empty() && !clients_left {
+        return Ok(());
+    }
+
+    let lobby_players = LobbyPlayers {
+        players: client_q
+            .iter()
+            .map(|(_, joined, ready, host)| LobbyPlayer {
+                username: joined.username.clone(),
+                ready: ready.0,
+                host,
+            })
+            .collect(),
+    };
+
+    for (client_entity, _, _, _) in client_q.iter() {
+        messages.write(
+            StreamHeader::Messages,
+            client_entity,
+            *message_id,
+            true,
+            &lobby_players,
+        )?;
+    }
+
+    Ok(())
+}
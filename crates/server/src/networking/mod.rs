@@ -1,7 +1,9 @@
-use std::net::{Ipv4Addr, SocketAddrV4};
+use std::{io::Write, net::SocketAddrV4, path::Path};
 
 use bevy::prelude::*;
+use common::networking::fingerprint::CertificateFingerprint;
 use nevy::*;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer, pem::PemObject};
 
 use crate::config::ServerConfig;
 
@@ -18,9 +20,9 @@ fn spawn_endpoint(mut commands: Commands, config: Res<ServerConfig>) -> Result {
         EndpointWithHeaderedConnections,
         EndpointWithMessageConnections,
         QuicEndpoint::new(
-            SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, config.bind_port),
+            SocketAddrV4::new(config.bind_address, config.bind_port),
             quinn_proto::EndpointConfig::default(),
-            Some(create_server_endpoint_config()),
+            Some(create_server_endpoint_config(&config)?),
             AlwaysAcceptIncoming::new(),
         )?,
     ));
@@ -28,25 +30,88 @@ fn spawn_endpoint(mut commands: Commands, config: Res<ServerConfig>) -> Result {
     Ok(())
 }
 
-fn create_server_endpoint_config() -> nevy::quinn_proto::ServerConfig {
-    let cert = rcgen::generate_simple_self_signed(vec!["dev.nevy".to_string()]).unwrap();
-    let key = rustls::pki_types::PrivateKeyDer::try_from(cert.key_pair.serialize_der()).unwrap();
-    let chain = cert.cert.der().clone();
+/// Loads the server's certificate chain and private key,
+/// or generates and saves a self signed certificate if they don't exist.
+///
+/// The first certificate in the chain is the server's own.
+pub fn load_or_create_certificate(
+    config: &ServerConfig,
+) -> Result<(Vec<CertificateDer<'static>>, PrivateKeyDer<'static>)> {
+    if config.certificate_path.exists() && config.private_key_path.exists() {
+        let chain = CertificateDer::pem_file_iter(&config.certificate_path)?
+            .collect::<Result<Vec<_>, _>>()?;
+        let private_key = PrivateKeyDer::from_pem_file(&config.private_key_path)?;
+
+        if chain.is_empty() {
+            return Err(format!("No certificates in {}", config.certificate_path.display()).into());
+        }
+
+        info!(
+            "Loaded certificate from {}",
+            config.certificate_path.display()
+        );
+
+        return Ok((chain, private_key));
+    }
+
+    let certified_key = rcgen::generate_simple_self_signed(vec![config.server_name.clone()])?;
+
+    std::fs::write(&config.certificate_path, certified_key.cert.pem())?;
+    write_private_key(
+        &config.private_key_path,
+        certified_key.key_pair.serialize_pem(),
+    )?;
+
+    info!(
+        "Generated a self signed certificate for \"{}\" and saved it to {}",
+        config.server_name,
+        config.certificate_path.display()
+    );
+
+    Ok((
+        vec![certified_key.cert.der().clone()],
+        PrivatePkcs8KeyDer::from(certified_key.key_pair.serialize_der()).into(),
+    ))
+}
+
+/// Writes the private key so that only the server's user can read it.
+fn write_private_key(path: &Path, pem: String) -> std::io::Result<()> {
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+
+    let mut file = options.open(path)?;
+
+    // an existing file keeps it's permissions when opened
+    #[cfg(unix)]
+    file.set_permissions(std::os::unix::fs::PermissionsExt::from_mode(0o600))?;
+
+    file.write_all(pem.as_bytes())
+}
+
+fn create_server_endpoint_config(config: &ServerConfig) -> Result<nevy::quinn_proto::ServerConfig> {
+    let (chain, key) = load_or_create_certificate(config)?;
+
+    // clients pin the server's own certificate to verify self signed certificates
+    info!(
+        "Certificate fingerprint: {}",
+        CertificateFingerprint::of(&chain[0])
+    );
 
     let mut tls_config = rustls::ServerConfig::builder_with_provider(std::sync::Arc::new(
         rustls::crypto::ring::default_provider(),
     ))
-    .with_protocol_versions(&[&rustls::version::TLS13])
-    .unwrap()
+    .with_protocol_versions(&[&rustls::version::TLS13])?
     .with_no_client_auth()
-    .with_single_cert(vec![chain], key)
-    .unwrap();
+    .with_single_cert(chain, key)?;
 
     tls_config.max_early_data_size = u32::MAX;
     tls_config.alpn_protocols = vec![b"h3".to_vec()]; // this one is important
 
     let quic_tls_config =
-        nevy::quinn_proto::crypto::rustls::QuicServerConfig::try_from(tls_config).unwrap();
+        nevy::quinn_proto::crypto::rustls::QuicServerConfig::try_from(tls_config)?;
 
     let mut server_config =
         nevy::quinn_proto::ServerConfig::with_crypto(std::sync::Arc::new(quic_tls_config));
@@ -57,5 +122,5 @@ fn create_server_endpoint_config() -> nevy::quinn_proto::ServerConfig {
 
     server_config.transport = std::sync::Arc::new(transport_config);
 
-    server_config
+    Ok(server_config)
 }
@@ -1,96 +1,196 @@
-use std::time::Duration;
+use std::{collections::VecDeque, time::Duration};
 
 use avian3d::prelude::*;
-use bevy::prelude::*;
+use bevy::{ecs::entity::EntityHashMap, prelude::*};
 use nevy::*;
 
 use common::{networking::StreamHeader, physics::*};
 
-use crate::state::JoinedClient;
+use crate::{
+    relevancy::{ClientRelevancy, Replicated},
+    state::JoinedClient,
+};
 
 pub const SNAPSHOT_INTERVAL: Duration = Duration::from_millis(150);
-pub const TIME_SAMPLE_INTERVAL: Duration = Duration::from_millis(100);
 
 pub fn build(app: &mut App) {
-    app.add_systems(Update, (send_time_samples, send_physics_snapshots));
+    app.add_systems(
+        Update,
+        (
+            insert_snapshot_baselines,
+            receive_snapshot_acks,
+            send_physics_snapshots,
+        )
+            .chain(),
+    );
 }
 
 /// Marker component for which physics bodies to replicate.
 #[derive(Component)]
+#[require(Replicated)]
 pub struct ReplicateBody;
 
-fn send_time_samples(
-    client_q: Query<Entity, With<JoinedClient>>,
-    mut messages: LocalMessageSender,
-    message_id: Res<MessageId<TimeSample>>,
-    time: Res<Time>,
-    mut last_sample: Local<Duration>,
-) -> Result {
-    if time.elapsed() > *last_sample + TIME_SAMPLE_INTERVAL {
-        *last_sample = time.elapsed();
-
-        for client_entity in client_q.iter() {
-            // if out of bandwidth don't send
-            messages.write(
-                StreamHeader::Messages,
-                client_entity,
-                *message_id,
-                false,
-                &TimeSample {
-                    time: time.elapsed(),
-                },
-            )?;
-        }
+/// The snapshots sent to a client that can be used as baselines for delta compression.
+#[derive(Component, Default)]
+pub struct SnapshotBaselines {
+    next_sequence: u32,
+    /// Sent snapshots that are newer than or equal to the acknowledged snapshot.
+    sent: VecDeque<(u32, EntityHashMap<QuantizedBody>)>,
+    /// The latest snapshot the client has acknowledged.
+    acknowledged: Option<u32>,
+}
+
+impl SnapshotBaselines {
+    fn get(&self, sequence: u32) -> Option<&EntityHashMap<QuantizedBody>> {
+        self.sent
+            .iter()
+            .find(|(sent_sequence, _)| *sent_sequence == sequence)
+            .map(|(_, bodies)| bodies)
     }
+}
 
-    Ok(())
+fn insert_snapshot_baselines(mut commands: Commands, client_q: Query<Entity, Added<JoinedClient>>) {
+    for client_entity in client_q.iter() {
+        commands
+            .entity(client_entity)
+            .insert(SnapshotBaselines::default());
+    }
+}
+
+fn receive_snapshot_acks(
+    mut client_q: Query<(
+        Entity,
+        &mut ReceivedMessages<PhysicsSnapshotAck>,
+        Option<&mut SnapshotBaselines>,
+    )>,
+) {
+    for (client_entity, mut messages, baselines) in client_q.iter_mut() {
+        let Some(mut baselines) = baselines else {
+            for _ in messages.drain() {
+                warn!(
+                    "Client {} acknowledged a snapshot before joining",
+                    client_entity
+                );
+            }
+
+            continue;
+        };
+
+        for PhysicsSnapshotAck { sequence } in messages.drain() {
+            if baselines
+                .acknowledged
+                .is_some_and(|acknowledged| sequence <= acknowledged)
+            {
+                continue;
+            }
+
+            if baselines.get(sequence).is_none() {
+                continue;
+            }
+
+            baselines.acknowledged = Some(sequence);
+
+            // older snapshots will never be used as a baseline again
+            while baselines
+                .sent
+                .front()
+                .is_some_and(|&(sent_sequence, _)| sent_sequence < sequence)
+            {
+                baselines.sent.pop_front();
+            }
+        }
+    }
 }
 
 fn send_physics_snapshots(
     body_q: Query<(Entity, &Position, &LinearVelocity, &Rotation), With<ReplicateBody>>,
-    client_q: Query<Entity, With<JoinedClient>>,
+    mut client_q: Query<(Entity, &mut SnapshotBaselines, &ClientRelevancy), With<JoinedClient>>,
     mut messages: LocalMessageSender,
     message_id: Res<MessageId<PhysicsSnapshot>>,
     time: Res<Time>,
     mut last_snapshot: Local<Duration>,
 ) -> Result {
-    if time.elapsed() > *last_snapshot + SNAPSHOT_INTERVAL {
-        *last_snapshot = time.elapsed();
-
-        let snapshot = PhysicsSnapshot {
-            bodies: body_q
-                .iter()
-                .map(
-                    |(
-                        body_entity,
-                        &Position(position),
-                        &LinearVelocity(linear_velocity),
-                        &Rotation(rotation),
-                    )| {
-                        (
-                            body_entity.into(),
-                            PhysicsBodySnapshot {
-                                position,
-                                linear_velocity,
-                                rotation,
-                            },
-                        )
-                    },
+    if time.elapsed() <= *last_snapshot + SNAPSHOT_INTERVAL {
+        return Ok(());
+    }
+
+    *last_snapshot = time.elapsed();
+
+    let bodies: EntityHashMap<QuantizedBody> = body_q
+        .iter()
+        .map(
+            |(
+                body_entity,
+                &Position(position),
+                &LinearVelocity(linear_velocity),
+                &Rotation(rotation),
+            )| {
+                (
+                    body_entity,
+                    QuantizedBody::new(position, linear_velocity, rotation),
                 )
-                .collect(),
+            },
+        )
+        .collect();
+
+    for (client_entity, mut baselines, relevancy) in client_q.iter_mut() {
+        let sequence = baselines.next_sequence;
+        baselines.next_sequence = sequence.wrapping_add(1);
+
+        let baseline = baselines
+            .acknowledged
+            .and_then(|acknowledged| Some((acknowledged, baselines.get(acknowledged)?)));
+
+        let mut snapshot = PhysicsSnapshot {
+            sequence,
+            baseline: baseline.map(|(acknowledged, _)| acknowledged),
             time: time.elapsed(),
+            bodies: Vec::new(),
+            removed: Vec::new(),
         };
 
-        for client_entity in client_q.iter() {
-            // if out of bandwidth don't send
-            messages.write(
-                StreamHeader::Messages,
-                client_entity,
-                *message_id,
-                false,
-                &snapshot,
-            )?;
+        // only send the bodies this client knows about
+        let bodies: EntityHashMap<QuantizedBody> = bodies
+            .iter()
+            .filter(|&(&body_entity, _)| relevancy.is_relevant(body_entity))
+            .map(|(&body_entity, &body)| (body_entity, body))
+            .collect();
+
+        for (&body_entity, body) in bodies.iter() {
+            let baseline_body = baseline.and_then(|(_, baseline)| baseline.get(&body_entity));
+
+            // bodies at rest don't need to be sent again
+            if baseline_body == Some(body) {
+                continue;
+            }
+
+            snapshot
+                .bodies
+                .push((body_entity.into(), body.encode(baseline_body)));
+        }
+
+        if let Some((_, baseline)) = baseline {
+            for &body_entity in baseline.keys() {
+                if !bodies.contains_key(&body_entity) {
+                    snapshot.removed.push(body_entity.into());
+                }
+            }
         }
+
+        baselines.sent.push_back((sequence, bodies));
+
+        if baselines.sent.len() > SNAPSHOT_HISTORY_LENGTH {
+            baselines.sent.pop_front();
+        }
+
+        // if out of bandwidth don't send
+        messages.write(
+            StreamHeader::Messages,
+            client_entity,
+            *message_id,
+            false,
+            &snapshot,
+        )?;
     }
 
     Ok(())
@@ -0,0 +1,205 @@
+//! Decides which replicated entities each client knows about.
+//!
+//! Entities only exist on a client while they are relevant to it.
+//! [InitializePairs](crate::state::initialize_pairs::InitializePairs) yields entities as they become relevant
+//! and a [ServerEntityRemoved] message is sent when they stop being relevant or are despawned.
+
+use avian3d::prelude::*;
+use bevy::{ecs::entity::EntityHashSet, prelude::*};
+use common::{
+    level::box_contains,
+    networking::{StreamHeader, replicate_despawn::ServerEntityRemoved},
+};
+use nevy::*;
+
+use crate::{character::ClientOfCharacter, state::JoinedClient};
+
+/// Entities within this distance of a client's character are relevant to it.
+const RELEVANCY_RANGE: f32 = 40.;
+/// How much further than [RELEVANCY_RANGE] a relevant entity has to move before it stops being relevant.
+///
+/// Stops entities on the edge of the range from being repeatedly spawned and despawned.
+const RELEVANCY_HYSTERESIS: f32 = 5.;
+
+pub fn build(app: &mut App) {
+    app.add_systems(
+        PostUpdate,
+        (update_relevancy, send_removed_entities)
+            .chain()
+            .in_set(UpdateRelevancy)
+            .before(UpdateEndpoints),
+    );
+}
+
+/// System set that updates every [ClientRelevancy] in [PostUpdate].
+///
+/// Systems that send messages about relevant entities should run after this.
+#[derive(SystemSet, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct UpdateRelevancy;
+
+/// Marker component for entities that are replicated to clients.
+#[derive(Component, Default)]
+pub struct Replicated;
+
+/// Replicated entities with this component are relevant to every client, such as level geometry.
+#[derive(Component, Default)]
+#[require(Replicated)]
+pub struct AlwaysRelevant;
+
+/// A box shaped area such as a room or a floor of a building.
+///
+/// If a client's character and an entity are both inside zones
+/// the entity is only relevant if they share a zone, no matter how close they are.
+/// Otherwise relevancy is decided by distance.
+#[derive(Component)]
+#[require(Transform)]
+pub struct RelevancyZone {
+    pub half_extents: Vec3,
+}
+
+/// The replicated entities that are relevant to a client.
+#[derive(Component, Default)]
+pub struct ClientRelevancy {
+    relevant: EntityHashSet,
+    /// Entities that became relevant this tick.
+    added: Vec<Entity>,
+    /// Entities that stopped being relevant this tick, including despawned entities.
+    removed: Vec<Entity>,
+}
+
+impl ClientRelevancy {
+    pub fn is_relevant(&self, entity: Entity) -> bool {
+        self.relevant.contains(&entity)
+    }
+
+    /// Entities that became relevant this tick.
+    pub fn added(&self) -> &[Entity] {
+        &self.added
+    }
+
+    /// Entities that stopped being relevant this tick.
+    pub fn removed(&self) -> &[Entity] {
+        &self.removed
+    }
+}
+
+fn update_relevancy(
+    mut client_q: Query<(&mut ClientRelevancy, Option<&ClientOfCharacter>), With<JoinedClient>>,
+    replicated_q: Query<
+        (
+            Entity,
+            Option<&Position>,
+            Option<&GlobalTransform>,
+            Has<AlwaysRelevant>,
+        ),
+        With<Replicated>,
+    >,
+    zone_q: Query<(Entity, &RelevancyZone, &GlobalTransform)>,
+) {
+    let entity_position = |position: Option<&Position>, transform: Option<&GlobalTransform>| {
+        position
+            .map(|position| position.0)
+            .or(transform.map(|transform| transform.translation()))
+    };
+
+    let zones_containing = |point: Vec3| -> Vec<Entity> {
+        zone_q
+            .iter()
+            .filter(|(_, zone, transform)| box_contains(transform, zone.half_extents, point))
+            .map(|(zone_entity, _, _)| zone_entity)
+            .collect()
+    };
+
+    for (mut relevancy, character_of) in client_q.iter_mut() {
+        let relevancy = &mut *relevancy;
+
+        relevancy.added.clear();
+        relevancy.removed.clear();
+
+        let character_entity = character_of.map(|character_of| **character_of);
+
+        let viewer = character_entity.and_then(|character_entity| {
+            let (_, position, transform, _) = replicated_q.get(character_entity).ok()?;
+            let position = entity_position(position, transform)?;
+
+            Some((position, zones_containing(position)))
+        });
+
+        let mut relevant = EntityHashSet::default();
+
+        for (entity, position, transform, always_relevant) in replicated_q.iter() {
+            let is_relevant = 'relevant: {
+                // a client's own character is always relevant to it
+                if always_relevant || character_entity == Some(entity) {
+                    break 'relevant true;
+                }
+
+                let Some((viewer_position, viewer_zones)) = &viewer else {
+                    break 'relevant false;
+                };
+
+                // entities without a position can't be out of range
+                let Some(position) = entity_position(position, transform) else {
+                    break 'relevant true;
+                };
+
+                let zones = zones_containing(position);
+
+                if !viewer_zones.is_empty() && !zones.is_empty() {
+                    break 'relevant zones.iter().any(|zone| viewer_zones.contains(zone));
+                }
+
+                let range = if relevancy.relevant.contains(&entity) {
+                    RELEVANCY_RANGE + RELEVANCY_HYSTERESIS
+                } else {
+                    RELEVANCY_RANGE
+                };
+
+                position.distance(*viewer_position) <= range
+            };
+
+            if is_relevant {
+                relevant.insert(entity);
+            }
+        }
+
+        for &entity in relevant.iter() {
+            if !relevancy.relevant.contains(&entity) {
+                relevancy.added.push(entity);
+            }
+        }
+
+        for &entity in relevancy.relevant.iter() {
+            if !relevant.contains(&entity) {
+                relevancy.removed.push(entity);
+            }
+        }
+
+        relevancy.relevant = relevant;
+    }
+}
+
+/// Sends a [ServerEntityRemoved] message to clients for entities that stopped being relevant to them.
+fn send_removed_entities(
+    client_q: Query<(Entity, &ClientRelevancy), With<JoinedClient>>,
+    mut messages: LocalMessageSender,
+    message_id: Res<MessageId<ServerEntityRemoved>>,
+) -> Result {
+    messages.flush()?;
+
+    for (client_entity, relevancy) in client_q.iter() {
+        for &removed_entity in relevancy.removed() {
+            messages.write(
+                StreamHeader::Messages,
+                client_entity,
+                *message_id,
+                true,
+                &ServerEntityRemoved {
+                    entity: removed_entity.into(),
+                },
+            )?;
+        }
+    }
+
+    Ok(())
+}
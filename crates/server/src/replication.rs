@@ -0,0 +1,113 @@
+//! Server side of [component replication](common::networking::replicate_component).
+//!
+//! Replicated components are sent to a client when their entity becomes relevant to it,
+//! and then whenever they change or are removed while the entity is still relevant.
+
+use bevy::prelude::*;
+use common::networking::{
+    StreamHeader,
+    replicate_component::{
+        RemoveComponent, ReplicateComponents, ReplicatedComponent, UpdateComponent,
+        replicated_components,
+    },
+};
+use nevy::*;
+
+use crate::{
+    relevancy::{ClientRelevancy, UpdateRelevancy},
+    state::{JoinedClient, initialize_pairs::InitializePairs},
+};
+
+pub fn build(app: &mut App) {
+    replicated_components(app, &mut ServerReplication);
+}
+
+/// System set in [PostUpdate] that sends replicated component updates to clients.
+///
+/// Messages that refer to replicated entities should be sent after this.
+#[derive(SystemSet, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct SendComponentUpdates;
+
+struct ServerReplication;
+
+impl ReplicateComponents for ServerReplication {
+    fn replicate<T: ReplicatedComponent>(&mut self, app: &mut App) {
+        app.add_systems(
+            PostUpdate,
+            send_component_updates::<T>
+                .in_set(SendComponentUpdates)
+                .after(UpdateRelevancy)
+                .before(UpdateEndpoints),
+        );
+    }
+}
+
+fn send_component_updates<T: ReplicatedComponent>(
+    pairs: InitializePairs<T>,
+    component_q: Query<(Entity, Ref<T>)>,
+    client_q: Query<(Entity, &ClientRelevancy), With<JoinedClient>>,
+    mut removed_components: RemovedComponents<T>,
+    mut messages: LocalMessageSender,
+    update_message_id: Res<MessageId<UpdateComponent<T>>>,
+    remove_message_id: Res<MessageId<RemoveComponent<T>>>,
+) -> Result {
+    messages.flush()?;
+
+    for (client_entity, entity) in pairs.iter() {
+        let (_, value) = component_q.get(entity)?;
+
+        messages.write(
+            StreamHeader::Messages,
+            client_entity,
+            *update_message_id,
+            true,
+            &UpdateComponent {
+                entity: entity.into(),
+                value: value.clone(),
+            },
+        )?;
+    }
+
+    for (entity, value) in component_q.iter() {
+        // added components were sent above
+        if !value.is_changed() || value.is_added() {
+            continue;
+        }
+
+        for (client_entity, relevancy) in client_q.iter() {
+            if !relevancy.is_relevant(entity) || relevancy.added().contains(&entity) {
+                continue;
+            }
+
+            messages.write(
+                StreamHeader::Messages,
+                client_entity,
+                *update_message_id,
+                true,
+                &UpdateComponent {
+                    entity: entity.into(),
+                    value: value.clone(),
+                },
+            )?;
+        }
+    }
+
+    for entity in removed_components.read() {
+        // despawned entities aren't relevant anymore and are removed by relevancy instead
+        for (client_entity, relevancy) in client_q.iter() {
+            if !relevancy.is_relevant(entity) {
+                continue;
+            }
+
+            messages.write(
+                StreamHeader::Messages,
+                client_entity,
+                *remove_message_id,
+                true,
+                &RemoveComponent::<T>::new(entity.into()),
+            )?;
+        }
+    }
+
+    Ok(())
+}
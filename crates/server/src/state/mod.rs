@@ -1,35 +1,208 @@
-use bevy::prelude::*;
-use common::state::JoinGameRequest;
+use std::time::Duration;
+
+use bevy::{platform::collections::HashMap, prelude::*};
+use common::{
+    networking::StreamHeader,
+    state::{
+        JoinGameRequest, JoinGameResponse, JoinRejectReason, PROTOCOL_VERSION, SessionToken,
+        is_valid_username,
+    },
+};
 use nevy::*;
 
+use crate::{
+    config::ServerConfig,
+    match_state::{Host, Ready},
+    relevancy::ClientRelevancy,
+};
+
 pub mod initialize_pairs;
 
+/// How long a disconnected player's session, and their character, is kept for them to rejoin.
+const SESSION_TIMEOUT: Duration = Duration::from_secs(60);
+
 pub fn build(app: &mut App) {
-    app.add_systems(PreUpdate, (accept_join_requests, remove_closed_clients));
+    app.init_resource::<Sessions>();
+
+    app.add_systems(
+        PreUpdate,
+        (accept_join_requests, remove_closed_clients, expire_sessions).chain(),
+    );
 }
 
-/// Marker component for connection entities that have joined the game and should receive game updates.
+/// Component for connection entities that have joined the game and should receive game updates.
 #[derive(Component)]
-pub struct JoinedClient;
+#[require(ClientRelevancy, Ready)]
+pub struct JoinedClient {
+    pub username: String,
+    pub session_token: SessionToken,
+}
+
+/// Every player that has joined, including ones that have disconnected but can still rejoin.
+#[derive(Resource, Default)]
+pub struct Sessions {
+    sessions: HashMap<SessionToken, Session>,
+}
+
+pub struct Session {
+    pub username: String,
+    /// The connection entity, if the player is connected.
+    pub client: Option<Entity>,
+    /// When the player disconnected.
+    disconnected_at: Option<Duration>,
+}
+
+impl Sessions {
+    pub fn get(&self, session_token: SessionToken) -> Option<&Session> {
+        self.sessions.get(&session_token)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sessions.is_empty()
+    }
+
+    fn is_username_taken(&self, username: &str) -> bool {
+        self.sessions
+            .values()
+            .any(|session| session.username.eq_ignore_ascii_case(username))
+    }
+}
 
 fn accept_join_requests(
     mut commands: Commands,
-    mut connection_q: Query<(Entity, &mut ReceivedMessages<JoinGameRequest>)>,
-) {
-    for (connection_entity, mut messages) in connection_q.iter_mut() {
-        for JoinGameRequest { username } in messages.drain() {
-            info!(
-                "client {} joined game with username \"{}\"",
-                connection_entity, username
-            );
-
-            commands.entity(connection_entity).insert(JoinedClient);
+    mut connection_q: Query<(
+        Entity,
+        &mut ReceivedMessages<JoinGameRequest>,
+        Has<JoinedClient>,
+    )>,
+    host_q: Query<(), With<Host>>,
+    mut sessions: ResMut<Sessions>,
+    config: Res<ServerConfig>,
+    mut messages: LocalMessageSender,
+    message_id: Res<MessageId<JoinGameResponse>>,
+) -> Result {
+    for (connection_entity, mut requests, mut joined) in connection_q.iter_mut() {
+        for request in requests.drain() {
+            let response =
+                match join_session(&mut sessions, &config, connection_entity, joined, request) {
+                    Ok((session_token, username, replaced_connection)) => {
+                        info!(
+                            "client {} joined game with username \"{}\"",
+                            connection_entity, username
+                        );
+
+                        // the player reconnected before their old connection timed out,
+                        // which is left to close on it's own
+                        if let Some(replaced_connection) = replaced_connection {
+                            debug!(
+                                "client {} took over the session of {}",
+                                connection_entity, replaced_connection
+                            );
+
+                            commands
+                                .entity(replaced_connection)
+                                .remove::<(JoinedClient, Host)>();
+
+                            if host_q.contains(replaced_connection) {
+                                commands.entity(connection_entity).insert(Host);
+                            }
+                        }
+
+                        joined = true;
+
+                        commands.entity(connection_entity).insert(JoinedClient {
+                            username,
+                            session_token,
+                        });
+
+                        JoinGameResponse::Accepted { session_token }
+                    }
+                    Err(reason) => {
+                        warn!(
+                            "rejected join request from {}: {}",
+                            connection_entity, reason
+                        );
+
+                        JoinGameResponse::Rejected { reason }
+                    }
+                };
+
+            messages.write(
+                StreamHeader::Messages,
+                connection_entity,
+                *message_id,
+                true,
+                &response,
+            )?;
         }
     }
+
+    Ok(())
 }
 
-fn remove_closed_clients(mut commands: Commands, connection_q: Query<(Entity, &ConnectionStatus)>) {
-    for (connection_entity, connection_status) in connection_q.iter() {
+/// Validates a join request and creates or resumes a session for it.
+///
+/// Resuming a session that is still connected takes it over from the old connection, which is returned.
+fn join_session(
+    sessions: &mut Sessions,
+    config: &ServerConfig,
+    connection_entity: Entity,
+    already_joined: bool,
+    request: JoinGameRequest,
+) -> Result<(SessionToken, String, Option<Entity>), JoinRejectReason> {
+    if already_joined {
+        return Err(JoinRejectReason::AlreadyJoined);
+    }
+
+    if request.protocol_version != PROTOCOL_VERSION {
+        return Err(JoinRejectReason::ProtocolMismatch {
+            server_version: PROTOCOL_VERSION,
+        });
+    }
+
+    if config.password.is_some() && request.password != config.password {
+        return Err(JoinRejectReason::WrongPassword);
+    }
+
+    // resume a session, the old connection may not have been noticed closing yet
+    if let Some(session_token) = request.session_token {
+        if let Some(session) = sessions.sessions.get_mut(&session_token) {
+            let replaced_connection = session.client.replace(connection_entity);
+            session.disconnected_at = None;
+
+            return Ok((session_token, session.username.clone(), replaced_connection));
+        }
+    }
+
+    if !is_valid_username(&request.username) {
+        return Err(JoinRejectReason::InvalidUsername);
+    }
+
+    if sessions.is_username_taken(&request.username) {
+        return Err(JoinRejectReason::UsernameTaken);
+    }
+
+    let session_token = SessionToken(rand::random());
+
+    sessions.sessions.insert(
+        session_token,
+        Session {
+            username: request.username.clone(),
+            client: Some(connection_entity),
+            disconnected_at: None,
+        },
+    );
+
+    Ok((session_token, request.username, None))
+}
+
+fn remove_closed_clients(
+    mut commands: Commands,
+    connection_q: Query<(Entity, &ConnectionStatus, Option<&JoinedClient>)>,
+    mut sessions: ResMut<Sessions>,
+    time: Res<Time>,
+) {
+    for (connection_entity, connection_status, joined) in connection_q.iter() {
         let ConnectionStatus::Closed { .. } = connection_status else {
             continue;
         };
@@ -37,5 +210,26 @@ fn remove_closed_clients(mut commands: Commands, connection_q: Query<(Entity, &C
         commands.entity(connection_entity).despawn();
 
         debug!("removed closed client {}", connection_entity);
+
+        if let Some(session) =
+            joined.and_then(|joined| sessions.sessions.get_mut(&joined.session_token))
+        {
+            session.client = None;
+            session.disconnected_at = Some(time.elapsed());
+        }
     }
 }
+
+fn expire_sessions(mut sessions: ResMut<Sessions>, time: Res<Time>) {
+    sessions.sessions.retain(|_, session| {
+        let expired = session
+            .disconnected_at
+            .is_some_and(|disconnected_at| time.elapsed() > disconnected_at + SESSION_TIMEOUT);
+
+        if expired {
+            debug!("session for \"{}\" expired", session.username);
+        }
+
+        !expired
+    });
+}